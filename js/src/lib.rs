@@ -3,7 +3,7 @@ mod utils;
 use std::{collections::HashMap, fmt::Display};
 
 use geo::GeometryCollection;
-use geo_weights::{Weights, QueensWeights,WeightBuilder};
+use geo_weights::{CompactWeights, Weights, QueensWeights, WeightsSpec};
 use geo_stats::lisa::lisa;
 use geojson::{GeoJson, quick_collection};
 use wasm_bindgen::prelude::*;
@@ -30,6 +30,10 @@ impl fmt::Display for WeightProxy{
     }
 }
 
+/// Holds weights in the compact u32 / f32 form for very large datasets
+#[wasm_bindgen]
+pub struct CompactWeightProxy(CompactWeights);
+
 impl Deref for WeightProxy {
     type Target = Weights;
 
@@ -72,6 +76,13 @@ impl WeightProxy{
         Ok(serde_wasm_bindgen::to_value(&self.0.no_elements())?)
     }
 
+    /// Converts to the compact representation, after which this proxy can be freed
+    #[wasm_bindgen]
+    pub fn compact(&self) -> Result<CompactWeightProxy, JsError>{
+        let compact = CompactWeights::from_weights(&self.0).map_err(|e| JsError::new(&e))?;
+        Ok(CompactWeightProxy(compact))
+    }
+
     #[wasm_bindgen]
     pub fn are_neighbors(&self, origin: usize, dest: usize) -> Result<JsValue,JsValue>{
         Ok(serde_wasm_bindgen::to_value(&self.0.are_neighbors(origin, dest))?)
@@ -87,27 +98,97 @@ impl WeightProxy{
     pub fn links_geojson(&self, geoms: JsValue) -> Result<JsValue,JsValue>{
         let geoms: GeoJson = serde_wasm_bindgen::from_value(geoms).unwrap();
         let geoms: GeometryCollection = quick_collection(&geoms).unwrap();
-        let fc = self.0.links_geojson(&geoms.0);
+        let fc = self.0.try_links_geojson(&geoms.0).map_err(JsValue::from)?;
+        let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+        Ok(fc.serialize(&serializer)?)
+    }
+
+    /// Unlike `links_geojson` the `origin` and `dest` properties are numbers and each feature
+    /// also has the `weight`
+    #[wasm_bindgen]
+    pub fn links_feature_collection(&self, geoms: JsValue, dedupe_symmetric: bool) -> Result<JsValue,JsValue>{
+        let geoms: GeoJson = serde_wasm_bindgen::from_value(geoms)?;
+        let geoms: GeometryCollection = quick_collection(&geoms).map_err(|e| JsValue::from(e.to_string()))?;
+        let fc = self.0.links_feature_collection(&geoms.0, dedupe_symmetric).map_err(JsValue::from)?;
         let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
         Ok(fc.serialize(&serializer)?)
     }
 }
 
 
+#[wasm_bindgen]
+impl CompactWeightProxy{
+
+    #[wasm_bindgen]
+    pub fn no_elements(&self) -> usize{
+        self.0.no_elements()
+    }
+
+    #[wasm_bindgen]
+    pub fn no_links(&self) -> usize{
+        self.0.no_links()
+    }
+}
+
 #[wasm_bindgen]
 pub fn calc_weights_from_geojson(geo_json: JsValue)->Result<WeightProxy, JsError>{
+    build_weights(geo_json, WeightsSpec::Queen(QueensWeights::new(10000.0)))
+}
+
+/// Builds weights from a spec such as `{"type": "knn", "k": 6, "metric": "haversine"}`
+#[wasm_bindgen]
+pub fn calc_weights_from_spec(geo_json: JsValue, spec: JsValue)->Result<WeightProxy, JsError>{
+    let spec: WeightsSpec<f64> = serde_wasm_bindgen::from_value(spec)
+                                    .map_err(|e| JsError::new(&format!("Invalid weights spec: {}", e)))?;
+    build_weights(geo_json, spec)
+}
+
+/// Builds queen weights straight into the compact form, without the intermediate hash maps
+/// used by `calc_weights_from_geojson`, so larger datasets fit in memory
+#[wasm_bindgen]
+pub fn calc_compact_weights_from_geojson(geo_json: JsValue)->Result<CompactWeightProxy, JsError>{
+    build_compact_weights(geo_json, WeightsSpec::Queen(QueensWeights::new(10000.0)))
+}
+
+/// Compact version of `calc_weights_from_spec`
+#[wasm_bindgen]
+pub fn calc_compact_weights_from_spec(geo_json: JsValue, spec: JsValue)->Result<CompactWeightProxy, JsError>{
+    let spec: WeightsSpec<f64> = serde_wasm_bindgen::from_value(spec)
+                                    .map_err(|e| JsError::new(&format!("Invalid weights spec: {}", e)))?;
+    build_compact_weights(geo_json, spec)
+}
+
+fn parse_geometries(geo_json: JsValue)->Result<GeometryCollection, JsError>{
     let geo_json:GeoJson = serde_wasm_bindgen::from_value(geo_json).unwrap(); 
-    let geom_collection : GeometryCollection = quick_collection(&geo_json)
-                                                .map_err(|_| JsError::new("Failed to parse geometry collection"))?;
-    let weights = QueensWeights::new(10000.0)
-                    .compute_weights(&geom_collection.0);
+    quick_collection(&geo_json).map_err(|_| JsError::new("Failed to parse geometry collection"))
+}
+
+fn build_weights(geo_json: JsValue, spec: WeightsSpec<f64>)->Result<WeightProxy, JsError>{
+    let geom_collection = parse_geometries(geo_json)?;
+    let weights = spec.build(&geom_collection.0)
+                    .map_err(|e| JsError::new(&e))?;
     Ok(WeightProxy(weights))
 }
 
+fn build_compact_weights(geo_json: JsValue, spec: WeightsSpec<f64>)->Result<CompactWeightProxy, JsError>{
+    let geom_collection = parse_geometries(geo_json)?;
+    let weights = spec.build_compact(&geom_collection.0)
+                    .map_err(|e| JsError::new(&e))?;
+    Ok(CompactWeightProxy(weights))
+}
+
 #[wasm_bindgen]
 pub fn calc_lisa(weights: &WeightProxy, values: JsValue)->Result<JsValue,JsValue>{
     let values : Vec<f64> = serde_wasm_bindgen::from_value(values)?;
-    let result = lisa(&*weights,&values,9999,false,geo_stats::lisa::PermutationMethod::LOOKUP)?;
+    let result = lisa(&weights.0,&values,9999,false,geo_stats::lisa::PermutationMethod::LOOKUP,None)?;
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+#[wasm_bindgen]
+pub fn calc_lisa_compact(weights: &CompactWeightProxy, values: JsValue)->Result<JsValue,JsValue>{
+    let values : Vec<f64> = serde_wasm_bindgen::from_value(values)?;
+    let result = lisa(&weights.0,&values,9999,false,geo_stats::lisa::PermutationMethod::LOOKUP,None)?;
 
     Ok(serde_wasm_bindgen::to_value(&result)?)
 }
@@ -0,0 +1,68 @@
+use crate::lisa::LISAResult;
+use arrow_array::types::Float64Type;
+use arrow_array::{Array, ArrayRef, Float64Array, ListArray, RecordBatch, StringArray, UInt8Array};
+use arrow_schema::{DataType, Field, Schema};
+use std::io::Write;
+use std::sync::Arc;
+
+impl LISAResult {
+    /// Returns the results as an Arrow record batch with one row per observation, in the same
+    /// order as the values, so it can be joined back on to the original table. The columns are
+    /// `moran_val`, `quad`, `lag` and `p_val`, followed by a `cluster` column with the GeoDa
+    /// cluster codes from `cluster_codes` when a significance level is given. A `sims` list
+    /// column is added when the simulations were kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `significance` - the p value at or below which an observation is significant, if the
+    ///   clusters should be included
+    ///
+    pub fn to_record_batch(&self, significance: Option<f64>) -> Result<RecordBatch, String> {
+        let mut fields = vec![
+            Field::new("moran_val", DataType::Float64, false),
+            Field::new("quad", DataType::Utf8, false),
+            Field::new("lag", DataType::Float64, false),
+            Field::new("p_val", DataType::Float64, false),
+        ];
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(Float64Array::from(self.moran_val.clone())),
+            Arc::new(StringArray::from_iter_values(
+                self.quads.iter().map(|q| format!("{:?}", q)),
+            )),
+            Arc::new(Float64Array::from(self.lags.clone())),
+            Arc::new(Float64Array::from(self.p_vals.clone())),
+        ];
+
+        if let Some(significance) = significance {
+            fields.push(Field::new("cluster", DataType::UInt8, false));
+            columns.push(Arc::new(UInt8Array::from(self.cluster_codes(significance))));
+        }
+
+        if self.sims.iter().any(|sims| !sims.is_empty()) {
+            let sims = ListArray::from_iter_primitive::<Float64Type, _, _>(
+                self.sims
+                    .iter()
+                    .map(|sims| Some(sims.iter().map(|s| Some(*s)))),
+            );
+            fields.push(Field::new("sims", sims.data_type().clone(), true));
+            columns.push(Arc::new(sims));
+        }
+
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(|e| e.to_string())
+    }
+
+    /// Writes the results to a Parquet file using the layout of `to_record_batch`
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - where to write the file
+    /// * `significance` - the significance level for the `cluster` column, if it's wanted
+    ///
+    pub fn write_parquet<W: Write + Send>(
+        &self,
+        writer: W,
+        significance: Option<f64>,
+    ) -> Result<(), String> {
+        geo_weights::write_parquet(&self.to_record_batch(significance)?, writer)
+    }
+}
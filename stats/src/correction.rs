@@ -0,0 +1,221 @@
+use crate::geary::LocalGearyResult;
+use crate::getis_ord::LocalGResult;
+use crate::join_counts::LocalJoinCountResult;
+use crate::lisa::LISAResult;
+use serde::Serialize;
+
+/// Specifies the correction for multiple testing to apply to the p values of a local statistic
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Correction {
+    /// Multiplies each p value by the number of tests, controlling the family wise error rate
+    Bonferroni,
+    /// The step down version of Bonferroni from Holm (1979), which is uniformly more powerful
+    Holm,
+    /// The step up false discovery rate procedure of Benjamini and Hochberg (1995)
+    BenjaminiHochberg,
+    /// The false discovery rate cutoff used by GeoDa, which stops at the first sorted p value
+    /// p_(k) above k * alpha / m rather than taking the last one below it
+    GeoDaFdr,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CorrectedPValues {
+    /// The correction that was applied
+    pub correction: Correction,
+    /// The significance level, or the false discovery rate for the FDR corrections
+    pub alpha: f64,
+    /// The adjusted p value of each observation, None where the observation has no p value.
+    /// GeoDaFdr only gives a cutoff so it has no adjusted p values and these are all None
+    pub p_vals: Vec<Option<f64>>,
+    /// The largest unadjusted p value that is significant after the correction, so
+    /// observations with an unadjusted p value at or below it are significant. None if no
+    /// observation is significant
+    pub cutoff: Option<f64>,
+    /// Whether each observation is significant at the given level after the correction, false
+    /// where the observation has no p value
+    pub significant: Vec<bool>,
+}
+
+/// Results of local statistics with a p value for each observation, which can be corrected
+/// for multiple testing as every observation is a separate test
+pub trait LocalPValues {
+    /// The p value of each observation, None where the statistic does not give one
+    fn local_p_values(&self) -> Vec<Option<f64>>;
+
+    /// Corrects the p values of the local statistic for multiple testing, see
+    /// `correct_p_values`
+    ///
+    /// # Arguments
+    ///
+    /// * `correction` - the correction to apply
+    /// * `alpha` - the significance level, or the false discovery rate for the FDR corrections
+    ///
+    fn corrected_p_values(
+        &self,
+        correction: Correction,
+        alpha: f64,
+    ) -> Result<CorrectedPValues, String> {
+        correct_p_values(&self.local_p_values(), correction, alpha)
+    }
+}
+
+/// Returns the index and value of each p value that is given, in ascending order of p value
+fn ascending_tests<P: Into<Option<f64>> + Copy>(p_vals: &[P]) -> Vec<(usize, f64)> {
+    let mut tests: Vec<(usize, f64)> = p_vals
+        .iter()
+        .enumerate()
+        .filter_map(|(index, p)| (*p).into().map(|p| (index, p)))
+        .collect();
+    tests.sort_by(|a, b| a.1.total_cmp(&b.1));
+    tests
+}
+
+/// Returns the GeoDa false discovery rate cutoff for the p values, the largest sorted p value
+/// p_(k) such that p_(j) <= j * alpha / m for every j up to k. Observations with a p value at
+/// or below the cutoff are significant. If even the smallest p value fails there is no cutoff.
+/// Missing p values are skipped and do not count towards the number of tests m.
+///
+/// # Arguments
+///
+/// * `p_vals` - the p value of each observation, either f64 or Option<f64>
+/// * `alpha` - the false discovery rate to control
+///
+pub fn geoda_fdr_cutoff<P: Into<Option<f64>> + Copy>(p_vals: &[P], alpha: f64) -> Option<f64> {
+    let tests = ascending_tests(p_vals);
+    let m = tests.len() as f64;
+    let mut cutoff = None;
+    for (rank, (_, p)) in tests.into_iter().enumerate() {
+        if p > (rank + 1) as f64 * alpha / m {
+            break;
+        }
+        cutoff = Some(p);
+    }
+    cutoff
+}
+
+/// Corrects the p values of a local statistic for multiple testing, as every observation is
+/// a separate test. Missing p values are skipped and do not count towards the number of tests.
+/// Returns a CorrectedPValues that contains
+/// - p_vals: the adjusted p value of each observation, except for GeoDaFdr
+/// - cutoff: the largest unadjusted p value that is still significant
+/// - significant: whether each observation is significant at the given level
+///
+/// # Arguments
+///
+/// * `p_vals` - the p value of each observation, either f64 such as the p_vals of a LISAResult
+///   or Option<f64> such as the p_sim of a LocalJoinCountResult
+/// * `correction` - the correction to apply
+/// * `alpha` - the significance level, or the false discovery rate for the FDR corrections
+///
+pub fn correct_p_values<P: Into<Option<f64>> + Copy>(
+    p_vals: &[P],
+    correction: Correction,
+    alpha: f64,
+) -> Result<CorrectedPValues, String> {
+    if !(0.0..=1.0).contains(&alpha) {
+        return Err(format!("alpha should be between 0 and 1 but got {}", alpha));
+    }
+    let tests = ascending_tests(p_vals);
+    if let Some((_, p)) = tests.iter().find(|(_, p)| !(0.0..=1.0).contains(p)) {
+        return Err(format!("p values should be between 0 and 1 but got {}", p));
+    }
+
+    let m = tests.len();
+    let mut adjusted: Vec<Option<f64>> = vec![None; p_vals.len()];
+
+    let cutoff = match correction {
+        Correction::Bonferroni => {
+            for (index, p) in tests.iter() {
+                adjusted[*index] = Some((p * m as f64).min(1.0));
+            }
+            None
+        }
+        Correction::Holm => {
+            // Running maximum from the smallest p value up keeps the adjusted values monotone
+            let mut running = 0.0_f64;
+            for (rank, (index, p)) in tests.iter().enumerate() {
+                running = running.max(((m - rank) as f64 * p).min(1.0));
+                adjusted[*index] = Some(running);
+            }
+            None
+        }
+        Correction::BenjaminiHochberg => {
+            // Running minimum from the largest p value down keeps the adjusted values monotone
+            let mut running = 1.0_f64;
+            for (rank, (index, p)) in tests.iter().enumerate().rev() {
+                running = running.min(m as f64 * p / (rank + 1) as f64);
+                adjusted[*index] = Some(running);
+            }
+            None
+        }
+        Correction::GeoDaFdr => geoda_fdr_cutoff(p_vals, alpha),
+    };
+
+    let significant: Vec<bool> = match correction {
+        Correction::GeoDaFdr => p_vals
+            .iter()
+            .map(|p| match ((*p).into(), cutoff) {
+                (Some(p), Some(cutoff)) => p <= cutoff,
+                _ => false,
+            })
+            .collect(),
+        _ => adjusted
+            .iter()
+            .map(|p| p.is_some_and(|p| p <= alpha))
+            .collect(),
+    };
+
+    // The adjusted p values are monotone in the unadjusted ones, so the significant
+    // observations are exactly those at or below the largest significant unadjusted p value
+    let cutoff = cutoff.or_else(|| {
+        tests
+            .iter()
+            .rev()
+            .find(|(index, _)| significant[*index])
+            .map(|(_, p)| *p)
+    });
+
+    Ok(CorrectedPValues {
+        correction,
+        alpha,
+        p_vals: adjusted,
+        cutoff,
+        significant,
+    })
+}
+
+impl LocalPValues for LISAResult {
+    /// The p value of each observation, None for observations without neighbors as they are
+    /// not tested
+    fn local_p_values(&self) -> Vec<Option<f64>> {
+        self.p_vals
+            .iter()
+            .zip(self.no_neighbors.iter())
+            .map(|(p, no_neighbors)| (*no_neighbors > 0 && !p.is_nan()).then_some(*p))
+            .collect()
+    }
+}
+
+impl LocalPValues for LocalGearyResult {
+    fn local_p_values(&self) -> Vec<Option<f64>> {
+        self.p_vals
+            .iter()
+            .map(|p| (!p.is_nan()).then_some(*p))
+            .collect()
+    }
+}
+
+impl LocalPValues for LocalGResult {
+    fn local_p_values(&self) -> Vec<Option<f64>> {
+        self.p_sim
+            .iter()
+            .map(|p| (!p.is_nan()).then_some(*p))
+            .collect()
+    }
+}
+
+impl LocalPValues for LocalJoinCountResult {
+    fn local_p_values(&self) -> Vec<Option<f64>> {
+        self.p_sim.clone()
+    }
+}
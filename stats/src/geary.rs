@@ -0,0 +1,345 @@
+use crate::lisa::PermutationMethod;
+use crate::permutation::{conditional_permutations, stream_rng};
+use crate::utils::{pseudo_p_value, sim_moments, standardize, two_sided_p_value, WeightSums};
+use geo_weights::weights::{ToSparseMatrix, TransformType};
+use nalgebra::DVector;
+use nalgebra_sparse::csr::CsrMatrix;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct GearyResult {
+    /// The value of Geary's C, below 1 for positive and above 1 for negative autocorrelation
+    pub c: f64,
+    /// The expected value of C under the null hypothesis, always 1
+    pub expected_c: f64,
+    /// The variance of C assuming the values are normally distributed
+    pub variance_norm: f64,
+    /// The variance of C assuming the values are randomly permuted over the observations
+    pub variance_rand: f64,
+    /// The z-score of C under normality
+    pub z_norm: f64,
+    /// The z-score of C under randomisation
+    pub z_rand: f64,
+    /// The two sided p value of C under normality
+    pub p_norm: f64,
+    /// The two sided p value of C under randomisation
+    pub p_rand: f64,
+    /// The pseudo p value from the permutations, None if no permutations were run
+    pub p_sim: Option<f64>,
+    /// The z-score of C relative to the mean and standard deviation of the permutations
+    pub z_sim: Option<f64>,
+    /// The value of C for each permutation if keep_sims is specified
+    pub sims: Vec<f64>,
+}
+
+/// Local Geary cluster classifications, following GeoDa
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum GearyCluster {
+    NotSignificant,
+    /// Positive association between a high value and high neighbors
+    HighHigh,
+    /// Positive association between a low value and low neighbors
+    LowLow,
+    /// Positive association where the value and its neighbors are on different sides of the mean
+    OtherPositive,
+    /// Positive association for the multivariate statistic, where high and low aren't defined
+    Positive,
+    /// Negative association, the value is dissimilar to its neighbors
+    Negative,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LocalGearyResult {
+    /// The local Geary value of each observation
+    pub geary_vals: Vec<f64>,
+    /// The pseudo p value of each observation from the conditional permutations
+    pub p_vals: Vec<f64>,
+    /// The cluster classification of each observation
+    pub clusters: Vec<GearyCluster>,
+    /// The simulated local Geary values for each observation if keep_sims is specified
+    pub sims: Vec<Vec<f64>>,
+}
+
+/// Computes Σ w_ij (x_i - x_j)² over all the pairs
+fn squared_difference_sum(w_matrix: &CsrMatrix<f64>, values: &[f64]) -> f64 {
+    w_matrix
+        .triplet_iter()
+        .map(|(i, j, w)| w * (values[i] - values[j]).powi(2))
+        .sum()
+}
+
+fn check_length(no_elements: usize, values: &[f64]) -> Result<(), String> {
+    if values.len() != no_elements {
+        return Err(format!(
+            "Expected {} values but got {}",
+            no_elements,
+            values.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Computes the global Geary's C for the given weights and values along with its moments and
+/// significance. As with `lisa` the weights are row standardized. Results are a GearyResult
+/// that contains
+/// - c: Geary's C
+/// - expected_c: the expected value of C under the null hypothesis
+/// - variance_norm and variance_rand: the variance of C under normality and randomisation
+/// - z_norm, z_rand, p_norm and p_rand: the z-scores and two sided p values under each
+/// - p_sim and z_sim: the pseudo p value and z-score from the permutations
+/// - sims: the value of C for each permutation if keep_sims is specified
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `values` - the value of each observation
+/// * `permutations` - the number of random permutations to run, 0 skips them
+/// * `keep_sims` - whether to return the value of C for each permutation
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn geary<W: ToSparseMatrix>(
+    weights: &W,
+    values: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<GearyResult, String> {
+    check_length(weights.no_elements(), values)?;
+    let n = values.len();
+    if n < 4 {
+        return Err("Geary's C needs at least 4 observations".into());
+    }
+
+    let mean = values.iter().sum::<f64>() / n as f64;
+    let deviations: Vec<f64> = values.iter().map(|v| v - mean).collect();
+    let sum_sq: f64 = deviations.iter().map(|d| d * d).sum();
+    if sum_sq == 0.0 {
+        return Err("Geary's C is undefined when all values are equal".into());
+    }
+
+    let w_matrix = weights.as_sparse_matrix(Some(TransformType::Row));
+    let WeightSums { s0, s1, s2 } = WeightSums::new(&w_matrix);
+    if s0 == 0.0 {
+        return Err("Geary's C is undefined when there are no neighbors".into());
+    }
+
+    let nf = n as f64;
+    let norm = (nf - 1.0) / (2.0 * s0 * sum_sq);
+    let c = norm * squared_difference_sum(&w_matrix, values);
+
+    // Moments of C under the normality and randomisation assumptions, Cliff and Ord (1981)
+    let s0_sq = s0 * s0;
+    let variance_norm = ((2.0 * s1 + s2) * (nf - 1.0) - 4.0 * s0_sq) / (2.0 * (nf + 1.0) * s0_sq);
+
+    let kurtosis = (deviations.iter().map(|d| d.powi(4)).sum::<f64>() / nf) / (sum_sq / nf).powi(2);
+    let nf_sq = nf * nf;
+    let a = (nf - 1.0) * s1 * (nf_sq - 3.0 * nf + 3.0 - (nf - 1.0) * kurtosis);
+    let b = 0.25 * ((nf - 1.0) * s2 * (nf_sq + 3.0 * nf - 6.0 - (nf_sq - nf + 2.0) * kurtosis));
+    let c_term = s0_sq * (nf_sq - 3.0 - (nf - 1.0).powi(2) * kurtosis);
+    let variance_rand = (a - b + c_term) / (nf * (nf - 2.0) * (nf - 3.0) * s0_sq);
+
+    let z_norm = (c - 1.0) / variance_norm.sqrt();
+    let z_rand = (c - 1.0) / variance_rand.sqrt();
+
+    // Randomly permute the values over the observations to build the reference distribution,
+    // each permutation drawing from its own stream so a seed gives the same results on any
+    // number of threads
+    let sims: Vec<f64> = cfg_into_iter!(0..permutations, 64)
+        .map(|permutation| {
+            let mut rng = stream_rng(seed, permutation);
+            let mut permuted = values.to_vec();
+            permuted.shuffle(&mut rng);
+            norm * squared_difference_sum(&w_matrix, &permuted)
+        })
+        .collect();
+
+    let (p_sim, z_sim) = if permutations > 0 {
+        let (sim_mean, sim_std) = sim_moments(&sims);
+        (
+            Some(pseudo_p_value(c, &sims)),
+            Some((c - sim_mean) / sim_std),
+        )
+    } else {
+        (None, None)
+    };
+
+    Ok(GearyResult {
+        c,
+        expected_c: 1.0,
+        variance_norm,
+        variance_rand,
+        z_norm,
+        z_rand,
+        p_norm: two_sided_p_value(z_norm),
+        p_rand: two_sided_p_value(z_rand),
+        p_sim,
+        z_sim,
+        sims: if keep_sims { sims } else { vec![] },
+    })
+}
+
+/// Computes the local Geary values (1/k) Σ_v Σ_j w_ij (z_vi - z_vj)² for standardized
+/// variables z_v along with their conditional permutation p values
+fn local_geary_values(
+    w_matrix: &CsrMatrix<f64>,
+    standardized: &[Vec<f64>],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> (Vec<f64>, Vec<f64>, Vec<Vec<f64>>) {
+    let k = standardized.len() as f64;
+    let local_value = |index: usize, neighbors: &[usize], weights: &[f64]| -> f64 {
+        standardized
+            .iter()
+            .map(|z| {
+                neighbors
+                    .iter()
+                    .zip(weights)
+                    .map(|(j, w)| w * (z[index] - z[*j]).powi(2))
+                    .sum::<f64>()
+            })
+            .sum::<f64>()
+            / k
+    };
+
+    let geary_vals: Vec<f64> = (0..w_matrix.nrows())
+        .map(|index| {
+            let row = w_matrix.row(index);
+            local_value(index, row.col_indices(), row.values())
+        })
+        .collect();
+
+    let (p_vals, sims) = conditional_permutations(
+        w_matrix,
+        permutations,
+        PermutationMethod::FULL,
+        seed,
+        keep_sims,
+        local_value,
+        |index, sims| pseudo_p_value(geary_vals[index], sims),
+    );
+
+    (geary_vals, p_vals, sims)
+}
+
+/// Computes the univariate Local Geary statistic (Anselin 2019) for each observation, using
+/// row standardized weights and values standardized to a mean of 0 and variance of 1. Small
+/// values indicate an observation similar to its neighbors. Results are a LocalGearyResult
+/// that contains
+/// - geary_vals: the local Geary value of each observation
+/// - p_vals: the pseudo p value of each observation from conditional permutations
+/// - clusters: HighHigh, LowLow or OtherPositive for significant observations with a local
+///   value below the mean, Negative for those above, otherwise NotSignificant
+/// - sims: the simulated values for each observation if keep_sims is specified
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `values` - the value of each observation
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `significance` - the p value below which an observation is classified as a cluster
+/// * `keep_sims` - whether to return the simulated values
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn local_geary<W: ToSparseMatrix>(
+    weights: &W,
+    values: &[f64],
+    permutations: usize,
+    significance: f64,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<LocalGearyResult, String> {
+    check_length(weights.no_elements(), values)?;
+    let z = standardize(values)?;
+    let w_matrix = weights.as_sparse_matrix(Some(TransformType::Row));
+
+    let (geary_vals, p_vals, sims) = local_geary_values(
+        &w_matrix,
+        std::slice::from_ref(&z),
+        permutations,
+        keep_sims,
+        seed,
+    );
+    let lags: Vec<f64> = (&w_matrix * &DVector::from_vec(z.clone()))
+        .iter()
+        .copied()
+        .collect();
+    let mean_geary = geary_vals.iter().sum::<f64>() / geary_vals.len() as f64;
+
+    // Observations without neighbors or permutations have no simulations to test against
+    let untested = |i: usize| permutations == 0 || w_matrix.row(i).nnz() == 0;
+    let clusters = (0..z.len())
+        .map(|i| match (geary_vals[i], p_vals[i]) {
+            (_, p) if p > significance || untested(i) => GearyCluster::NotSignificant,
+            (g, _) if g >= mean_geary => GearyCluster::Negative,
+            _ if z[i] > 0.0 && lags[i] > 0.0 => GearyCluster::HighHigh,
+            _ if z[i] < 0.0 && lags[i] < 0.0 => GearyCluster::LowLow,
+            _ => GearyCluster::OtherPositive,
+        })
+        .collect();
+
+    Ok(LocalGearyResult {
+        geary_vals,
+        p_vals,
+        clusters,
+        sims,
+    })
+}
+
+/// Computes the multivariate Local Geary statistic (Anselin 2019), the average of the local
+/// Geary values of each variable. Neighbors are permuted jointly across the variables. Results
+/// are as for `local_geary`, except significant observations below the mean are classified as
+/// Positive since high and low aren't defined across several variables.
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `columns` - the variables, each with one value per observation
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `significance` - the p value below which an observation is classified as a cluster
+/// * `keep_sims` - whether to return the simulated values
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn multivariate_local_geary<W: ToSparseMatrix>(
+    weights: &W,
+    columns: &[Vec<f64>],
+    permutations: usize,
+    significance: f64,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<LocalGearyResult, String> {
+    if columns.is_empty() {
+        return Err("At least one variable is needed".into());
+    }
+    let standardized = columns
+        .iter()
+        .map(|column| {
+            check_length(weights.no_elements(), column)?;
+            standardize(column)
+        })
+        .collect::<Result<Vec<Vec<f64>>, String>>()?;
+    let w_matrix = weights.as_sparse_matrix(Some(TransformType::Row));
+
+    let (geary_vals, p_vals, sims) =
+        local_geary_values(&w_matrix, &standardized, permutations, keep_sims, seed);
+    let mean_geary = geary_vals.iter().sum::<f64>() / geary_vals.len() as f64;
+
+    // Observations without neighbors or permutations have no simulations to test against
+    let untested = |i: usize| permutations == 0 || w_matrix.row(i).nnz() == 0;
+    let clusters = (0..geary_vals.len())
+        .map(|i| match (geary_vals[i], p_vals[i]) {
+            (_, p) if p > significance || untested(i) => GearyCluster::NotSignificant,
+            (g, _) if g >= mean_geary => GearyCluster::Negative,
+            _ => GearyCluster::Positive,
+        })
+        .collect();
+
+    Ok(LocalGearyResult {
+        geary_vals,
+        p_vals,
+        clusters,
+        sims,
+    })
+}
@@ -0,0 +1,379 @@
+use crate::lisa::PermutationMethod;
+use crate::permutation::{conditional_permutations, stream_rng};
+use crate::utils::{pseudo_p_value, sim_moments, two_sided_p_value, WeightSums};
+use geo_weights::weights::{ToSparseMatrix, TransformType};
+use nalgebra_sparse::coo::CooMatrix;
+use nalgebra_sparse::csr::CsrMatrix;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct GeneralGResult {
+    /// The value of the General G statistic
+    pub g: f64,
+    /// The expected value of G under the null hypothesis
+    pub expected_g: f64,
+    /// The variance of G under the null hypothesis
+    pub variance: f64,
+    /// The z-score of G
+    pub z_norm: f64,
+    /// The two sided p value of G
+    pub p_norm: f64,
+    /// The pseudo p value from the permutations, None if no permutations were run
+    pub p_sim: Option<f64>,
+    /// The z-score of G relative to the mean and standard deviation of the permutations
+    pub z_sim: Option<f64>,
+    /// The value of G for each permutation if keep_sims is specified
+    pub sims: Vec<f64>,
+}
+
+/// Which form of the local statistic to compute
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum LocalGType {
+    /// Gi, which leaves each observations own value out
+    Gi,
+    /// Gi*, which includes each observations own value with the given weight, usually 1
+    GiStar { self_weight: f64 },
+}
+
+/// Hot and cold spot classification by confidence level, matching the Gi_Bin field of ArcGIS
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum HotSpot {
+    Cold99,
+    Cold95,
+    Cold90,
+    NotSignificant,
+    Hot90,
+    Hot95,
+    Hot99,
+}
+
+impl HotSpot {
+    /// Classifies an observation from its z-score, which gives the direction, and its p value
+    pub fn classify(z: f64, p: f64) -> Self {
+        match (z > 0.0, p) {
+            (true, p) if p <= 0.01 => HotSpot::Hot99,
+            (true, p) if p <= 0.05 => HotSpot::Hot95,
+            (true, p) if p <= 0.1 => HotSpot::Hot90,
+            (false, p) if p <= 0.01 => HotSpot::Cold99,
+            (false, p) if p <= 0.05 => HotSpot::Cold95,
+            (false, p) if p <= 0.1 => HotSpot::Cold90,
+            _ => HotSpot::NotSignificant,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LocalGResult {
+    /// The local G value of each observation
+    pub g_vals: Vec<f64>,
+    /// The z-score of each observation, NaN where it is undefined because the values the
+    /// statistic is taken over are all equal
+    pub z_vals: Vec<f64>,
+    /// The two sided p value of each observation from its z-score, NaN where the z-score is
+    pub p_norm: Vec<f64>,
+    /// The pseudo p value of each observation from the conditional permutations
+    pub p_sim: Vec<f64>,
+    /// The hot and cold spot classification of each observation from its z-score and p_norm,
+    /// NotSignificant where the z-score is undefined
+    pub hot_spots: Vec<HotSpot>,
+    /// The simulated local G values for each observation if keep_sims is specified
+    pub sims: Vec<Vec<f64>>,
+}
+
+fn check_values(no_elements: usize, values: &[f64]) -> Result<(), String> {
+    if values.len() != no_elements {
+        return Err(format!(
+            "Expected {} values but got {}",
+            no_elements,
+            values.len()
+        ));
+    }
+    if values.iter().any(|v| *v < 0.0) {
+        return Err("Getis-Ord statistics need non negative values".into());
+    }
+    Ok(())
+}
+
+/// Computes Σ_ij w_ij x_i x_j
+fn cross_product_sum(w_matrix: &CsrMatrix<f64>, values: &[f64]) -> f64 {
+    w_matrix
+        .triplet_iter()
+        .map(|(i, j, w)| w * values[i] * values[j])
+        .sum()
+}
+
+/// Computes the General G statistic of Getis and Ord (1992) for the given weights and values,
+/// which need to be non negative. High values of G indicate clustering of high values. The
+/// weights are made binary, as the moments assume. Results are a GeneralGResult that contains
+/// - g: the General G statistic
+/// - expected_g and variance: the moments of G under the null hypothesis
+/// - z_norm and p_norm: the z-score and two sided p value
+/// - p_sim and z_sim: the pseudo p value and z-score from the permutations
+/// - sims: the value of G for each permutation if keep_sims is specified
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `values` - the non negative value of each observation
+/// * `permutations` - the number of random permutations to run, 0 skips them
+/// * `keep_sims` - whether to return the value of G for each permutation
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn general_g<W: ToSparseMatrix>(
+    weights: &W,
+    values: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<GeneralGResult, String> {
+    check_values(weights.no_elements(), values)?;
+    let n = values.len();
+    if n < 4 {
+        return Err("General G needs at least 4 observations".into());
+    }
+
+    let w_matrix = without_diagonal(&weights.as_sparse_matrix(Some(TransformType::Binary))).0;
+    let WeightSums { s0, s1, s2 } = WeightSums::new(&w_matrix);
+
+    let sum: f64 = values.iter().sum();
+    let sum2: f64 = values.iter().map(|v| v.powi(2)).sum();
+    let sum3: f64 = values.iter().map(|v| v.powi(3)).sum();
+    let sum4: f64 = values.iter().map(|v| v.powi(4)).sum();
+
+    // Σ_i≠j x_i x_j
+    let denominator = sum * sum - sum2;
+    if denominator == 0.0 {
+        return Err("General G is undefined when fewer than two values are non zero".into());
+    }
+    let g = cross_product_sum(&w_matrix, values) / denominator;
+
+    // Moments of G from Getis and Ord (1992)
+    let nf = n as f64;
+    let s0_sq = s0 * s0;
+    let expected_g = s0 / (nf * (nf - 1.0));
+    let b0 = (nf * nf - 3.0 * nf + 3.0) * s1 - nf * s2 + 3.0 * s0_sq;
+    let b1 = -((nf * nf - nf) * s1 - 2.0 * nf * s2 + 6.0 * s0_sq);
+    let b2 = -(2.0 * nf * s1 - (nf + 3.0) * s2 + 6.0 * s0_sq);
+    let b3 = 4.0 * (nf - 1.0) * s1 - 2.0 * (nf + 1.0) * s2 + 8.0 * s0_sq;
+    let b4 = s1 - s2 + s0_sq;
+    let expected_g2 =
+        (b0 * sum2 * sum2 + b1 * sum4 + b2 * sum * sum * sum2 + b3 * sum * sum3 + b4 * sum.powi(4))
+            / (denominator * denominator * nf * (nf - 1.0) * (nf - 2.0) * (nf - 3.0));
+    let variance = expected_g2 - expected_g * expected_g;
+    let z_norm = (g - expected_g) / variance.sqrt();
+
+    // Randomly permute the values over the observations to build the reference distribution,
+    // each permutation drawing from its own stream so a seed gives the same results on any
+    // number of threads
+    let sims: Vec<f64> = cfg_into_iter!(0..permutations, 64)
+        .map(|permutation| {
+            let mut rng = stream_rng(seed, permutation);
+            let mut permuted = values.to_vec();
+            permuted.shuffle(&mut rng);
+            cross_product_sum(&w_matrix, &permuted) / denominator
+        })
+        .collect();
+
+    let (p_sim, z_sim) = if permutations > 0 {
+        let (sim_mean, sim_std) = sim_moments(&sims);
+        (
+            Some(pseudo_p_value(g, &sims)),
+            Some((g - sim_mean) / sim_std),
+        )
+    } else {
+        (None, None)
+    };
+
+    Ok(GeneralGResult {
+        g,
+        expected_g,
+        variance,
+        z_norm,
+        p_norm: two_sided_p_value(z_norm),
+        p_sim,
+        z_sim,
+        sims: if keep_sims { sims } else { vec![] },
+    })
+}
+
+/// Splits a matrix in to its off diagonal part and its diagonal
+fn without_diagonal(w_matrix: &CsrMatrix<f64>) -> (CsrMatrix<f64>, Vec<f64>) {
+    let n = w_matrix.nrows();
+    let mut diagonal = vec![0.0; n];
+    let mut coo = CooMatrix::new(n, n);
+    for (i, j, w) in w_matrix.triplet_iter() {
+        if i == j {
+            diagonal[i] = *w;
+        } else {
+            coo.push(i, j, *w);
+        }
+    }
+    (CsrMatrix::from(&coo), diagonal)
+}
+
+/// Sets the diagonal of the raw weights to the self weight then applies the transform, the
+/// same way as `Weights::as_sparse_matrix`
+fn with_self_weight(
+    w_matrix: &CsrMatrix<f64>,
+    self_weight: f64,
+    transform: Option<TransformType>,
+) -> CsrMatrix<f64> {
+    let n = w_matrix.nrows();
+    let (off_diagonal, _) = without_diagonal(w_matrix);
+    let mut triplets: Vec<(usize, usize, f64)> = off_diagonal
+        .triplet_iter()
+        .map(|(i, j, w)| (i, j, *w))
+        .collect();
+    if self_weight != 0.0 {
+        triplets.extend((0..n).map(|i| (i, i, self_weight)));
+    }
+
+    let mut row_sums = vec![0.0; n];
+    for (i, _, w) in triplets.iter() {
+        row_sums[*i] += w;
+    }
+    let total: f64 = row_sums.iter().sum();
+
+    let mut coo = CooMatrix::new(n, n);
+    for (i, j, w) in triplets {
+        let weight = match transform {
+            Some(TransformType::Row) => w / row_sums[i],
+            Some(TransformType::Binary) => 1.0,
+            Some(TransformType::DoublyStandardized) => w / total,
+            None => w,
+        };
+        coo.push(i, j, weight);
+    }
+    CsrMatrix::from(&coo)
+}
+
+/// Computes the local Gi or Gi* statistic of Getis and Ord (1992) for each observation, with
+/// the z-scores of Ord and Getis (1995) which hold for any weights. Values need to be non
+/// negative. Positive z-scores indicate a cluster of high values, a hot spot, and negative
+/// ones a cluster of low values, a cold spot. Results are a LocalGResult that contains
+/// - g_vals: the local G value of each observation
+/// - z_vals and p_norm: the z-score and two sided p value of each observation
+/// - p_sim: the pseudo p value of each observation from conditional permutations
+/// - hot_spots: the hot or cold spot classification at 90, 95 and 99% confidence
+/// - sims: the simulated values for each observation if keep_sims is specified
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `values` - the non negative value of each observation
+/// * `g_type` - whether to compute Gi or Gi*, and the self weight to use for Gi*
+/// * `transform` - the transform to apply to the weights after setting the self weight
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `keep_sims` - whether to return the simulated values
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn local_g<W: ToSparseMatrix>(
+    weights: &W,
+    values: &[f64],
+    g_type: LocalGType,
+    transform: Option<TransformType>,
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<LocalGResult, String> {
+    check_values(weights.no_elements(), values)?;
+    let n = values.len();
+    if n < 3 {
+        return Err("Local G needs at least 3 observations".into());
+    }
+
+    let self_weight = match g_type {
+        LocalGType::Gi => 0.0,
+        LocalGType::GiStar { self_weight } => self_weight,
+    };
+    let full_matrix = with_self_weight(&weights.as_sparse_matrix(None), self_weight, transform);
+    let (neighbors_matrix, self_weights) = without_diagonal(&full_matrix);
+
+    let sum: f64 = values.iter().sum();
+    let sum2: f64 = values.iter().map(|v| v * v).sum();
+    let star = matches!(g_type, LocalGType::GiStar { .. });
+
+    // The sum of the values the statistic is taken over, which leaves out the observation for Gi
+    let reference_sum = |index: usize| {
+        if star {
+            sum
+        } else {
+            sum - values[index]
+        }
+    };
+    let local_value = |index: usize, neighbors: &[usize], weights: &[f64]| -> f64 {
+        let lag: f64 = neighbors
+            .iter()
+            .zip(weights)
+            .map(|(j, w)| w * values[*j])
+            .sum();
+        (lag + self_weights[index] * values[index]) / reference_sum(index)
+    };
+
+    let mut g_vals: Vec<f64> = Vec::with_capacity(n);
+    let mut z_vals: Vec<f64> = Vec::with_capacity(n);
+    for index in 0..n {
+        let row = neighbors_matrix.row(index);
+        g_vals.push(local_value(index, row.col_indices(), row.values()));
+
+        // Moments of the values the statistic is taken over
+        let (count, values_sum, values_sum2) = if star {
+            (n as f64, sum, sum2)
+        } else {
+            let v = values[index];
+            ((n - 1) as f64, sum - v, sum2 - v * v)
+        };
+        let mean = values_sum / count;
+        let std = (values_sum2 / count - mean * mean).sqrt();
+
+        let row_weights: Vec<f64> = row
+            .values()
+            .iter()
+            .copied()
+            .chain(std::iter::once(self_weights[index]))
+            .collect();
+        let weight_sum: f64 = row_weights.iter().sum();
+        let weight_sum2: f64 = row_weights.iter().map(|w| w * w).sum();
+        let lag = g_vals[index] * reference_sum(index);
+
+        let z = (lag - weight_sum * mean)
+            / (std * ((count * weight_sum2 - weight_sum * weight_sum) / (count - 1.0)).sqrt());
+        z_vals.push(if z.is_finite() { z } else { f64::NAN });
+    }
+
+    let (p_sim, sims) = conditional_permutations(
+        &neighbors_matrix,
+        permutations,
+        PermutationMethod::FULL,
+        seed,
+        keep_sims,
+        local_value,
+        |index, sims| pseudo_p_value(g_vals[index], sims),
+    );
+    let p_norm: Vec<f64> = z_vals
+        .iter()
+        .map(|z| {
+            if z.is_nan() {
+                f64::NAN
+            } else {
+                two_sided_p_value(*z)
+            }
+        })
+        .collect();
+    let hot_spots = z_vals
+        .iter()
+        .zip(p_norm.iter())
+        .map(|(z, p)| HotSpot::classify(*z, *p))
+        .collect();
+
+    Ok(LocalGResult {
+        g_vals,
+        z_vals,
+        p_norm,
+        p_sim,
+        hot_spots,
+        sims,
+    })
+}
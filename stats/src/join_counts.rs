@@ -0,0 +1,361 @@
+use crate::lisa::PermutationMethod;
+use crate::permutation::{conditional_permutations, stream_rng};
+use crate::utils::{pseudo_p_value, two_sided_p_value, WeightSums};
+use geo_weights::weights::{ToSparseMatrix, TransformType};
+use nalgebra_sparse::coo::CooMatrix;
+use nalgebra_sparse::csr::CsrMatrix;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct JoinCount {
+    /// The number of joins of this type
+    pub count: f64,
+    /// The expected number of joins under the null hypothesis
+    pub expected: f64,
+    /// The variance of the number of joins under the null hypothesis
+    pub variance: f64,
+    /// The z-score of the count
+    pub z_norm: f64,
+    /// The two sided p value of the count
+    pub p_norm: f64,
+    /// The pseudo p value from the permutations, None if no permutations were run
+    pub p_sim: Option<f64>,
+    /// The count for each permutation if keep_sims is specified
+    pub sims: Vec<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JoinCountResult {
+    /// Joins between two observations that are both true (black-black)
+    pub bb: JoinCount,
+    /// Joins between a true and a false observation (black-white)
+    pub bw: JoinCount,
+    /// Joins between two observations that are both false (white-white)
+    pub ww: JoinCount,
+    /// The total number of joins
+    pub joins: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LocalJoinCountResult {
+    /// The local join count of each observation
+    pub counts: Vec<f64>,
+    /// The one sided pseudo p value of each observation, None for observations the statistic
+    /// does not apply to
+    pub p_sim: Vec<Option<f64>>,
+    /// The simulated counts for each observation if keep_sims is specified
+    pub sims: Vec<Vec<f64>>,
+}
+
+/// Returns the symmetric binary weights matrix without any self links, after checking the
+/// values match. Asymmetric weights such as KNN are symmetrized, two observations being joined
+/// if either is a neighbor of the other, so every join is counted once from each end.
+fn binary_matrix<W: ToSparseMatrix>(
+    weights: &W,
+    no_values: usize,
+) -> Result<CsrMatrix<f64>, String> {
+    if no_values != weights.no_elements() {
+        return Err(format!(
+            "Expected {} values but got {}",
+            weights.no_elements(),
+            no_values
+        ));
+    }
+    let w_matrix = weights.as_sparse_matrix(Some(TransformType::Binary));
+
+    // Add the transpose so each link is in both directions, then make the sums binary again
+    let n = w_matrix.nrows();
+    let mut coo = CooMatrix::new(n, n);
+    for (i, j, _) in w_matrix.triplet_iter().filter(|(i, j, _)| i != j) {
+        coo.push(i, j, 1.0);
+        coo.push(j, i, 1.0);
+    }
+    let mut symmetric = CsrMatrix::from(&coo);
+    symmetric.values_mut().iter_mut().for_each(|w| *w = 1.0);
+    Ok(symmetric)
+}
+
+/// Counts the BB, BW and WW joins, each link of the symmetric matrix counting half a join
+fn count_joins(w_matrix: &CsrMatrix<f64>, values: &[bool]) -> (f64, f64, f64) {
+    let (mut bb, mut bw, mut ww) = (0.0, 0.0, 0.0);
+    for (i, j, w) in w_matrix.triplet_iter() {
+        match (values[i], values[j]) {
+            (true, true) => bb += w,
+            (false, false) => ww += w,
+            _ => bw += w,
+        }
+    }
+    (bb / 2.0, bw / 2.0, ww / 2.0)
+}
+
+/// The falling factorial n (n - 1) ... (n - k + 1)
+fn falling_factorial(n: f64, k: usize) -> f64 {
+    (0..k).map(|t| n - t as f64).product()
+}
+
+/// Computes the global join count statistics of Cliff and Ord (1981) for a binary variable,
+/// with the moments under non free sampling, that is with the number of true observations
+/// held fixed. The weights are made binary and symmetric, see `binary_matrix`. Results are a JoinCountResult that contains, for each of the BB, BW
+/// and WW joins
+/// - count: the number of joins
+/// - expected and variance: the moments of the count under the null hypothesis
+/// - z_norm and p_norm: the z-score and two sided p value
+/// - p_sim: the pseudo p value from the permutations
+/// - sims: the count for each permutation if keep_sims is specified
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `values` - the binary value of each observation
+/// * `permutations` - the number of random permutations to run, 0 skips them
+/// * `keep_sims` - whether to return the counts for each permutation
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn join_counts<W: ToSparseMatrix>(
+    weights: &W,
+    values: &[bool],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<JoinCountResult, String> {
+    let w_matrix = binary_matrix(weights, values.len())?;
+    let n = values.len();
+    if n < 4 {
+        return Err("Join counts need at least 4 observations".into());
+    }
+    let WeightSums { s0, s1, s2 } = WeightSums::new(&w_matrix);
+    if s0 == 0.0 {
+        return Err("Join counts are undefined when there are no neighbors".into());
+    }
+
+    let nf = n as f64;
+    let no_black = values.iter().filter(|v| **v).count() as f64;
+    let no_white = nf - no_black;
+    let s0_sq = s0 * s0;
+
+    // Moments for joins between observations of the same color
+    let same_moments = |m: f64| {
+        let expected = s0 * falling_factorial(m, 2) / (2.0 * falling_factorial(nf, 2));
+        let variance = 0.25
+            * (s1 * falling_factorial(m, 2) / falling_factorial(nf, 2)
+                + (s2 - 2.0 * s1) * falling_factorial(m, 3) / falling_factorial(nf, 3)
+                + (s0_sq + s1 - s2) * falling_factorial(m, 4) / falling_factorial(nf, 4))
+            - expected * expected;
+        (expected, variance)
+    };
+    let expected_bw = s0 * no_black * no_white / falling_factorial(nf, 2);
+    let variance_bw = 0.25
+        * (2.0 * s1 * no_black * no_white / falling_factorial(nf, 2)
+            + (s2 - 2.0 * s1) * no_black * no_white * (no_black + no_white - 2.0)
+                / falling_factorial(nf, 3)
+            + 4.0
+                * (s0_sq + s1 - s2)
+                * falling_factorial(no_black, 2)
+                * falling_factorial(no_white, 2)
+                / falling_factorial(nf, 4))
+        - expected_bw * expected_bw;
+
+    // Randomly permute the values over the observations to build the reference distributions,
+    // each permutation drawing from its own stream so a seed gives the same results on any
+    // number of threads
+    let sims: Vec<(f64, f64, f64)> = cfg_into_iter!(0..permutations, 64)
+        .map(|permutation| {
+            let mut rng = stream_rng(seed, permutation);
+            let mut permuted = values.to_vec();
+            permuted.shuffle(&mut rng);
+            count_joins(&w_matrix, &permuted)
+        })
+        .collect();
+
+    let (bb, bw, ww) = count_joins(&w_matrix, values);
+    let join_count = |count: f64, (expected, variance): (f64, f64), sims: Vec<f64>| {
+        let z_norm = (count - expected) / variance.sqrt();
+        JoinCount {
+            count,
+            expected,
+            variance,
+            z_norm,
+            p_norm: two_sided_p_value(z_norm),
+            p_sim: if permutations > 0 {
+                Some(pseudo_p_value(count, &sims))
+            } else {
+                None
+            },
+            sims: if keep_sims { sims } else { vec![] },
+        }
+    };
+
+    Ok(JoinCountResult {
+        bb: join_count(
+            bb,
+            same_moments(no_black),
+            sims.iter().map(|s| s.0).collect(),
+        ),
+        bw: join_count(
+            bw,
+            (expected_bw, variance_bw),
+            sims.iter().map(|s| s.1).collect(),
+        ),
+        ww: join_count(
+            ww,
+            same_moments(no_white),
+            sims.iter().map(|s| s.2).collect(),
+        ),
+        joins: s0 / 2.0,
+    })
+}
+
+/// The fraction of simulations at least as large as the observed count
+fn upper_p_value(observed: f64, sims: &[f64]) -> f64 {
+    let larger = sims.iter().filter(|s| **s >= observed).count();
+    (larger as f64 + 1.0) / (sims.len() as f64 + 1.0)
+}
+
+/// Computes a local join count where `focal` says whether the statistic applies to an
+/// observation and `neighbor` whether a neighbor counts towards it
+fn local_join_count<W: ToSparseMatrix>(
+    weights: &W,
+    focal: &[bool],
+    neighbor: &[bool],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<LocalJoinCountResult, String> {
+    let w_matrix = binary_matrix(weights, focal.len())?;
+
+    let count = |index: usize, neighbors: &[usize], weights: &[f64]| -> f64 {
+        if !focal[index] {
+            return 0.0;
+        }
+        neighbors
+            .iter()
+            .zip(weights)
+            .filter(|(j, _)| neighbor[**j])
+            .map(|(_, w)| w)
+            .sum()
+    };
+
+    let counts: Vec<f64> = (0..focal.len())
+        .map(|index| {
+            let row = w_matrix.row(index);
+            count(index, row.col_indices(), row.values())
+        })
+        .collect();
+
+    let (p_sim, sims) = conditional_permutations(
+        &w_matrix,
+        permutations,
+        PermutationMethod::FULL,
+        seed,
+        keep_sims,
+        count,
+        |index, sims| {
+            if focal[index] && !sims.is_empty() {
+                Some(upper_p_value(counts[index], sims))
+            } else {
+                None
+            }
+        },
+    );
+
+    Ok(LocalJoinCountResult {
+        counts,
+        p_sim,
+        sims,
+    })
+}
+
+/// Computes the univariate local join count of Anselin and Li (2019), the number of true
+/// neighbors of each true observation. Significance comes from conditional permutations and
+/// the one sided pseudo p value, which is only given for true observations. Results are a
+/// LocalJoinCountResult that contains
+/// - counts: the local join count of each observation, 0 for false observations
+/// - p_sim: the pseudo p value of each true observation
+/// - sims: the simulated counts for each observation if keep_sims is specified
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use, which are made binary and symmetric
+/// * `values` - the binary value of each observation
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `keep_sims` - whether to return the simulated counts
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn local_join_counts<W: ToSparseMatrix>(
+    weights: &W,
+    values: &[bool],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<LocalJoinCountResult, String> {
+    local_join_count(weights, values, values, permutations, keep_sims, seed)
+}
+
+/// Computes the bivariate local join count of Anselin and Li (2019), for observations where x
+/// is true and z false the number of neighbors where z is true and x false. The two variables
+/// should not occur together, see `local_colocation_join_counts` for when they can.
+/// Significance comes from conditional permutations as in `local_join_counts`.
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use, which are made binary and symmetric
+/// * `x` - the binary value of the first variable at each observation
+/// * `z` - the binary value of the second variable at each observation
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `keep_sims` - whether to return the simulated counts
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn local_bivariate_join_counts<W: ToSparseMatrix>(
+    weights: &W,
+    x: &[bool],
+    z: &[bool],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<LocalJoinCountResult, String> {
+    if x.len() != z.len() {
+        return Err("Both variables need a value for every observation".into());
+    }
+    let focal: Vec<bool> = x.iter().zip(z).map(|(x, z)| *x && !*z).collect();
+    let neighbor: Vec<bool> = x.iter().zip(z).map(|(x, z)| !*x && *z).collect();
+    local_join_count(weights, &focal, &neighbor, permutations, keep_sims, seed)
+}
+
+/// Computes the co-location local join count of Anselin and Li (2019), for observations where
+/// every variable is true the number of neighbors where every variable is also true.
+/// Significance comes from conditional permutations as in `local_join_counts`.
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use, which are made binary and symmetric
+/// * `columns` - the binary values of each variable, one vector per variable
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `keep_sims` - whether to return the simulated counts
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn local_colocation_join_counts<W: ToSparseMatrix>(
+    weights: &W,
+    columns: &[Vec<bool>],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<LocalJoinCountResult, String> {
+    if columns.is_empty() {
+        return Err("Co-location needs at least one variable".into());
+    }
+    let n = columns[0].len();
+    if columns.iter().any(|c| c.len() != n) {
+        return Err("Every variable needs a value for every observation".into());
+    }
+    let colocated: Vec<bool> = (0..n).map(|i| columns.iter().all(|c| c[i])).collect();
+    local_join_count(
+        weights,
+        &colocated,
+        &colocated,
+        permutations,
+        keep_sims,
+        seed,
+    )
+}
@@ -23,5 +23,14 @@ macro_rules! cfg_into_iter {
     }};
 }
 
-
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod correction;
+pub mod geary;
+pub mod getis_ord;
+pub mod join_counts;
 pub mod lisa;
+pub mod moran;
+mod permutation;
+pub mod rates;
+mod utils;
@@ -1,7 +1,8 @@
-use geo_weights::weights::{TransformType, Weights};
+use crate::permutation::{conditional_permutations, perturbation_lookups};
+use crate::rates::empirical_bayes_standardize;
+use crate::utils::{differences, pseudo_p_value, standardize};
+use geo_weights::weights::{ToSparseMatrix, TransformType};
 use nalgebra::DVector;
-use rand::seq::index::sample;
-use rayon::prelude::*;
 use serde::Serialize;
 
 #[derive(Debug, Serialize)]
@@ -11,6 +12,7 @@ pub struct LISAResult {
     pub lags: Vec<f64>,
     pub p_vals: Vec<f64>,
     pub sims: Vec<Vec<f64>>,
+    pub no_neighbors: Vec<usize>,
 }
 
 /// Specifies the permutation method to use when performing significance tests
@@ -18,7 +20,7 @@ pub struct LISAResult {
 /// simulation draw
 /// If it's LOOKUP, we generate a set of permutaitons ahead of time corrisoponding to each set of
 /// no of neighbors and then this is used as a look up when performing the simulation.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone, Copy)]
 pub enum PermutationMethod {
     FULL,
     LOOKUP,
@@ -33,6 +35,97 @@ pub enum Quad {
     LL,
 }
 
+/// LISA cluster classification that takes significance into account. The numeric codes from
+/// `code` match the cluster map output of GeoDa.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Cluster {
+    NotSignificant,
+    HighHigh,
+    LowLow,
+    LowHigh,
+    HighLow,
+    /// The statistic could not be computed, for example because of a missing value
+    Undefined,
+    /// The observation has no neighbors so it has no lag to compare against
+    Neighborless,
+}
+
+impl Cluster {
+    /// Returns the GeoDa code for the cluster
+    pub fn code(&self) -> u8 {
+        match self {
+            Cluster::NotSignificant => 0,
+            Cluster::HighHigh => 1,
+            Cluster::LowLow => 2,
+            Cluster::LowHigh => 3,
+            Cluster::HighLow => 4,
+            Cluster::Undefined => 5,
+            Cluster::Neighborless => 6,
+        }
+    }
+}
+
+impl LISAResult {
+    /// Classifies each observation in to a cluster. Observations without neighbors are
+    /// Neighborless and those with an undefined value or p value are Undefined. The rest are
+    /// NotSignificant if their p value is above the significance level, otherwise they take the
+    /// cluster matching their quad.
+    ///
+    /// # Arguments
+    ///
+    /// * `significance` - the p value at or below which an observation is significant, such as
+    ///   0.05 or the cutoff of a `correction::CorrectedPValues`
+    ///
+    pub fn clusters(&self, significance: f64) -> Vec<Cluster> {
+        self.quads
+            .iter()
+            .zip(self.moran_val.iter().zip(self.p_vals.iter()))
+            .zip(self.no_neighbors.iter())
+            .map(|((quad, (moran, p_val)), no_neighbors)| {
+                if *no_neighbors == 0 {
+                    Cluster::Neighborless
+                } else if moran.is_nan() || p_val.is_nan() {
+                    Cluster::Undefined
+                } else if *p_val > significance {
+                    Cluster::NotSignificant
+                } else {
+                    match quad {
+                        Quad::HH => Cluster::HighHigh,
+                        Quad::LL => Cluster::LowLow,
+                        Quad::LH => Cluster::LowHigh,
+                        Quad::HL => Cluster::HighLow,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the GeoDa cluster code of each observation, see `clusters`
+    ///
+    /// # Arguments
+    ///
+    /// * `significance` - the p value at or below which an observation is significant
+    ///
+    pub fn cluster_codes(&self, significance: f64) -> Vec<u8> {
+        self.clusters(significance)
+            .iter()
+            .map(|c| c.code())
+            .collect()
+    }
+}
+
+impl Quad {
+    /// Assigns the quad from the standardized value of an observation and its lag
+    pub(crate) fn from_value_and_lag(value: f64, lag: f64) -> Self {
+        match (value >= 0.0, lag >= 0.0) {
+            (true, true) => Quad::HH,
+            (true, false) => Quad::HL,
+            (false, true) => Quad::LH,
+            (false, false) => Quad::LL,
+        }
+    }
+}
+
 /// Generates a nested set of vectors which contain permuted indices for each of the different
 /// number of neighbors that we have in the set. These are used to speed up selection of neighbors
 /// during the simulation phase of lisa generation
@@ -41,14 +134,26 @@ pub fn generate_perturbation_lookups(
     permutations: usize,
     no_observations: usize,
 ) -> Vec<Vec<Vec<usize>>> {
-    cfg_into_iter!((0..max_no_neighbors + 1))
-        .map(|no_neighbors| {
-            let mut rng = rand::thread_rng();
-            (0..permutations)
-                .map(|_| sample(&mut rng, no_observations - 1, no_neighbors).into_vec())
-                .collect()
-        })
-        .collect()
+    perturbation_lookups(max_no_neighbors, permutations, no_observations, None)
+}
+
+/// Generates the same lookups as `generate_perturbation_lookups` but reproducibly, each number
+/// of neighbors drawing from its own stream of the seed
+///
+/// # Arguments
+///
+/// * `max_no_neighbors` - the largest number of neighbors to generate permutations for
+/// * `permutations` - the number of permutations for each number of neighbors
+/// * `no_observations` - the number of observations to draw from
+/// * `seed` - the seed for the random number generators
+///
+pub fn seeded_generate_perturbation_lookups(
+    max_no_neighbors: usize,
+    permutations: usize,
+    no_observations: usize,
+    seed: u64,
+) -> Vec<Vec<Vec<usize>>> {
+    perturbation_lookups(max_no_neighbors, permutations, no_observations, Some(seed))
 }
 
 /// Computes the LISA stats for the given weights and values. Results are a LisaResult object that
@@ -58,16 +163,31 @@ pub fn generate_perturbation_lookups(
 /// - quads: the moran quad specification for each observation,
 /// - p_vals: the estimated p_val of each observation
 /// - sims: the simulated moran values for each observation if keep_sims is specified
-pub fn lisa(
-    weights: &Weights,
+/// - no_neighbors: the number of neighbors of each observation
+///
+/// With a seed each observation, or with the LOOKUP method each number of neighbors, draws its
+/// permutations from its own stream of the seed, so the results are identical for the same
+/// seed however many threads are used.
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `values` - the value of each observation
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `keep_sims` - whether to return the simulated moran values
+/// * `permutation_method` - the permutation method to use
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn lisa<W: ToSparseMatrix>(
+    weights: &W,
     values: &[f64],
     permutations: usize,
     keep_sims: bool,
     permutation_method: PermutationMethod,
+    seed: Option<u64>,
 ) -> Result<LISAResult, String> {
     // Generate a vector from the slice of values we are provided
     let x = DVector::from_column_slice(values);
-    let no_observations = x.len();
 
     // Standardize the vector by dividing by subtracting off the mean and dividing by the standard
     // deviation.
@@ -85,13 +205,7 @@ pub fn lisa(
     let quads: Vec<Quad> = x_z
         .iter()
         .zip(lags.iter())
-        .map(|(a, b)| match (a, b) {
-            (a, b) if *a >= 0.0 && *b >= 0.0 => Quad::HH,
-            (a, b) if *a >= 0.0 && *b < 0.0 => Quad::HL,
-            (a, b) if *a < 0.0 && *b >= 0.0 => Quad::LH,
-            (a, b) if *a < 0.0 && *b < 0.0 => Quad::LL,
-            (_, _) => unreachable!(),
-        })
+        .map(|(a, b)| Quad::from_value_and_lag(*a, *b))
         .collect();
 
     // Multiply the input values by the lags and normalize to get the moran value
@@ -99,110 +213,195 @@ pub fn lisa(
     let norm = (x_z.len() as f64 - 1.0) / x_z.dot(&x_z);
     results *= norm;
 
-    // Next section runs the simulation to determine the significance of each observation
-    //
-    // First we get the number of neighbors for each observation determined by the no of entries in the
-    // sparse matrix. we also record the max number of neighbors.
-
-    let no_neighbors: Vec<usize> = w_matrix.row_iter().map(|row| row.values().len()).collect();
-    let max_neighbors = no_neighbors
-        .iter()
-        .max()
-        .ok_or("Failed get get max no neighbors")?;
-
-    // If the lookup permutations method is specified we generate the lookup table of permutations
-    // to be used in the simulation
-    let permutation_lookup = match permutation_method {
-        PermutationMethod::LOOKUP => Some(generate_perturbation_lookups(
-            *max_neighbors,
-            permutations,
-            no_observations,
-        )),
-        PermutationMethod::FULL => None,
+    // Next we run the conditional permutations to determine the significance of each
+    // observation. For each permutation the observation keeps its value while its neighbors
+    // values are drawn from the other observations, giving a simulated moran value.
+    let local_value = |index: usize, neighbors: &[usize], weights: &[f64]| -> f64 {
+        let lag: f64 = neighbors
+            .iter()
+            .zip(weights)
+            .map(|(j, w)| w * x_z[*j])
+            .sum();
+        x_z[index] * lag * norm
     };
 
-    // Next we iterate over the moran values of our observations, using multiple threads if they are available.
-    let sim_results: Vec<(f64, Vec<f64>)> = cfg_into_iter!(results.data.as_vec())
-        .zip(no_neighbors)
-        .enumerate()
-        .map(|(index, (moran, values_to_sample))| {
-            // For each observation, because we are randomizing the neighbor observations, we dont
-            // need to known which weight corresponds to which neighbor. So we simply collapse them
-            // to a vector.
-            let collapsed_weights: Vec<f64> = w_matrix.row(index).values().into();
-
-            // We get the value at the current observation.
-            let self_value: f64 = *x_z
-                .get(index)
-                .expect(&format!("Failed to get value for index {}", index));
-
-            // Then generate a list of observations with that value removed. I suspect there is a
-            // way to simply mask the value out rather than having to construct this new array
-            // which might be a performance boost in future.
-            let mut values_with_self_removed: Vec<f64> = x_z.iter().copied().collect();
-            values_with_self_removed.remove(index);
-
-            let mut rng = rand::thread_rng();
-
-            // For each permutation we calclate the lags and moran value for this observation.
-            let sim_vals: Vec<f64> = (0..permutations)
-                .map(|permutation| {
-                    let sim_moran = match &permutation_lookup {
-                        // if we haven't generated a permutation lookup we sample from the set of
-                        // observations directly
-                        None => sample(&mut rng, no_observations - 1, values_to_sample)
-                            .into_iter()
-                            .map(|i| values_with_self_removed[i])
-                            .zip(&collapsed_weights)
-                            .map(|(val, weight)| val * weight)
-                            .sum(),
-
-                        // if we have generated a permutation lookup we will use it to simply
-                        // lookup permuted indices for the correct number of weights
-                        Some(lookup) => lookup[values_to_sample][permutation]
-                            .iter()
-                            .map(|i| values_with_self_removed[*i])
-                            .zip(&collapsed_weights)
-                            .map(|(val, weight)| val * weight)
-                            .sum(),
-                    };
-                    sim_moran
-                })
-                .map(|v: f64| v * self_value * norm)
-                .collect();
-
-            // Now that we have the moran values for each simulation, we could how many of them are
-            // greater or equal to the moran value for the actual dataset
-            let mut larger = sim_vals.iter().filter(|v| *v >= moran).count();
-
-            // Because the distribution has two tails, and can either be in the lower tail or the
-            // upper tail, we check to see if most observations are above or bellow the moran value
-            // and switch directions if needed
-            if permutations - larger < larger {
-                larger = permutations - larger;
-            }
-
-            // Calculate the sudo p value as defined as the fraction  of observations more extream
-            // than the calculated moran value
-            let p_val = (larger as f64 + 1.0) / (permutations as f64 + 1.0);
-
-            if keep_sims {
-                (p_val, sim_vals)
-            } else {
-                (p_val, vec![])
-            }
-        })
-        .collect();
+    // The pseudo p value is the fraction of simulations more extreme than the moran value for
+    // the actual dataset, in whichever tail it falls
+    let (p_vals, sims) = conditional_permutations(
+        &w_matrix,
+        permutations,
+        permutation_method,
+        seed,
+        keep_sims,
+        local_value,
+        |index, sims| pseudo_p_value(results[index], sims),
+    );
 
     Ok(LISAResult {
         moran_val: results.data.into(),
         quads,
         lags: lags.data.into(),
-        sims: sim_results.iter().map(|r| r.1.clone()).collect(),
-        p_vals: sim_results.iter().map(|r| r.0).collect(),
+        p_vals,
+        sims,
+        no_neighbors: w_matrix.row_iter().map(|row| row.values().len()).collect(),
+    })
+}
+
+/// Computes the bivariate LISA of Anselin et al. (2002) for the given weights and values, which
+/// relates x at each observation to the spatial lag of y at its neighbors. Both variables are
+/// standardized and the weights row standardized as in `lisa`. Significance comes from
+/// conditional permutations, where x at the observation is held fixed while the y values of
+/// its neighbors are drawn from the other observations. Results are a LISAResult object that
+/// contains the
+/// - moran_values: for each observation
+/// - lags: the lag of y for each observation
+/// - quads: the quad of x against the lag of y for each observation
+/// - p_vals: the estimated p_val of each observation
+/// - sims: the simulated moran values for each observation if keep_sims is specified
+/// - no_neighbors: the number of neighbors of each observation
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `x` - the value of the first variable at each observation
+/// * `y` - the value of the second variable at each observation, which is lagged
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `keep_sims` - whether to return the simulated moran values
+/// * `permutation_method` - the permutation method to use, as in `lisa`
+/// * `seed` - the seed for the permutations to make them reproducible, as in `lisa`
+///
+pub fn bivariate_lisa<W: ToSparseMatrix>(
+    weights: &W,
+    x: &[f64],
+    y: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    permutation_method: PermutationMethod,
+    seed: Option<u64>,
+) -> Result<LISAResult, String> {
+    let n = x.len();
+    if n != weights.no_elements() || y.len() != n {
+        return Err(format!(
+            "Expected {} values for both variables but got {} and {}",
+            weights.no_elements(),
+            n,
+            y.len()
+        ));
+    }
+    let z_x = standardize(x)?;
+    let z_y = standardize(y)?;
+    let w_matrix = weights.as_sparse_matrix(Some(TransformType::Row));
+
+    // With standardized values z_x'z_x is n, so this is the same norm as `lisa` uses
+    let norm = (n as f64 - 1.0) / n as f64;
+    let local_value = |index: usize, neighbors: &[usize], weights: &[f64]| -> f64 {
+        let lag: f64 = neighbors
+            .iter()
+            .zip(weights)
+            .map(|(j, w)| w * z_y[*j])
+            .sum();
+        z_x[index] * lag * norm
+    };
+
+    let lags: Vec<f64> = (&w_matrix * &DVector::from_column_slice(&z_y)).data.into();
+    let moran_val: Vec<f64> = z_x
+        .iter()
+        .zip(lags.iter())
+        .map(|(value, lag)| value * lag * norm)
+        .collect();
+    let quads = z_x
+        .iter()
+        .zip(lags.iter())
+        .map(|(value, lag)| Quad::from_value_and_lag(*value, *lag))
+        .collect();
+
+    let (p_vals, sims) = conditional_permutations(
+        &w_matrix,
+        permutations,
+        permutation_method,
+        seed,
+        keep_sims,
+        local_value,
+        |index, sims| pseudo_p_value(moran_val[index], sims),
+    );
+
+    Ok(LISAResult {
+        moran_val,
+        quads,
+        lags,
+        p_vals,
+        sims,
+        no_neighbors: w_matrix.row_iter().map(|row| row.values().len()).collect(),
     })
 }
 
+/// Computes the differential LISA, the LISA of the change in a variable between two times. The
+/// quads then describe the change, so HH marks observations whose increase is above average
+/// and surrounded by above average increases. Results are the same as for `lisa` on
+/// after - before.
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `before` - the value of each observation at the first time
+/// * `after` - the value of each observation at the second time
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `keep_sims` - whether to return the simulated moran values
+/// * `permutation_method` - the permutation method to use, as in `lisa`
+/// * `seed` - the seed for the permutations to make them reproducible, as in `lisa`
+///
+pub fn differential_lisa<W: ToSparseMatrix>(
+    weights: &W,
+    before: &[f64],
+    after: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    permutation_method: PermutationMethod,
+    seed: Option<u64>,
+) -> Result<LISAResult, String> {
+    lisa(
+        weights,
+        &differences(before, after)?,
+        permutations,
+        keep_sims,
+        permutation_method,
+        seed,
+    )
+}
+
+/// Computes the Empirical Bayes LISA for rates, the LISA of the rates e_i / p_i after
+/// `empirical_bayes_standardize`, so that unstable rates from small populations are not
+/// flagged as outliers. Results are otherwise the same as for `lisa`.
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `events` - the number of events at each observation, such as cases
+/// * `population` - the population at risk at each observation
+/// * `permutations` - the number of conditional permutations to run for each observation
+/// * `keep_sims` - whether to return the simulated moran values
+/// * `permutation_method` - the permutation method to use, as in `lisa`
+/// * `seed` - the seed for the permutations to make them reproducible, as in `lisa`
+///
+pub fn empirical_bayes_lisa<W: ToSparseMatrix>(
+    weights: &W,
+    events: &[f64],
+    population: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    permutation_method: PermutationMethod,
+    seed: Option<u64>,
+) -> Result<LISAResult, String> {
+    lisa(
+        weights,
+        &empirical_bayes_standardize(events, population)?,
+        permutations,
+        keep_sims,
+        permutation_method,
+        seed,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -233,7 +432,14 @@ mod tests {
                 .collect();
 
             b.iter(|| {
-                lisa(&weights, &values, 9999, false, PermutationMethod::LOOKUP);
+                lisa(
+                    &weights,
+                    &values,
+                    9999,
+                    false,
+                    PermutationMethod::LOOKUP,
+                    None,
+                );
             })
         } else {
             panic!("Expected data to be a feature collection")
@@ -261,7 +467,14 @@ mod tests {
                 .collect();
 
             b.iter(|| {
-                lisa(&weights, &values, 9999, false, PermutationMethod::LOOKUP);
+                lisa(
+                    &weights,
+                    &values,
+                    9999,
+                    false,
+                    PermutationMethod::LOOKUP,
+                    None,
+                );
             })
         } else {
             panic!("Expected data to be a feature collection")
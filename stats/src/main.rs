@@ -1,6 +1,6 @@
 use geo::GeometryCollection;
 use geo_stats::lisa::{lisa, PermutationMethod};
-use geo_weights::{QueensWeights, WeightBuilder};
+use geo_weights::{QueensWeights, WeightsSpec};
 use geojson::GeoJson;
 
 fn main() {
@@ -12,8 +12,14 @@ fn main() {
     .unwrap();
     let geojson: GeoJson = jsonfile.parse().unwrap();
     let geoms: GeometryCollection<f64> = geojson::quick_collection(&geojson).unwrap();
-    let weight_builder = QueensWeights::new(10000.0);
-    let weights = weight_builder.compute_weights(&geoms.0);
+    // The weights can optionally be described by a json spec passed as the first argument
+    let weights_spec: WeightsSpec<f64> = match std::env::args().nth(1) {
+        Some(spec) => serde_json::from_str(&spec).expect("Failed to parse weights spec"),
+        None => WeightsSpec::Queen(QueensWeights::new(10000.0)),
+    };
+    let weights = weights_spec
+        .build(&geoms.0)
+        .expect("Failed to build weights");
 
     if let GeoJson::FeatureCollection(fc) = geojson {
         let values: Vec<f64> = fc
@@ -22,7 +28,14 @@ fn main() {
             .map(|f| f.property("cases").unwrap().as_f64().unwrap())
             .collect();
 
-        lisa(&weights, &values, 9999, false, PermutationMethod::LOOKUP);
+        lisa(
+            &weights,
+            &values,
+            9999,
+            false,
+            PermutationMethod::LOOKUP,
+            None,
+        );
     } else {
         panic!("Expected data to be a feature collection")
     }
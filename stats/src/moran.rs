@@ -0,0 +1,294 @@
+use crate::permutation::stream_rng;
+use crate::rates::empirical_bayes_standardize;
+use crate::utils::{
+    differences, pseudo_p_value, sim_moments, standardize, two_sided_p_value, WeightSums,
+};
+use geo_weights::weights::{ToSparseMatrix, TransformType};
+use nalgebra::DVector;
+use nalgebra_sparse::csr::CsrMatrix;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct MoranResult {
+    /// The value of Moran's I
+    pub i: f64,
+    /// The expected value of I under the null hypothesis, -1 / (n - 1)
+    pub expected_i: f64,
+    /// The variance of I assuming the values are normally distributed
+    pub variance_norm: f64,
+    /// The variance of I assuming the values are randomly permuted over the observations
+    pub variance_rand: f64,
+    /// The z-score of I under normality
+    pub z_norm: f64,
+    /// The z-score of I under randomisation
+    pub z_rand: f64,
+    /// The two sided p value of I under normality
+    pub p_norm: f64,
+    /// The two sided p value of I under randomisation
+    pub p_rand: f64,
+    /// The pseudo p value from the permutations, None if no permutations were run
+    pub p_sim: Option<f64>,
+    /// The z-score of I relative to the mean and standard deviation of the permutations
+    pub z_sim: Option<f64>,
+    /// The value of I for each permutation if keep_sims is specified
+    pub sims: Vec<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BivariateMoranResult {
+    /// The value of the bivariate Moran's I
+    pub i: f64,
+    /// The pseudo p value from the permutations, None if no permutations were run
+    pub p_sim: Option<f64>,
+    /// The z-score of I relative to the mean and standard deviation of the permutations
+    pub z_sim: Option<f64>,
+    /// The value of I for each permutation if keep_sims is specified
+    pub sims: Vec<f64>,
+}
+
+/// Computes I = n / s0 * z'Wz / z'z for deviations from the mean z
+fn moran_i(w_matrix: &CsrMatrix<f64>, z: &DVector<f64>, s0: f64) -> f64 {
+    bivariate_moran_i(w_matrix, z, z, s0)
+}
+
+/// Computes I = n / s0 * x'Wy / x'x, which is Moran's I when x and y are the same
+fn bivariate_moran_i(
+    w_matrix: &CsrMatrix<f64>,
+    x: &DVector<f64>,
+    y: &DVector<f64>,
+    s0: f64,
+) -> f64 {
+    let lags: DVector<f64> = w_matrix * y;
+    x.len() as f64 / s0 * x.dot(&lags) / x.dot(x)
+}
+
+/// Computes the global Moran's I for the given weights and values along with its moments and
+/// significance. As with `lisa` the weights are row standardized. Results are a MoranResult
+/// that contains
+/// - i: Moran's I
+/// - expected_i: the expected value of I under the null hypothesis
+/// - variance_norm and variance_rand: the variance of I under normality and randomisation
+/// - z_norm, z_rand, p_norm and p_rand: the z-scores and two sided p values under each
+/// - p_sim and z_sim: the pseudo p value and z-score from the permutations
+/// - sims: the value of I for each permutation if keep_sims is specified
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `values` - the value of each observation
+/// * `permutations` - the number of random permutations to run, 0 skips them
+/// * `keep_sims` - whether to return the value of I for each permutation
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn moran<W: ToSparseMatrix>(
+    weights: &W,
+    values: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<MoranResult, String> {
+    let n = values.len();
+    if n != weights.no_elements() {
+        return Err(format!(
+            "Expected {} values but got {}",
+            weights.no_elements(),
+            n
+        ));
+    }
+    if n < 4 {
+        return Err("Moran's I needs at least 4 observations".into());
+    }
+
+    let x = DVector::from_column_slice(values);
+    let z = x.add_scalar(-x.mean());
+    if z.dot(&z) == 0.0 {
+        return Err("Moran's I is undefined when all values are equal".into());
+    }
+
+    let w_matrix = weights.as_sparse_matrix(Some(TransformType::Row));
+    let WeightSums { s0, s1, s2 } = WeightSums::new(&w_matrix);
+    if s0 == 0.0 {
+        return Err("Moran's I is undefined when there are no neighbors".into());
+    }
+
+    let i = moran_i(&w_matrix, &z, s0);
+
+    // Moments of I under the normality and randomisation assumptions, Cliff and Ord (1981)
+    let nf = n as f64;
+    let expected_i = -1.0 / (nf - 1.0);
+    let s0_sq = s0 * s0;
+    let variance_norm = (nf * nf * s1 - nf * s2 + 3.0 * s0_sq) / ((nf * nf - 1.0) * s0_sq)
+        - expected_i * expected_i;
+
+    let kurtosis = nf * z.iter().map(|v| v.powi(4)).sum::<f64>() / z.dot(&z).powi(2);
+    let a = nf * ((nf * nf - 3.0 * nf + 3.0) * s1 - nf * s2 + 3.0 * s0_sq);
+    let b = kurtosis * ((nf * nf - nf) * s1 - 2.0 * nf * s2 + 6.0 * s0_sq);
+    let variance_rand =
+        (a - b) / ((nf - 1.0) * (nf - 2.0) * (nf - 3.0) * s0_sq) - expected_i * expected_i;
+
+    let z_norm = (i - expected_i) / variance_norm.sqrt();
+    let z_rand = (i - expected_i) / variance_rand.sqrt();
+
+    // Randomly permute the values over the observations to build the reference distribution,
+    // each permutation drawing from its own stream so a seed gives the same results on any
+    // number of threads
+    let sims: Vec<f64> = cfg_into_iter!(0..permutations, 64)
+        .map(|permutation| {
+            let mut rng = stream_rng(seed, permutation);
+            let mut permuted: Vec<f64> = z.iter().copied().collect();
+            permuted.shuffle(&mut rng);
+            moran_i(&w_matrix, &DVector::from_vec(permuted), s0)
+        })
+        .collect();
+
+    let (p_sim, z_sim) = if permutations > 0 {
+        let (mean, std) = sim_moments(&sims);
+        (Some(pseudo_p_value(i, &sims)), Some((i - mean) / std))
+    } else {
+        (None, None)
+    };
+
+    Ok(MoranResult {
+        i,
+        expected_i,
+        variance_norm,
+        variance_rand,
+        z_norm,
+        z_rand,
+        p_norm: two_sided_p_value(z_norm),
+        p_rand: two_sided_p_value(z_rand),
+        p_sim,
+        z_sim,
+        sims: if keep_sims { sims } else { vec![] },
+    })
+}
+
+/// Computes the global bivariate Moran's I of Wartenberg (1985), the correlation between x at
+/// each observation and the spatial lag of y. Both variables are standardized and, as with
+/// `lisa`, the weights are row standardized. There is no analytical inference so significance
+/// comes from permuting y over the observations. Results are a BivariateMoranResult that
+/// contains
+/// - i: the bivariate Moran's I
+/// - p_sim and z_sim: the pseudo p value and z-score from the permutations
+/// - sims: the value of I for each permutation if keep_sims is specified
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `x` - the value of the first variable at each observation
+/// * `y` - the value of the second variable at each observation, which is lagged
+/// * `permutations` - the number of random permutations to run, 0 skips them
+/// * `keep_sims` - whether to return the value of I for each permutation
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn bivariate_moran<W: ToSparseMatrix>(
+    weights: &W,
+    x: &[f64],
+    y: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<BivariateMoranResult, String> {
+    let n = x.len();
+    if n != weights.no_elements() || y.len() != n {
+        return Err(format!(
+            "Expected {} values for both variables but got {} and {}",
+            weights.no_elements(),
+            n,
+            y.len()
+        ));
+    }
+
+    let z_x = DVector::from_vec(standardize(x)?);
+    let z_y = standardize(y)?;
+
+    let w_matrix = weights.as_sparse_matrix(Some(TransformType::Row));
+    let s0: f64 = w_matrix.values().iter().sum();
+    if s0 == 0.0 {
+        return Err("Moran's I is undefined when there are no neighbors".into());
+    }
+
+    let i = bivariate_moran_i(&w_matrix, &z_x, &DVector::from_column_slice(&z_y), s0);
+
+    // Randomly permute y over the observations to build the reference distribution
+    let sims: Vec<f64> = cfg_into_iter!(0..permutations, 64)
+        .map(|permutation| {
+            let mut rng = stream_rng(seed, permutation);
+            let mut permuted = z_y.clone();
+            permuted.shuffle(&mut rng);
+            bivariate_moran_i(&w_matrix, &z_x, &DVector::from_vec(permuted), s0)
+        })
+        .collect();
+
+    let (p_sim, z_sim) = if permutations > 0 {
+        let (mean, std) = sim_moments(&sims);
+        (Some(pseudo_p_value(i, &sims)), Some((i - mean) / std))
+    } else {
+        (None, None)
+    };
+
+    Ok(BivariateMoranResult {
+        i,
+        p_sim,
+        z_sim,
+        sims: if keep_sims { sims } else { vec![] },
+    })
+}
+
+/// Computes the differential Moran's I, Moran's I of the change in a variable between two
+/// times, which tests for clustering of the change rather than of the levels. The result and
+/// its inference are the same as for `moran` on after - before.
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `before` - the value of each observation at the first time
+/// * `after` - the value of each observation at the second time
+/// * `permutations` - the number of random permutations to run, 0 skips them
+/// * `keep_sims` - whether to return the value of I for each permutation
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn differential_moran<W: ToSparseMatrix>(
+    weights: &W,
+    before: &[f64],
+    after: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<MoranResult, String> {
+    moran(
+        weights,
+        &differences(before, after)?,
+        permutations,
+        keep_sims,
+        seed,
+    )
+}
+
+/// Computes the Empirical Bayes Moran's I for rates of Assunção and Reis (1999), Moran's I of
+/// the rates e_i / p_i after `empirical_bayes_standardize`, so that unstable rates from small
+/// populations do not dominate. The result and its inference are otherwise the same as for
+/// `moran`.
+///
+/// # Arguments
+///
+/// * `weights` - the weights to use
+/// * `events` - the number of events at each observation, such as cases
+/// * `population` - the population at risk at each observation
+/// * `permutations` - the number of random permutations to run, 0 skips them
+/// * `keep_sims` - whether to return the value of I for each permutation
+/// * `seed` - the seed for the permutations to make them reproducible
+///
+pub fn empirical_bayes_moran<W: ToSparseMatrix>(
+    weights: &W,
+    events: &[f64],
+    population: &[f64],
+    permutations: usize,
+    keep_sims: bool,
+    seed: Option<u64>,
+) -> Result<MoranResult, String> {
+    let rates = empirical_bayes_standardize(events, population)?;
+    moran(weights, &rates, permutations, keep_sims, seed)
+}
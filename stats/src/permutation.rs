@@ -0,0 +1,134 @@
+use crate::lisa::PermutationMethod;
+use nalgebra_sparse::csr::CsrMatrix;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+
+/// Returns the random number generator for one stream of permutations, such as those of a
+/// single observation. With a seed every stream uses the ChaCha key derived from the seed and
+/// selects its own ChaCha stream by id, so streams never overlap, different seeds never share
+/// streams and the results are the same however the streams are split over threads. Without a
+/// seed the key is drawn from the operating system.
+pub fn stream_rng(seed: Option<u64>, stream: usize) -> ChaCha8Rng {
+    let mut rng = match seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    };
+    rng.set_stream(stream as u64);
+    rng
+}
+
+/// Draws the permuted indices for each number of neighbors from 0 to `max_no_neighbors`, each
+/// number of neighbors using its own stream of the seed
+pub fn perturbation_lookups(
+    max_no_neighbors: usize,
+    permutations: usize,
+    no_observations: usize,
+    seed: Option<u64>,
+) -> Vec<Vec<Vec<usize>>> {
+    cfg_into_iter!((0..max_no_neighbors + 1))
+        .map(|no_neighbors| {
+            let mut rng = stream_rng(seed, no_neighbors);
+            (0..permutations)
+                .map(|_| sample(&mut rng, no_observations - 1, no_neighbors).into_vec())
+                .collect()
+        })
+        .collect()
+}
+
+/// Runs conditional permutations for every observation. For each permutation the observation
+/// keeps its own value while the ids of its neighbors are replaced by the same number of ids
+/// drawn at random from all the other observations, either freshly for every permutation or
+/// from a lookup shared by all observations with the same number of neighbors, see
+/// `PermutationMethod`. The statistic is passed the observation id, the drawn neighbor ids and
+/// the observations row of weights, in the same order as the drawn ids. The simulated values of
+/// each observation are then passed to `reduce` along with the observation id, usually to
+/// compute a pseudo p value, and dropped unless keep_sims is set so only one observations
+/// simulations are held in memory per thread. Observations without neighbors get no
+/// simulations. Returns the reduced value of each observation and the simulated values of each
+/// observation if keep_sims is set, otherwise an empty vector.
+///
+/// # Arguments
+///
+/// * `w_matrix` - the weights matrix used to find the number of neighbors and their weights
+/// * `permutations` - the number of permutations to run for each observation
+/// * `permutation_method` - whether to draw the neighbors for every permutation or look them up
+/// * `seed` - the seed for the permutations, see `stream_rng`
+/// * `keep_sims` - whether to return the simulated values
+/// * `statistic` - computes the local statistic from the drawn neighbors
+/// * `reduce` - computes the result for an observation from its simulated values
+///
+pub fn conditional_permutations<F, R, T>(
+    w_matrix: &CsrMatrix<f64>,
+    permutations: usize,
+    permutation_method: PermutationMethod,
+    seed: Option<u64>,
+    keep_sims: bool,
+    statistic: F,
+    reduce: R,
+) -> (Vec<T>, Vec<Vec<f64>>)
+where
+    F: Fn(usize, &[usize], &[f64]) -> f64 + Sync + Send,
+    R: Fn(usize, &[f64]) -> T + Sync + Send,
+    T: Send,
+{
+    let no_observations = w_matrix.nrows();
+    let no_neighbors = |index: usize| {
+        w_matrix
+            .row(index)
+            .values()
+            .len()
+            .min(no_observations.saturating_sub(1))
+    };
+
+    // With the LOOKUP method the draws for each number of neighbors are made once up front
+    let lookup = match permutation_method {
+        PermutationMethod::LOOKUP if no_observations > 0 => Some(perturbation_lookups(
+            (0..no_observations).map(no_neighbors).max().unwrap_or(0),
+            permutations,
+            no_observations,
+            seed,
+        )),
+        _ => None,
+    };
+
+    let results: Vec<(T, Vec<f64>)> = cfg_into_iter!(0..no_observations)
+        .map(|index| {
+            let row = w_matrix.row(index);
+            let weights = row.values();
+            let no_neighbors = no_neighbors(index);
+            if no_neighbors == 0 {
+                return (reduce(index, &[]), vec![]);
+            }
+
+            // Ids are drawn from the other observations by skipping over this one
+            let skip_self = |id: usize| if id >= index { id + 1 } else { id };
+            let mut rng = stream_rng(seed, index);
+            let sims: Vec<f64> = (0..permutations)
+                .map(|permutation| {
+                    let ids: Vec<usize> = match &lookup {
+                        None => sample(&mut rng, no_observations - 1, no_neighbors)
+                            .into_iter()
+                            .map(skip_self)
+                            .collect(),
+                        Some(lookup) => lookup[no_neighbors][permutation]
+                            .iter()
+                            .map(|id| skip_self(*id))
+                            .collect(),
+                    };
+                    statistic(index, &ids, &weights[..no_neighbors])
+                })
+                .collect();
+
+            let reduced = reduce(index, &sims);
+            (reduced, if keep_sims { sims } else { vec![] })
+        })
+        .collect();
+
+    if keep_sims {
+        results.into_iter().unzip()
+    } else {
+        (results.into_iter().map(|r| r.0).collect(), vec![])
+    }
+}
@@ -0,0 +1,57 @@
+/// Standardizes rates with the Empirical Bayes method of Assunção and Reis (1999), which takes
+/// into account that rates from observations with a small population at risk are less stable.
+/// Each rate r_i = e_i / p_i is standardized as (r_i - b) / sqrt(a + b / p_i), where b is the
+/// overall rate and a an estimate of the variance between observations. If a + b / p_i is
+/// negative, b / p_i is used on its own. The results can be passed to `moran` or `lisa`.
+///
+/// # Arguments
+///
+/// * `events` - the number of events at each observation, such as cases
+/// * `population` - the population at risk at each observation, which must be positive
+///
+pub fn empirical_bayes_standardize(events: &[f64], population: &[f64]) -> Result<Vec<f64>, String> {
+    if events.len() != population.len() {
+        return Err(format!(
+            "Expected the same number of events and populations but got {} and {}",
+            events.len(),
+            population.len()
+        ));
+    }
+    if events.is_empty() {
+        return Err("Rates need at least one observation".into());
+    }
+    if population.iter().any(|p| *p <= 0.0) {
+        return Err("Every population at risk needs to be positive".into());
+    }
+    if events.iter().any(|e| *e < 0.0) {
+        return Err("Event counts can not be negative".into());
+    }
+
+    let n = events.len() as f64;
+    let events_sum: f64 = events.iter().sum();
+    let population_sum: f64 = population.iter().sum();
+    let rates: Vec<f64> = events.iter().zip(population).map(|(e, p)| e / p).collect();
+
+    if events_sum == 0.0 {
+        return Err("Rates can not be standardized when there are no events".into());
+    }
+
+    let b = events_sum / population_sum;
+    let s2 = rates
+        .iter()
+        .zip(population)
+        .map(|(r, p)| p * (r - b).powi(2))
+        .sum::<f64>()
+        / population_sum;
+    let a = s2 - b / (population_sum / n);
+
+    Ok(rates
+        .iter()
+        .zip(population)
+        .map(|(r, p)| {
+            let variance = a + b / p;
+            let variance = if variance < 0.0 { b / p } else { variance };
+            (r - b) / variance.sqrt()
+        })
+        .collect())
+}
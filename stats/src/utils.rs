@@ -0,0 +1,104 @@
+use nalgebra_sparse::csr::CsrMatrix;
+
+/// Complementary error function, using the Chebyshev fit from Numerical Recipes which has a
+/// fractional error below 1.2e-7 everywhere
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let result = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+/// The probability that a standard normal variable is greater than z
+pub fn normal_sf(z: f64) -> f64 {
+    0.5 * erfc(z / std::f64::consts::SQRT_2)
+}
+
+/// The two sided p value of a standard normal z score
+pub fn two_sided_p_value(z: f64) -> f64 {
+    (2.0 * normal_sf(z.abs())).min(1.0)
+}
+
+/// The pseudo p value of a statistic from its simulated values, defined as the fraction of
+/// simulations at least as extreme in the direction of the observed value
+pub fn pseudo_p_value(observed: f64, sims: &[f64]) -> f64 {
+    let permutations = sims.len();
+    let mut larger = sims.iter().filter(|s| **s >= observed).count();
+    if permutations - larger < larger {
+        larger = permutations - larger;
+    }
+    (larger as f64 + 1.0) / (permutations as f64 + 1.0)
+}
+
+/// Standardizes the values to have a mean of 0 and a (population) standard deviation of 1
+pub fn standardize(values: &[f64]) -> Result<Vec<f64>, String> {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    if std == 0.0 || !std.is_finite() {
+        return Err("Values can not be standardized when they are all equal".into());
+    }
+    Ok(values.iter().map(|v| (v - mean) / std).collect())
+}
+
+/// Returns the change after - before at each observation, checking both have the same length
+pub fn differences(before: &[f64], after: &[f64]) -> Result<Vec<f64>, String> {
+    if before.len() != after.len() {
+        return Err(format!(
+            "Expected the same number of values at both times but got {} and {}",
+            before.len(),
+            after.len()
+        ));
+    }
+    Ok(after.iter().zip(before).map(|(a, b)| a - b).collect())
+}
+
+/// Returns the mean and standard deviation of the simulated values
+pub fn sim_moments(sims: &[f64]) -> (f64, f64) {
+    let n = sims.len() as f64;
+    let mean = sims.iter().sum::<f64>() / n;
+    let variance = sims.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+/// The sums of a weights matrix used in the moments of the global statistics
+/// - s0: the sum of all the weights
+/// - s1: half the sum of (w_ij + w_ji)² over all pairs
+/// - s2: the sum over each observation of its row sum plus its column sum, squared
+pub struct WeightSums {
+    pub s0: f64,
+    pub s1: f64,
+    pub s2: f64,
+}
+
+impl WeightSums {
+    pub fn new(w_matrix: &CsrMatrix<f64>) -> Self {
+        let n = w_matrix.nrows();
+        let s0 = w_matrix.values().iter().sum();
+
+        let symmetric = w_matrix + &w_matrix.transpose();
+        let s1 = 0.5 * symmetric.values().iter().map(|v| v * v).sum::<f64>();
+
+        let mut row_col_sums = vec![0.0; n];
+        for (i, j, v) in w_matrix.triplet_iter() {
+            row_col_sums[i] += v;
+            row_col_sums[j] += v;
+        }
+        let s2 = row_col_sums.iter().map(|s| s * s).sum();
+
+        Self { s0, s1, s2 }
+    }
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "arrow")]
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, UInt8Type};
+use geo_stats::lisa::{lisa, PermutationMethod};
+use geo_weights::Weights;
+
+#[test]
+fn lisa_results_should_convert_to_record_batch() {
+    let origins = vec![0, 1, 2, 3];
+    let dests = vec![1, 2, 3, 4];
+    let weights = Weights::from_list_rep(&origins, &dests, &vec![1.0; 4], 5);
+    let values = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+
+    let result = lisa(&weights, &values, 99, false, PermutationMethod::FULL, None).unwrap();
+    let batch = result.to_record_batch(None).unwrap();
+    assert_eq!(batch.num_rows(), 5);
+    assert_eq!(batch.num_columns(), 4);
+    let p_vals = batch.column_by_name("p_val").unwrap();
+    assert_eq!(
+        p_vals.as_primitive::<Float64Type>().values(),
+        &result.p_vals[..]
+    );
+    assert_eq!(
+        batch
+            .column_by_name("quad")
+            .unwrap()
+            .as_string::<i32>()
+            .value(0),
+        "LL"
+    );
+
+    let batch = result.to_record_batch(Some(0.05)).unwrap();
+    assert_eq!(batch.num_columns(), 5);
+    assert_eq!(
+        batch
+            .column_by_name("cluster")
+            .unwrap()
+            .as_primitive::<UInt8Type>()
+            .values(),
+        &result.cluster_codes(0.05)[..]
+    );
+
+    let result = lisa(&weights, &values, 99, true, PermutationMethod::FULL, None).unwrap();
+    let batch = result.to_record_batch(None).unwrap();
+    assert_eq!(
+        batch
+            .column_by_name("sims")
+            .unwrap()
+            .as_list::<i32>()
+            .value(0)
+            .len(),
+        99
+    );
+}
@@ -0,0 +1,20 @@
+use geo_weights::Weights;
+use std::collections::HashMap;
+
+/// A 10 observation irregular lattice shared by the tests
+pub fn grid_weights() -> Weights {
+    let mut dict: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+
+    dict.insert(0, HashMap::from([(1, 1.0), (3, 1.0)]));
+    dict.insert(1, HashMap::from([(0, 1.0), (4, 1.0)]));
+    dict.insert(2, HashMap::from([(3, 1.0), (6, 1.0)]));
+    dict.insert(3, HashMap::from([(0, 1.0), (2, 1.0), (4, 1.0), (7, 1.0)]));
+    dict.insert(4, HashMap::from([(1, 1.0), (3, 1.0), (5, 1.0), (8, 1.0)]));
+    dict.insert(5, HashMap::from([(4, 1.0), (9, 1.0)]));
+    dict.insert(6, HashMap::from([(2, 1.0), (7, 1.0)]));
+    dict.insert(7, HashMap::from([(3, 1.0), (6, 1.0), (8, 1.0)]));
+    dict.insert(8, HashMap::from([(4, 1.0), (7, 1.0), (9, 1.0)]));
+    dict.insert(9, HashMap::from([(5, 1.0), (8, 1.0)]));
+
+    Weights::new(dict, 10)
+}
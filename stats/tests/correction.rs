@@ -0,0 +1,136 @@
+use geo_stats::correction::{correct_p_values, geoda_fdr_cutoff, Correction, LocalPValues};
+use geo_stats::join_counts::local_join_counts;
+use geo_stats::lisa::{lisa, PermutationMethod};
+use geo_weights::Weights;
+
+#[macro_use]
+extern crate approx;
+
+fn p_vals() -> Vec<f64> {
+    vec![0.01, 0.04, 0.03, 0.005, 0.2, 0.5, 0.045, 0.002, 0.9, 0.06]
+}
+
+fn assert_all_relative_eq(values: &[Option<f64>], expected: &[f64]) {
+    assert_eq!(values.len(), expected.len());
+    for (value, expected) in values.iter().zip(expected.iter()) {
+        assert_relative_eq!(value.unwrap(), expected, epsilon = 1e-10);
+    }
+}
+
+#[test]
+fn bonferroni_should_scale_by_number_of_tests() {
+    let result = correct_p_values(&p_vals(), Correction::Bonferroni, 0.05).unwrap();
+    assert_all_relative_eq(
+        &result.p_vals,
+        &[0.1, 0.4, 0.3, 0.05, 1.0, 1.0, 0.45, 0.02, 1.0, 0.6],
+    );
+    let significant: Vec<usize> = (0..10).filter(|i| result.significant[*i]).collect();
+    assert_eq!(significant, vec![3, 7]);
+    assert_eq!(result.cutoff, Some(0.005));
+}
+
+#[test]
+fn holm_should_step_down() {
+    let result = correct_p_values(&p_vals(), Correction::Holm, 0.05).unwrap();
+    assert_all_relative_eq(
+        &result.p_vals,
+        &[0.08, 0.24, 0.21, 0.045, 0.6, 1.0, 0.24, 0.02, 1.0, 0.24],
+    );
+    let significant: Vec<usize> = (0..10).filter(|i| result.significant[*i]).collect();
+    assert_eq!(significant, vec![3, 7]);
+}
+
+#[test]
+fn benjamini_hochberg_should_control_false_discovery_rate() {
+    let result = correct_p_values(&p_vals(), Correction::BenjaminiHochberg, 0.05).unwrap();
+    assert_all_relative_eq(
+        &result.p_vals,
+        &[
+            0.03333333333333333,
+            0.075,
+            0.075,
+            0.025,
+            0.25,
+            0.5555555555555556,
+            0.075,
+            0.02,
+            0.9,
+            0.08571428571428572,
+        ],
+    );
+    let significant: Vec<usize> = (0..10).filter(|i| result.significant[*i]).collect();
+    assert_eq!(significant, vec![0, 3, 7]);
+    assert_eq!(result.cutoff, Some(0.01));
+}
+
+#[test]
+fn geoda_fdr_should_stop_at_first_failure() {
+    let p_vals = vec![0.001, 0.012, 0.013, 0.02, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8];
+
+    // The second smallest p value is above 2 * 0.05 / 10 so GeoDa stops there, while
+    // Benjamini-Hochberg carries on to the fourth which is at 4 * 0.05 / 10
+    assert_eq!(geoda_fdr_cutoff(&p_vals, 0.05), Some(0.001));
+    let geoda = correct_p_values(&p_vals, Correction::GeoDaFdr, 0.05).unwrap();
+    assert_eq!(geoda.cutoff, Some(0.001));
+    assert!(geoda.p_vals.iter().all(|p| p.is_none()));
+    assert_eq!(geoda.significant.iter().filter(|s| **s).count(), 1);
+
+    let bh = correct_p_values(&p_vals, Correction::BenjaminiHochberg, 0.05).unwrap();
+    assert_eq!(bh.significant.iter().filter(|s| **s).count(), 4);
+
+    assert_eq!(geoda_fdr_cutoff(&[0.5, 0.9], 0.05), None);
+    let none = correct_p_values(&[0.5, 0.9], Correction::GeoDaFdr, 0.05).unwrap();
+    assert!(none.significant.iter().all(|s| !s));
+    assert_eq!(none.cutoff, None);
+}
+
+#[test]
+fn corrections_should_skip_missing_p_values() {
+    let p_vals = vec![Some(0.01), None, Some(0.02), None, Some(0.5)];
+    let result = correct_p_values(&p_vals, Correction::Bonferroni, 0.05).unwrap();
+
+    // Only the three given p values count as tests
+    assert_eq!(result.p_vals[1], None);
+    assert_eq!(result.p_vals[3], None);
+    assert_relative_eq!(result.p_vals[0].unwrap(), 0.03, epsilon = 1e-10);
+    assert_relative_eq!(result.p_vals[2].unwrap(), 0.06, epsilon = 1e-10);
+    assert_relative_eq!(result.p_vals[4].unwrap(), 1.0, epsilon = 1e-10);
+    assert_eq!(result.significant, vec![true, false, false, false, false]);
+    assert_eq!(result.cutoff, Some(0.01));
+
+    assert_eq!(geoda_fdr_cutoff(&p_vals, 0.05), Some(0.02));
+}
+
+#[test]
+fn corrections_should_reject_invalid_input() {
+    assert!(correct_p_values(&[0.5, 1.5], Correction::Holm, 0.05).is_err());
+    assert!(correct_p_values(&[0.5, f64::NAN], Correction::Holm, 0.05).is_err());
+    assert!(correct_p_values(&[0.5], Correction::Holm, 2.0).is_err());
+}
+
+#[test]
+fn lisa_results_should_be_correctable() {
+    let origins: Vec<usize> = (0..7).collect();
+    let dests: Vec<usize> = (1..8).collect();
+    let weights = Weights::from_list_rep(&origins, &dests, &vec![1.0; 7], 8);
+    let values: Vec<f64> = (0..8).map(|v| v as f64).collect();
+
+    let result = lisa(&weights, &values, 99, false, PermutationMethod::FULL, None).unwrap();
+    let corrected = result
+        .corrected_p_values(Correction::Bonferroni, 0.05)
+        .unwrap();
+    for (raw, adjusted) in result.p_vals.iter().zip(corrected.p_vals.iter()) {
+        assert_relative_eq!(adjusted.unwrap(), (raw * 8.0).min(1.0), epsilon = 1e-10);
+    }
+    assert_eq!(corrected.correction, Correction::Bonferroni);
+
+    // Local join counts only give p values for true observations
+    let binary: Vec<bool> = (0..8).map(|v| v >= 4).collect();
+    let counts = local_join_counts(&weights, &binary, 99, false, Some(1)).unwrap();
+    let corrected = counts
+        .corrected_p_values(Correction::BenjaminiHochberg, 0.05)
+        .unwrap();
+    for (p_sim, adjusted) in counts.p_sim.iter().zip(corrected.p_vals.iter()) {
+        assert_eq!(p_sim.is_some(), adjusted.is_some());
+    }
+}
@@ -0,0 +1,90 @@
+use geo_stats::geary::{geary, local_geary, multivariate_local_geary, GearyCluster};
+
+mod common;
+
+use common::grid_weights;
+
+#[macro_use]
+extern crate approx;
+
+fn values() -> Vec<f64> {
+    vec![
+        2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
+    ]
+}
+
+#[test]
+fn geary_should_produce_correct_moments() {
+    let result = geary(&grid_weights(), &values(), 999, true, None).unwrap();
+
+    assert_relative_eq!(result.c, 0.9129707584831759, epsilon = 1e-10);
+    assert_relative_eq!(result.expected_c, 1.0);
+    assert_relative_eq!(result.variance_norm, 0.051477272727272726, epsilon = 1e-10);
+    assert_relative_eq!(result.variance_rand, 0.054054268553907686, epsilon = 1e-10);
+    assert_relative_eq!(result.z_norm, -0.3835813061136813, epsilon = 1e-10);
+    assert_relative_eq!(result.z_rand, -0.3743261789271795, epsilon = 1e-10);
+
+    assert_eq!(result.sims.len(), 999);
+    assert!(result.p_sim.unwrap() <= 0.5);
+}
+
+#[test]
+fn local_geary_should_produce_correct_values_and_clusters() {
+    let result = local_geary(&grid_weights(), &values(), 99, 1.0, false, None).unwrap();
+    let expected = vec![
+        1.4071858388217406,
+        1.4450147136018598,
+        2.4169320619848484,
+        4.449832403848051,
+        1.0866929001100087,
+        0.9130004012708061,
+        0.33279494980454055,
+        5.70028602832542,
+        2.1975830603112256,
+        0.3389167193254094,
+    ];
+    for (value, expected) in result.geary_vals.iter().zip(expected) {
+        assert_relative_eq!(*value, expected, epsilon = 1e-10);
+    }
+
+    // With a significance of 1 every observation is classified
+    assert_eq!(result.clusters[0], GearyCluster::OtherPositive);
+    assert_eq!(result.clusters[3], GearyCluster::Negative);
+    assert_eq!(result.clusters[6], GearyCluster::HighHigh);
+    assert_eq!(result.clusters[9], GearyCluster::LowLow);
+    assert!(result.p_vals.iter().all(|p| *p > 0.0 && *p <= 0.5));
+    assert!(result.sims.is_empty());
+
+    let result = local_geary(&grid_weights(), &values(), 99, 0.0, true, None).unwrap();
+    assert!(result
+        .clusters
+        .iter()
+        .all(|c| *c == GearyCluster::NotSignificant));
+    assert_eq!(result.sims[0].len(), 99);
+}
+
+#[test]
+fn multivariate_local_geary_should_average_variables() {
+    let univariate = local_geary(&grid_weights(), &values(), 0, 0.05, false, None).unwrap();
+    let columns = vec![values(), values().iter().map(|v| v * 3.0 + 1.0).collect()];
+    let result = multivariate_local_geary(&grid_weights(), &columns, 99, 1.0, false, None).unwrap();
+
+    for (a, b) in result.geary_vals.iter().zip(univariate.geary_vals.iter()) {
+        assert_relative_eq!(*a, *b, epsilon = 1e-10);
+    }
+    assert_eq!(result.clusters[6], GearyCluster::Positive);
+    assert_eq!(result.clusters[7], GearyCluster::Negative);
+
+    assert!(multivariate_local_geary(&grid_weights(), &[], 99, 0.05, false, None).is_err());
+}
+
+#[test]
+fn geary_should_be_reproducible_with_a_seed() {
+    let run = |seed| geary(&grid_weights(), &values(), 99, true, Some(seed)).unwrap();
+    assert_eq!(run(1).sims, run(1).sims);
+    assert_ne!(run(1).sims, run(2).sims);
+
+    let local = |seed| local_geary(&grid_weights(), &values(), 99, 0.05, true, Some(seed)).unwrap();
+    assert_eq!(local(1).sims, local(1).sims);
+    assert_eq!(local(1).p_vals, local(1).p_vals);
+}
@@ -0,0 +1,210 @@
+use geo_stats::getis_ord::{general_g, local_g, HotSpot, LocalGType};
+use geo_weights::weights::TransformType;
+
+mod common;
+
+use common::grid_weights;
+
+#[macro_use]
+extern crate approx;
+
+fn values() -> Vec<f64> {
+    vec![2.24, 3.1, 4.55, 5.15, 4.39, 0.46, 5.54, 9.02, 2.09, 3.06]
+}
+
+#[test]
+fn general_g_should_produce_correct_moments() {
+    let result = general_g(&grid_weights(), &values(), 999, true, None).unwrap();
+
+    assert_relative_eq!(result.g, 0.3491596132369361, epsilon = 1e-10);
+    assert_relative_eq!(result.expected_g, 0.28888888888888886, epsilon = 1e-10);
+    assert_relative_eq!(result.variance, 0.0015003271712608068, epsilon = 1e-10);
+    assert_relative_eq!(result.z_norm, 1.556013726020283, epsilon = 1e-10);
+    assert_relative_eq!(result.p_norm, 0.11970482846596484, epsilon = 1e-6);
+
+    assert_eq!(result.sims.len(), 999);
+    let p_sim = result.p_sim.unwrap();
+    assert!(p_sim > 0.0 && p_sim <= 0.5);
+}
+
+#[test]
+fn general_g_should_reject_negative_values() {
+    let mut values = values();
+    values[3] = -1.0;
+    assert!(general_g(&grid_weights(), &values, 0, false, None).is_err());
+    assert!(local_g(
+        &grid_weights(),
+        &values,
+        LocalGType::Gi,
+        None,
+        0,
+        false,
+        None
+    )
+    .is_err());
+}
+
+#[test]
+fn local_gi_should_match_reference_values() {
+    let result = local_g(
+        &grid_weights(),
+        &values(),
+        LocalGType::Gi,
+        None,
+        99,
+        true,
+        None,
+    )
+    .unwrap();
+
+    let expected_g = vec![
+        0.22082441113490361,
+        0.18164383561643832,
+        0.30499286733238223,
+        0.5863570391872277,
+        0.3067310423175234,
+        0.19034236075625952,
+        0.3984145625366998,
+        0.41792020928711565,
+        0.4390829112236736,
+        0.06978653530377667,
+    ];
+    let expected_z = vec![
+        -0.01727152297542052,
+        -0.4774910906952557,
+        0.9311966133408488,
+        1.328803416690178,
+        -1.2998130132171641,
+        -0.4671415789205018,
+        1.974008306501604,
+        1.106597123480801,
+        1.1644206302846505,
+        -1.797117068570011,
+    ];
+
+    for i in 0..10 {
+        assert_relative_eq!(result.g_vals[i], expected_g[i], epsilon = 1e-10);
+        assert_relative_eq!(result.z_vals[i], expected_z[i], epsilon = 1e-10);
+        assert_eq!(result.sims[i].len(), 99);
+        assert!(result.p_sim[i] > 0.0 && result.p_sim[i] <= 0.5);
+    }
+    assert_eq!(result.hot_spots[6], HotSpot::Hot95);
+    assert_eq!(result.hot_spots[9], HotSpot::Cold90);
+    assert_eq!(result.hot_spots[0], HotSpot::NotSignificant);
+}
+
+#[test]
+fn local_gi_star_should_match_reference_values() {
+    let g_type = LocalGType::GiStar { self_weight: 1.0 };
+    let binary = local_g(&grid_weights(), &values(), g_type, None, 0, false, None).unwrap();
+    let row = local_g(
+        &grid_weights(),
+        &values(),
+        g_type,
+        Some(TransformType::Row),
+        0,
+        false,
+        None,
+    )
+    .unwrap();
+
+    let expected_binary_g = vec![
+        0.26489898989898986,
+        0.24570707070707065,
+        0.38484848484848483,
+        0.640151515151515,
+        0.38358585858585853,
+        0.19974747474747467,
+        0.48257575757575744,
+        0.5505050505050504,
+        0.46868686868686854,
+        0.14166666666666664,
+    ];
+    let expected_row_g = vec![
+        0.08829966329966328,
+        0.08190235690235688,
+        0.12828282828282825,
+        0.12803030303030302,
+        0.07671717171717171,
+        0.06658249158249156,
+        0.16085858585858584,
+        0.1376262626262626,
+        0.11717171717171714,
+        0.04722222222222221,
+    ];
+    let expected_z = vec![
+        -0.40571881429192785,
+        -0.6275506839767225,
+        0.9807303712380396,
+        1.4847145804821154,
+        -1.233249408292353,
+        -1.1587796350639945,
+        2.110321602396138,
+        1.6272738055311835,
+        0.7426484481618815,
+        -1.8301129248995573,
+    ];
+
+    for i in 0..10 {
+        assert_relative_eq!(binary.g_vals[i], expected_binary_g[i], epsilon = 1e-10);
+        assert_relative_eq!(row.g_vals[i], expected_row_g[i], epsilon = 1e-10);
+        // The z-scores are unchanged by scaling each row of the weights
+        assert_relative_eq!(binary.z_vals[i], expected_z[i], epsilon = 1e-10);
+        assert_relative_eq!(row.z_vals[i], expected_z[i], epsilon = 1e-10);
+    }
+    assert!(binary.sims.is_empty());
+}
+
+#[test]
+fn hot_spots_should_be_classified_by_confidence() {
+    assert_eq!(HotSpot::classify(3.0, 0.003), HotSpot::Hot99);
+    assert_eq!(HotSpot::classify(2.0, 0.04), HotSpot::Hot95);
+    assert_eq!(HotSpot::classify(1.7, 0.09), HotSpot::Hot90);
+    assert_eq!(HotSpot::classify(-3.0, 0.003), HotSpot::Cold99);
+    assert_eq!(HotSpot::classify(-2.0, 0.04), HotSpot::Cold95);
+    assert_eq!(HotSpot::classify(-1.7, 0.09), HotSpot::Cold90);
+    assert_eq!(HotSpot::classify(0.5, 0.6), HotSpot::NotSignificant);
+}
+
+#[test]
+fn local_gi_should_leave_undefined_z_scores_as_nan() {
+    // Leaving out the first observation all the remaining values are equal
+    let mut values = vec![1.0; 10];
+    values[0] = 5.0;
+
+    let result = local_g(
+        &grid_weights(),
+        &values,
+        LocalGType::Gi,
+        None,
+        0,
+        false,
+        None,
+    )
+    .unwrap();
+    assert!(result.z_vals[0].is_nan());
+    assert!(result.p_norm[0].is_nan());
+    assert_eq!(result.hot_spots[0], HotSpot::NotSignificant);
+    assert!(result.z_vals[1..].iter().all(|z| z.is_finite()));
+}
+
+#[test]
+fn getis_ord_should_be_reproducible_with_a_seed() {
+    let run = |seed| general_g(&grid_weights(), &values(), 99, true, Some(seed)).unwrap();
+    assert_eq!(run(1).sims, run(1).sims);
+    assert_ne!(run(1).sims, run(2).sims);
+
+    let local = |seed| {
+        local_g(
+            &grid_weights(),
+            &values(),
+            LocalGType::Gi,
+            None,
+            99,
+            true,
+            Some(seed),
+        )
+    };
+    assert_eq!(local(1).unwrap().sims, local(1).unwrap().sims);
+    assert_eq!(local(1).unwrap().p_sim, local(1).unwrap().p_sim);
+}
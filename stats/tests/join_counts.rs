@@ -0,0 +1,142 @@
+use geo_stats::join_counts::{
+    join_counts, local_bivariate_join_counts, local_colocation_join_counts, local_join_counts,
+};
+use geo_weights::Weights;
+
+mod common;
+
+use common::grid_weights;
+
+#[macro_use]
+extern crate approx;
+
+fn to_bools(values: &[u8]) -> Vec<bool> {
+    values.iter().map(|v| *v == 1).collect()
+}
+
+#[test]
+fn join_counts_should_produce_correct_moments() {
+    let values = to_bools(&[1, 1, 0, 0, 0, 1, 1, 0, 1, 0]);
+    let result = join_counts(&grid_weights(), &values, 999, true, None).unwrap();
+
+    assert_relative_eq!(result.joins, 13.0);
+    assert_relative_eq!(result.bb.count, 1.0);
+    assert_relative_eq!(result.bw.count, 9.0);
+    assert_relative_eq!(result.ww.count, 3.0);
+
+    // Moments checked against an exact enumeration of every arrangement of the values
+    assert_relative_eq!(result.bb.expected, 2.888888888888889, epsilon = 1e-10);
+    assert_relative_eq!(result.bb.variance, 1.1146384479717817, epsilon = 1e-10);
+    assert_relative_eq!(result.bw.expected, 7.222222222222222, epsilon = 1e-10);
+    assert_relative_eq!(result.bw.variance, 2.680776014109348, epsilon = 1e-10);
+    assert_relative_eq!(result.ww.expected, 2.888888888888889, epsilon = 1e-10);
+    assert_relative_eq!(result.ww.variance, 1.1146384479717817, epsilon = 1e-10);
+
+    assert_relative_eq!(result.bb.z_norm, -1.789119718489684, epsilon = 1e-10);
+    assert_relative_eq!(result.bb.p_norm, 0.073595537177612, epsilon = 1e-6);
+    assert_relative_eq!(result.bw.z_norm, 1.0857934280612735, epsilon = 1e-10);
+
+    assert_eq!(result.bb.sims.len(), 999);
+    for (bb, (bw, ww)) in result
+        .bb
+        .sims
+        .iter()
+        .zip(result.bw.sims.iter().zip(result.ww.sims.iter()))
+    {
+        assert_relative_eq!(bb + bw + ww, 13.0);
+    }
+    assert!(result.bb.p_sim.unwrap() <= 0.5);
+}
+
+#[test]
+fn join_counts_should_check_values() {
+    assert!(join_counts(&grid_weights(), &[true, false], 0, false, None).is_err());
+}
+
+#[test]
+fn local_join_counts_should_count_true_neighbors() {
+    let values = to_bools(&[1, 1, 0, 0, 0, 1, 1, 0, 1, 0]);
+    let result = local_join_counts(&grid_weights(), &values, 99, true, None).unwrap();
+
+    assert_eq!(
+        result.counts,
+        vec![1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+    );
+    for (index, value) in values.iter().enumerate() {
+        assert_eq!(result.p_sim[index].is_some(), *value);
+        assert_eq!(result.sims[index].len(), 99);
+    }
+    // Observations with no true neighbors can never be significant
+    assert_relative_eq!(result.p_sim[5].unwrap(), 1.0);
+}
+
+#[test]
+fn local_bivariate_join_counts_should_count_other_variable() {
+    let x = to_bools(&[1, 1, 0, 0, 0, 1, 1, 0, 1, 0]);
+    let z = to_bools(&[0, 1, 1, 0, 1, 1, 0, 0, 1, 1]);
+    let result = local_bivariate_join_counts(&grid_weights(), &x, &z, 0, false, None).unwrap();
+
+    assert_eq!(
+        result.counts,
+        vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0]
+    );
+    assert!(result.p_sim.iter().all(|p| p.is_none()));
+}
+
+#[test]
+fn local_colocation_join_counts_should_need_every_variable() {
+    let x = to_bools(&[1, 1, 0, 0, 0, 1, 1, 0, 1, 0]);
+    let z = to_bools(&[1, 1, 0, 0, 1, 1, 0, 0, 1, 1]);
+    let result = local_colocation_join_counts(&grid_weights(), &[x, z], 0, false, None).unwrap();
+
+    assert_eq!(
+        result.counts,
+        vec![1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+    );
+    assert!(local_colocation_join_counts(&grid_weights(), &[], 0, false, None).is_err());
+}
+
+#[test]
+fn join_counts_should_symmetrize_asymmetric_weights() {
+    // Each observation only lists its next neighbor along a chain, as KNN can
+    let origins: Vec<usize> = (0..5).collect();
+    let dests: Vec<usize> = (1..6).collect();
+    let one_way = Weights::from_list_rep(&origins, &dests, &vec![1.0; 5], 6);
+    let both_ways = Weights::from_list_rep(
+        &origins
+            .iter()
+            .chain(dests.iter())
+            .copied()
+            .collect::<Vec<_>>(),
+        &dests
+            .iter()
+            .chain(origins.iter())
+            .copied()
+            .collect::<Vec<_>>(),
+        &vec![1.0; 10],
+        6,
+    );
+    let values = to_bools(&[1, 1, 0, 1, 0, 0]);
+
+    let result = join_counts(&one_way, &values, 0, false, None).unwrap();
+    let expected = join_counts(&both_ways, &values, 0, false, None).unwrap();
+    assert_relative_eq!(result.joins, 5.0);
+    assert_relative_eq!(result.bb.count, 1.0);
+    assert_relative_eq!(result.bw.count, 3.0);
+    assert_relative_eq!(result.ww.count, 1.0);
+    assert_relative_eq!(result.bb.variance, expected.bb.variance);
+
+    let local = local_join_counts(&one_way, &values, 0, false, None).unwrap();
+    assert_eq!(local.counts, vec![1.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn join_counts_should_be_reproducible_with_a_seed() {
+    let values = to_bools(&[1, 1, 0, 0, 0, 1, 1, 0, 1, 0]);
+    let run = |seed| join_counts(&grid_weights(), &values, 99, true, Some(seed)).unwrap();
+    assert_eq!(run(1).bb.sims, run(1).bb.sims);
+    assert_ne!(run(1).bb.sims, run(2).bb.sims);
+
+    let local = |seed| local_join_counts(&grid_weights(), &values, 99, true, Some(seed)).unwrap();
+    assert_eq!(local(1).sims, local(1).sims);
+}
@@ -3,14 +3,22 @@
 extern crate test;
 
 use geo::{polygon, Geometry, GeometryCollection};
-use geo_stats::lisa::{lisa, PermutationMethod};
+use geo_stats::lisa::{
+    bivariate_lisa, differential_lisa, empirical_bayes_lisa, lisa,
+    seeded_generate_perturbation_lookups, Cluster, LISAResult, PermutationMethod, Quad,
+};
+use geo_stats::rates::empirical_bayes_standardize;
 use geo_weights::{QueensWeights, WeightBuilder, Weights};
 use geojson::{quick_collection, FeatureCollection, GeoJson};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Write;
 use test::{black_box, Bencher};
 
+mod common;
+
+use common::grid_weights;
+
 #[macro_use]
 extern crate approx;
 
@@ -20,22 +28,9 @@ fn lisa_should_produce_correct_values_using_full_permutation() {
         2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
     ];
 
-    let mut dict: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
-
-    dict.insert(0, HashMap::from([(1, 1.0), (3, 1.0)]));
-    dict.insert(1, HashMap::from([(0, 1.0), (4, 1.0)]));
-    dict.insert(2, HashMap::from([(3, 1.0), (6, 1.0)]));
-    dict.insert(3, HashMap::from([(0, 1.0), (2, 1.0), (4, 1.0), (7, 1.0)]));
-    dict.insert(4, HashMap::from([(1, 1.0), (3, 1.0), (5, 1.0), (8, 1.0)]));
-    dict.insert(5, HashMap::from([(4, 1.0), (9, 1.0)]));
-    dict.insert(6, HashMap::from([(2, 1.0), (7, 1.0)]));
-    dict.insert(7, HashMap::from([(3, 1.0), (6, 1.0), (8, 1.0)]));
-    dict.insert(8, HashMap::from([(4, 1.0), (7, 1.0), (9, 1.0)]));
-    dict.insert(9, HashMap::from([(5, 1.0), (8, 1.0)]));
-
-    let weights = Weights::new(dict, 10);
+    let weights = grid_weights();
 
-    let moran = lisa(&weights, &values, 9999, true, PermutationMethod::FULL).unwrap();
+    let moran = lisa(&weights, &values, 9999, true, PermutationMethod::FULL, None).unwrap();
     let expected: Vec<f64> = vec![
         -0.11409277104439629,
         -0.19940542567754874,
@@ -61,22 +56,17 @@ fn lisa_should_produce_correct_values_using_lookup_permutation() {
         2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
     ];
 
-    let mut dict: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+    let weights = grid_weights();
 
-    dict.insert(0, HashMap::from([(1, 1.0), (3, 1.0)]));
-    dict.insert(1, HashMap::from([(0, 1.0), (4, 1.0)]));
-    dict.insert(2, HashMap::from([(3, 1.0), (6, 1.0)]));
-    dict.insert(3, HashMap::from([(0, 1.0), (2, 1.0), (4, 1.0), (7, 1.0)]));
-    dict.insert(4, HashMap::from([(1, 1.0), (3, 1.0), (5, 1.0), (8, 1.0)]));
-    dict.insert(5, HashMap::from([(4, 1.0), (9, 1.0)]));
-    dict.insert(6, HashMap::from([(2, 1.0), (7, 1.0)]));
-    dict.insert(7, HashMap::from([(3, 1.0), (6, 1.0), (8, 1.0)]));
-    dict.insert(8, HashMap::from([(4, 1.0), (7, 1.0), (9, 1.0)]));
-    dict.insert(9, HashMap::from([(5, 1.0), (8, 1.0)]));
-
-    let weights = Weights::new(dict, 10);
-
-    let moran = lisa(&weights, &values, 9999, true, PermutationMethod::LOOKUP).unwrap();
+    let moran = lisa(
+        &weights,
+        &values,
+        9999,
+        true,
+        PermutationMethod::LOOKUP,
+        None,
+    )
+    .unwrap();
     let expected: Vec<f64> = vec![
         -0.11409277104439629,
         -0.19940542567754874,
@@ -96,6 +86,250 @@ fn lisa_should_produce_correct_values_using_lookup_permutation() {
     let mut file = File::create("results.json").unwrap();
 }
 
+#[test]
+fn bivariate_lisa_should_produce_correct_values() {
+    let x: Vec<f64> = vec![
+        2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
+    ];
+    let y: Vec<f64> = vec![1.5, 2.0, 3.1, -1.2, 0.4, 2.2, 4.8, 6.1, -0.7, 1.1];
+
+    let weights = grid_weights();
+
+    let result =
+        bivariate_lisa(&weights, &x, &y, 999, true, PermutationMethod::FULL, None).unwrap();
+    let expected: Vec<f64> = vec![
+        -0.17444274956696332,
+        -0.1906276189017391,
+        -0.042932495498562956,
+        -0.4881989383252122,
+        0.6864537588081979,
+        0.06207725823047929,
+        1.1292021392571407,
+        -0.7212273577699243,
+        -0.1757563855781336,
+        0.4508885553324136,
+    ];
+    for (value, expected) in result.moran_val.iter().zip(expected.iter()) {
+        assert_relative_eq!(value, expected, epsilon = 1e-10);
+    }
+    assert_relative_eq!(result.lags[6], 1.2315660610216443, epsilon = 1e-10);
+    assert!(matches!(result.quads[0], Quad::HL));
+    assert!(matches!(result.quads[3], Quad::LH));
+    assert!(matches!(result.quads[4], Quad::LL));
+    assert!(matches!(result.quads[6], Quad::HH));
+    assert!(result.sims.iter().all(|sims| sims.len() == 999));
+    assert!(result.p_vals.iter().all(|p| *p > 0.0 && *p <= 0.5));
+
+    // With the same variable twice it reduces to the univariate lisa
+    let univariate = lisa(&weights, &x, 0, false, PermutationMethod::FULL, None).unwrap();
+    let same = bivariate_lisa(&weights, &x, &x, 0, false, PermutationMethod::FULL, None).unwrap();
+    for (a, b) in univariate.moran_val.iter().zip(same.moran_val.iter()) {
+        assert_relative_eq!(a, b, epsilon = 1e-10);
+    }
+
+    assert!(bivariate_lisa(
+        &weights,
+        &x,
+        &y[..5],
+        0,
+        false,
+        PermutationMethod::FULL,
+        None
+    )
+    .is_err());
+}
+
+#[test]
+fn differential_lisa_should_use_the_change() {
+    let before: Vec<f64> = vec![1.5, 2.0, 3.1, -1.2, 0.4, 2.2, 4.8, 6.1, -0.7, 1.1];
+    let change: Vec<f64> = vec![
+        2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
+    ];
+    let after: Vec<f64> = before.iter().zip(&change).map(|(b, c)| b + c).collect();
+
+    let weights = grid_weights();
+
+    let result = differential_lisa(
+        &weights,
+        &before,
+        &after,
+        99,
+        true,
+        PermutationMethod::FULL,
+        Some(42),
+    )
+    .unwrap();
+    let expected = lisa(
+        &weights,
+        &change,
+        99,
+        true,
+        PermutationMethod::FULL,
+        Some(42),
+    )
+    .unwrap();
+    for (value, expected) in result.moran_val.iter().zip(expected.moran_val.iter()) {
+        assert_relative_eq!(value, expected, epsilon = 1e-10);
+    }
+    assert!(result.sims.iter().all(|sims| sims.len() == 99));
+    assert_eq!(result.p_vals, expected.p_vals);
+
+    assert!(differential_lisa(
+        &weights,
+        &before,
+        &after[..5],
+        0,
+        false,
+        PermutationMethod::FULL,
+        None
+    )
+    .is_err());
+}
+
+#[test]
+fn empirical_bayes_lisa_should_use_standardized_rates() {
+    let events: Vec<f64> = vec![12.0, 3.0, 40.0, 8.0, 2.0, 15.0, 30.0, 55.0, 4.0, 9.0];
+    let population: Vec<f64> = vec![
+        1000.0, 150.0, 2500.0, 900.0, 400.0, 800.0, 1200.0, 2600.0, 350.0, 1100.0,
+    ];
+
+    let weights = grid_weights();
+
+    let result = empirical_bayes_lisa(
+        &weights,
+        &events,
+        &population,
+        99,
+        false,
+        PermutationMethod::LOOKUP,
+        None,
+    )
+    .unwrap();
+    let rates = empirical_bayes_standardize(&events, &population).unwrap();
+    let expected = lisa(&weights, &rates, 0, false, PermutationMethod::FULL, None).unwrap();
+    for (value, expected) in result.moran_val.iter().zip(expected.moran_val.iter()) {
+        assert_relative_eq!(value, expected, epsilon = 1e-10);
+    }
+    assert_eq!(result.p_vals.len(), 10);
+}
+
+#[test]
+fn lisa_clusters_should_use_significance_and_neighbors() {
+    let result = LISAResult {
+        moran_val: vec![0.5, 0.4, -0.3, -0.2, 0.1, f64::NAN, 0.0],
+        quads: vec![
+            Quad::HH,
+            Quad::LL,
+            Quad::LH,
+            Quad::HL,
+            Quad::HH,
+            Quad::HH,
+            Quad::HL,
+        ],
+        lags: vec![1.0, -1.0, 1.0, -1.0, 0.5, f64::NAN, 0.0],
+        p_vals: vec![0.01, 0.02, 0.03, 0.04, 0.2, 0.01, 0.01],
+        sims: vec![],
+        no_neighbors: vec![2, 2, 3, 1, 2, 2, 0],
+    };
+
+    assert_eq!(
+        result.clusters(0.05),
+        vec![
+            Cluster::HighHigh,
+            Cluster::LowLow,
+            Cluster::LowHigh,
+            Cluster::HighLow,
+            Cluster::NotSignificant,
+            Cluster::Undefined,
+            Cluster::Neighborless,
+        ]
+    );
+    assert_eq!(result.cluster_codes(0.05), vec![1, 2, 3, 4, 0, 5, 6]);
+    assert_eq!(result.cluster_codes(0.015), vec![1, 0, 0, 0, 0, 5, 6]);
+}
+
+#[test]
+fn lisa_should_record_neighborless_observations() {
+    // A chain of 7 observations and an island
+    let origins: Vec<usize> = (0..6).collect();
+    let dests: Vec<usize> = (1..7).collect();
+    let weights = Weights::from_list_rep(&origins, &dests, &vec![1.0; 6], 8);
+    let values: Vec<f64> = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 4.0];
+
+    let result = lisa(&weights, &values, 99, false, PermutationMethod::FULL, None).unwrap();
+    assert_eq!(result.no_neighbors, vec![1, 2, 2, 2, 2, 2, 1, 0]);
+    assert_eq!(result.clusters(1.0)[7], Cluster::Neighborless);
+    assert_eq!(result.clusters(1.0)[0], Cluster::LowLow);
+    assert_eq!(result.clusters(1.0)[6], Cluster::HighHigh);
+
+    let bivariate = bivariate_lisa(
+        &weights,
+        &values,
+        &values,
+        0,
+        false,
+        PermutationMethod::FULL,
+        None,
+    )
+    .unwrap();
+    assert_eq!(bivariate.no_neighbors, result.no_neighbors);
+}
+
+#[test]
+fn lisa_should_be_reproducible_with_a_seed() {
+    let values: Vec<f64> = vec![
+        2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
+    ];
+
+    let weights = grid_weights();
+
+    for method in [PermutationMethod::FULL, PermutationMethod::LOOKUP] {
+        let run = |seed: u64| lisa(&weights, &values, 999, true, method, Some(seed)).unwrap();
+        let first = run(42);
+
+        // The same seed gives the same results on a single thread as on many
+        let single_thread = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| run(42));
+        assert_eq!(first.p_vals, single_thread.p_vals);
+        assert_eq!(first.sims, single_thread.sims);
+
+        let other_seed = run(43);
+        assert_ne!(first.sims, other_seed.sims);
+
+        // Dropping the simulations as they are reduced gives the same p values
+        let without_sims = lisa(&weights, &values, 999, false, method, Some(42)).unwrap();
+        assert_eq!(without_sims.p_vals, first.p_vals);
+        assert!(without_sims.sims.is_empty());
+    }
+
+    assert_eq!(
+        seeded_generate_perturbation_lookups(4, 99, 10, 7),
+        seeded_generate_perturbation_lookups(4, 99, 10, 7)
+    );
+    assert_ne!(
+        seeded_generate_perturbation_lookups(4, 99, 10, 7),
+        seeded_generate_perturbation_lookups(4, 99, 10, 8)
+    );
+
+    let bivariate = |seed| {
+        bivariate_lisa(
+            &weights,
+            &values,
+            &values,
+            99,
+            true,
+            PermutationMethod::LOOKUP,
+            Some(seed),
+        )
+        .unwrap()
+    };
+    assert_eq!(bivariate(5).sims, bivariate(5).sims);
+    assert_ne!(bivariate(5).sims, bivariate(6).sims);
+}
+
 #[test]
 fn real_data() {
     let jsonfile = std::fs::read_to_string(format!(
@@ -116,7 +350,15 @@ fn real_data() {
             .map(|f| f.property("Donatns").unwrap().as_f64().unwrap())
             .collect();
 
-        let lisa_results = lisa(&weights, &values, 9999, false, PermutationMethod::LOOKUP).unwrap();
+        let lisa_results = lisa(
+            &weights,
+            &values,
+            9999,
+            false,
+            PermutationMethod::LOOKUP,
+            None,
+        )
+        .unwrap();
         println!("{:#?}, {:#?}", lisa_results.moran_val, lisa_results.p_vals);
         let j = serde_json::to_string(&lisa_results).unwrap();
         let mut file = File::create("results_real.json").unwrap();
@@ -151,7 +393,15 @@ fn real_data_sdh() {
             })
             .collect();
 
-        let lisa_results = lisa(&weights, &values, 9999, false, PermutationMethod::LOOKUP).unwrap();
+        let lisa_results = lisa(
+            &weights,
+            &values,
+            9999,
+            false,
+            PermutationMethod::LOOKUP,
+            None,
+        )
+        .unwrap();
         println!("{:#?}, {:#?}", lisa_results.moran_val, lisa_results.p_vals);
         let j = serde_json::to_string(&lisa_results).unwrap();
         let mut file = File::create("results_real.json").unwrap();
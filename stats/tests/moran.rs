@@ -0,0 +1,115 @@
+use geo_stats::moran::{bivariate_moran, differential_moran, empirical_bayes_moran, moran};
+use geo_weights::Weights;
+
+mod common;
+
+use common::grid_weights;
+
+#[macro_use]
+extern crate approx;
+
+#[test]
+fn moran_should_produce_correct_moments() {
+    let values: Vec<f64> = vec![
+        2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
+    ];
+
+    let result = moran(&grid_weights(), &values, 999, true, None).unwrap();
+
+    assert_relative_eq!(result.i, 0.07361243111060219, epsilon = 1e-10);
+    assert_relative_eq!(result.expected_i, -1.0 / 9.0, epsilon = 1e-10);
+    assert_relative_eq!(result.variance_norm, 0.057491582491582496, epsilon = 1e-10);
+    assert_relative_eq!(result.variance_rand, 0.06296699883493344, epsilon = 1e-10);
+    assert_relative_eq!(result.z_norm, 0.7704068159278009, epsilon = 1e-10);
+    assert_relative_eq!(result.z_rand, 0.7361490412723056, epsilon = 1e-10);
+    assert_relative_eq!(result.p_norm, 0.4410586115435101, epsilon = 1e-6);
+    assert_relative_eq!(result.p_rand, 0.461640000355457, epsilon = 1e-6);
+
+    assert_eq!(result.sims.len(), 999);
+    let p_sim = result.p_sim.unwrap();
+    assert!(p_sim > 0.0 && p_sim <= 0.5);
+}
+
+#[test]
+fn moran_should_detect_strong_clustering() {
+    // A 1 x 8 chain with low values on the left and high values on the right
+    let origins: Vec<usize> = (0..7).collect();
+    let dests: Vec<usize> = (1..8).collect();
+    let weights = Weights::from_list_rep(&origins, &dests, &vec![1.0; 7], 8);
+    let values: Vec<f64> = (0..8).map(|v| v as f64).collect();
+
+    let result = moran(&weights, &values, 0, false, None).unwrap();
+    assert!(result.i > 0.5);
+    assert!(result.p_norm < 0.05);
+    assert!(result.p_sim.is_none());
+    assert!(result.sims.is_empty());
+}
+
+#[test]
+fn moran_should_reject_invalid_input() {
+    assert!(moran(&grid_weights(), &[1.0, 2.0], 0, false, None).is_err());
+    assert!(moran(&grid_weights(), &[1.0; 10], 0, false, None).is_err());
+}
+
+#[test]
+fn bivariate_moran_should_produce_correct_values() {
+    let x: Vec<f64> = vec![
+        2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
+    ];
+    let y: Vec<f64> = vec![1.5, 2.0, 3.1, -1.2, 0.4, 2.2, 4.8, 6.1, -0.7, 1.1];
+
+    let result = bivariate_moran(&grid_weights(), &x, &y, 999, true, None).unwrap();
+    assert_relative_eq!(result.i, 0.05949290733196625, epsilon = 1e-10);
+    assert_eq!(result.sims.len(), 999);
+    assert!(result.p_sim.unwrap() <= 0.5);
+
+    // With the same variable twice it reduces to Moran's I
+    let same = bivariate_moran(&grid_weights(), &x, &x, 0, false, None).unwrap();
+    assert_relative_eq!(same.i, 0.07361243111060219, epsilon = 1e-10);
+    assert!(same.p_sim.is_none());
+
+    assert!(bivariate_moran(&grid_weights(), &x, &y[..5], 0, false, None).is_err());
+}
+
+#[test]
+fn differential_moran_should_use_the_change() {
+    let before: Vec<f64> = vec![1.5, 2.0, 3.1, -1.2, 0.4, 2.2, 4.8, 6.1, -0.7, 1.1];
+    let change: Vec<f64> = vec![
+        2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
+    ];
+    let after: Vec<f64> = before.iter().zip(&change).map(|(b, c)| b + c).collect();
+
+    let result = differential_moran(&grid_weights(), &before, &after, 0, false, None).unwrap();
+    assert_relative_eq!(result.i, 0.07361243111060219, epsilon = 1e-10);
+    assert_relative_eq!(result.z_norm, 0.7704068159278009, epsilon = 1e-10);
+
+    assert!(differential_moran(&grid_weights(), &before, &after[..5], 0, false, None).is_err());
+}
+
+#[test]
+fn empirical_bayes_moran_should_use_standardized_rates() {
+    let events: Vec<f64> = vec![12.0, 3.0, 40.0, 8.0, 2.0, 15.0, 30.0, 55.0, 4.0, 9.0];
+    let population: Vec<f64> = vec![
+        1000.0, 150.0, 2500.0, 900.0, 400.0, 800.0, 1200.0, 2600.0, 350.0, 1100.0,
+    ];
+
+    let result =
+        empirical_bayes_moran(&grid_weights(), &events, &population, 99, false, None).unwrap();
+    assert_relative_eq!(result.i, 0.05213572146004786, epsilon = 1e-10);
+    assert!(result.p_sim.is_some());
+
+    assert!(
+        empirical_bayes_moran(&grid_weights(), &events, &population[..5], 0, false, None).is_err()
+    );
+}
+
+#[test]
+fn moran_should_be_reproducible_with_a_seed() {
+    let values: Vec<f64> = vec![
+        2.24, 3.1, 4.55, -5.15, -4.39, 0.46, 5.54, 9.02, -2.09, -3.06,
+    ];
+    let run = |seed| moran(&grid_weights(), &values, 99, true, Some(seed)).unwrap();
+    assert_eq!(run(1).sims, run(1).sims);
+    assert_eq!(run(1).p_sim, run(1).p_sim);
+    assert_ne!(run(1).sims, run(2).sims);
+}
@@ -0,0 +1,63 @@
+use geo_stats::rates::empirical_bayes_standardize;
+
+#[macro_use]
+extern crate approx;
+
+fn population() -> Vec<f64> {
+    vec![
+        1000.0, 150.0, 2500.0, 900.0, 400.0, 800.0, 1200.0, 2600.0, 350.0, 1100.0,
+    ]
+}
+
+#[test]
+fn empirical_bayes_should_standardize_rates() {
+    let events = vec![12.0, 3.0, 40.0, 8.0, 2.0, 15.0, 30.0, 55.0, 4.0, 9.0];
+    let result = empirical_bayes_standardize(&events, &population()).unwrap();
+
+    let expected = vec![
+        -0.7162271643073032,
+        0.3404384037087812,
+        -0.03682232136993371,
+        -1.217381457672962,
+        -1.4636732520066618,
+        0.41587281959339967,
+        1.573843331867786,
+        1.0121294720674896,
+        -0.5934974149018771,
+        -1.400729465994994,
+    ];
+    for (value, expected) in result.iter().zip(expected.iter()) {
+        assert_relative_eq!(value, expected, epsilon = 1e-10);
+    }
+}
+
+#[test]
+fn empirical_bayes_should_fall_back_when_variance_estimate_is_negative() {
+    // Rates this close together give a negative estimate of the variance between observations
+    let events = vec![11.0, 2.0, 24.0, 9.0, 4.0, 8.0, 13.0, 26.0, 3.0, 11.0];
+    let result = empirical_bayes_standardize(&events, &population()).unwrap();
+
+    let expected = vec![
+        0.7887054840251918,
+        0.42389016745502156,
+        -0.24434639286300097,
+        -0.05808225348114967,
+        -0.022404089746974134,
+        -0.04632375043689073,
+        0.25602219737234805,
+        -0.04614544538090558,
+        -0.33918301543145385,
+        -0.14176606433431757,
+    ];
+    for (value, expected) in result.iter().zip(expected.iter()) {
+        assert_relative_eq!(value, expected, epsilon = 1e-10);
+    }
+}
+
+#[test]
+fn empirical_bayes_should_reject_invalid_input() {
+    assert!(empirical_bayes_standardize(&[1.0, 2.0], &[10.0]).is_err());
+    assert!(empirical_bayes_standardize(&[1.0, 2.0], &[10.0, 0.0]).is_err());
+    assert!(empirical_bayes_standardize(&[1.0, -2.0], &[10.0, 10.0]).is_err());
+    assert!(empirical_bayes_standardize(&[0.0, 0.0], &[10.0, 10.0]).is_err());
+}
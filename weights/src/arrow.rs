@@ -0,0 +1,645 @@
+use crate::utils::representative_point;
+use crate::weights::Weights;
+use crate::GeometryCoords;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, UInt64Type};
+use arrow_array::{Array, ArrayRef, Float64Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use geo_types::{
+    Coord, Geometry, GeometryCollection, LineString, MultiLineString, MultiPoint, MultiPolygon,
+    Point, Polygon,
+};
+use parquet::arrow::ArrowWriter;
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::io::Write;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// The field metadata key holding the GeoArrow extension name
+const EXTENSION_NAME_KEY: &str = "ARROW:extension:name";
+
+/// The GeoArrow geometry encodings which can be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeoArrowEncoding {
+    Wkb,
+    Point,
+    LineString,
+    Polygon,
+    MultiPoint,
+    MultiLineString,
+    MultiPolygon,
+}
+
+impl GeoArrowEncoding {
+    /// Works out the encoding of a field from its GeoArrow extension name. Binary fields without
+    /// an extension name are assumed to be WKB, as written by GeoParquet.
+    pub fn from_field(field: &Field) -> Result<Self, String> {
+        match field.metadata().get(EXTENSION_NAME_KEY).map(|s| s.as_str()) {
+            Some("geoarrow.wkb") | Some("ogc.wkb") => Ok(Self::Wkb),
+            Some("geoarrow.point") => Ok(Self::Point),
+            Some("geoarrow.linestring") => Ok(Self::LineString),
+            Some("geoarrow.polygon") => Ok(Self::Polygon),
+            Some("geoarrow.multipoint") => Ok(Self::MultiPoint),
+            Some("geoarrow.multilinestring") => Ok(Self::MultiLineString),
+            Some("geoarrow.multipolygon") => Ok(Self::MultiPolygon),
+            Some(other) => Err(format!("Unsupported geometry extension {}", other)),
+            None => match field.data_type() {
+                DataType::Binary | DataType::LargeBinary => Ok(Self::Wkb),
+                other => Err(format!(
+                    "Field {} has no GeoArrow extension name and type {}",
+                    field.name(),
+                    other
+                )),
+            },
+        }
+    }
+}
+
+/// A GeoArrow geometry column which the weight builders can read through `GeometryCoords`,
+/// straight from the Arrow buffers without decoding the column in to geo_types geometries.
+/// Both the WKB and the native encodings, with separated or interleaved coordinates, are
+/// supported. Z and M values are skipped. WKB geometries are checked when the column is
+/// opened and then parsed again each time they are read.
+#[derive(Debug)]
+pub struct GeoArrowGeometries<'a> {
+    encoding: GeoArrowEncoding,
+    len: usize,
+    source: GeometrySource<'a>,
+}
+
+#[derive(Debug)]
+enum GeometrySource<'a> {
+    Wkb {
+        offsets: Offsets<'a>,
+        bytes: &'a [u8],
+    },
+    Native {
+        /// The offsets of each level of lists from the outside in
+        levels: Vec<Offsets<'a>>,
+        coords: CoordBuffer<'a>,
+    },
+}
+
+/// The offsets of a list or binary array, borrowed from its Arrow buffer
+#[derive(Debug, Clone, Copy)]
+enum Offsets<'a> {
+    Small(&'a [i32]),
+    Large(&'a [i64]),
+}
+
+impl<'a> Offsets<'a> {
+    fn get(&self, index: usize) -> usize {
+        match self {
+            Offsets::Small(offsets) => offsets[index] as usize,
+            Offsets::Large(offsets) => offsets[index] as usize,
+        }
+    }
+
+    /// The range of child items covered by a range of items at this level
+    fn span(&self, range: Range<usize>) -> Range<usize> {
+        self.get(range.start)..self.get(range.end)
+    }
+
+    fn range(&self, index: usize) -> Range<usize> {
+        self.span(index..index + 1)
+    }
+}
+
+/// Coordinates borrowed from the Arrow buffers, either a struct of x and y or a fixed size list
+/// of interleaved values
+#[derive(Debug, Clone, Copy)]
+enum CoordBuffer<'a> {
+    Separated { xs: &'a [f64], ys: &'a [f64] },
+    Interleaved { values: &'a [f64], size: usize },
+}
+
+impl<'a> CoordBuffer<'a> {
+    fn from_array(array: &'a dyn Array) -> Result<Self, String> {
+        match array.data_type() {
+            DataType::Struct(_) => {
+                let array = array.as_struct();
+                let column = |name: &str| {
+                    array
+                        .column_by_name(name)
+                        .ok_or_else(|| format!("Coordinates are missing the {} field", name))
+                        .and_then(|c| float_values(c.as_ref()))
+                };
+                Ok(CoordBuffer::Separated {
+                    xs: column("x")?,
+                    ys: column("y")?,
+                })
+            }
+            DataType::FixedSizeList(_, size) if *size >= 2 => {
+                let array = array.as_fixed_size_list();
+                Ok(CoordBuffer::Interleaved {
+                    values: float_values(array.values().as_ref())?,
+                    size: *size as usize,
+                })
+            }
+            other => Err(format!("Unsupported coordinate type {}", other)),
+        }
+    }
+
+    fn get(&self, index: usize) -> Coord {
+        match self {
+            CoordBuffer::Separated { xs, ys } => Coord {
+                x: xs[index],
+                y: ys[index],
+            },
+            CoordBuffer::Interleaved { values, size } => Coord {
+                x: values[index * size],
+                y: values[index * size + 1],
+            },
+        }
+    }
+}
+
+/// Unwraps `depth` levels of lists, returning the offsets of each level from the outside in
+/// along with the innermost array
+fn list_levels(array: &dyn Array, depth: usize) -> Result<(Vec<Offsets<'_>>, &dyn Array), String> {
+    let mut levels = vec![];
+    let mut current = array;
+    for _ in 0..depth {
+        current = match current.data_type() {
+            DataType::List(_) => {
+                let list = current.as_list::<i32>();
+                levels.push(Offsets::Small(list.value_offsets()));
+                list.values().as_ref()
+            }
+            DataType::LargeList(_) => {
+                let list = current.as_list::<i64>();
+                levels.push(Offsets::Large(list.value_offsets()));
+                list.values().as_ref()
+            }
+            other => return Err(format!("Expected a list but got {}", other)),
+        };
+    }
+    Ok((levels, current))
+}
+
+impl<'a> GeoArrowGeometries<'a> {
+    /// Opens a GeoArrow geometry column, checking its encoding and, for WKB, that every
+    /// geometry can be read
+    ///
+    /// # Arguments
+    ///
+    /// * `array` - the geometry column
+    /// * `field` - the field describing the column, used to find its GeoArrow extension name
+    ///
+    pub fn try_new(array: &'a dyn Array, field: &Field) -> Result<Self, String> {
+        if let Some(index) = (0..array.len()).find(|i| array.is_null(*i)) {
+            return Err(format!("Geometry {} is null", index));
+        }
+
+        let encoding = GeoArrowEncoding::from_field(field)?;
+        let depth = match encoding {
+            GeoArrowEncoding::Wkb => {
+                let (offsets, bytes) = match array.data_type() {
+                    DataType::Binary => {
+                        let array = array.as_binary::<i32>();
+                        (Offsets::Small(array.value_offsets()), array.value_data())
+                    }
+                    DataType::LargeBinary => {
+                        let array = array.as_binary::<i64>();
+                        (Offsets::Large(array.value_offsets()), array.value_data())
+                    }
+                    other => return Err(format!("Expected a binary WKB column but got {}", other)),
+                };
+                let geometries = Self {
+                    encoding,
+                    len: array.len(),
+                    source: GeometrySource::Wkb { offsets, bytes },
+                };
+                for index in 0..geometries.len {
+                    geometries
+                        .wkb_reader(index)
+                        .paths(&mut |_| {})
+                        .map_err(|e| format!("Failed to read geometry {}: {}", index, e))?;
+                }
+                return Ok(geometries);
+            }
+            GeoArrowEncoding::Point => 0,
+            GeoArrowEncoding::LineString | GeoArrowEncoding::MultiPoint => 1,
+            GeoArrowEncoding::Polygon | GeoArrowEncoding::MultiLineString => 2,
+            GeoArrowEncoding::MultiPolygon => 3,
+        };
+
+        let (levels, coords) = list_levels(array, depth)?;
+        Ok(Self {
+            encoding,
+            len: array.len(),
+            source: GeometrySource::Native {
+                levels,
+                coords: CoordBuffer::from_array(coords)?,
+            },
+        })
+    }
+
+    fn wkb_reader(&self, index: usize) -> WkbReader<'a> {
+        match &self.source {
+            GeometrySource::Wkb { offsets, bytes } => WkbReader {
+                bytes: &bytes[offsets.range(index)],
+                position: 0,
+            },
+            GeometrySource::Native { .. } => unreachable!("Only WKB columns have a reader"),
+        }
+    }
+
+    /// Decodes a single geometry
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the geometry to decode
+    ///
+    pub fn geometry(&self, index: usize) -> Geometry {
+        let (levels, coords) = match &self.source {
+            GeometrySource::Wkb { .. } => {
+                return self
+                    .wkb_reader(index)
+                    .geometry()
+                    .expect("WKB is checked when the column is opened")
+            }
+            GeometrySource::Native { levels, coords } => (levels, coords),
+        };
+        let path = |offsets: &Offsets, path: usize| -> Vec<Coord> {
+            offsets.range(path).map(|c| coords.get(c)).collect()
+        };
+        let rings = |outer: &Offsets, inner: &Offsets, item: usize| -> Vec<Vec<Coord>> {
+            outer.range(item).map(|ring| path(inner, ring)).collect()
+        };
+
+        match self.encoding {
+            GeoArrowEncoding::Point => Point(coords.get(index)).into(),
+            GeoArrowEncoding::LineString => LineString(path(&levels[0], index)).into(),
+            GeoArrowEncoding::Polygon => polygon(rings(&levels[0], &levels[1], index)).into(),
+            GeoArrowEncoding::MultiPoint => MultiPoint(
+                levels[0]
+                    .range(index)
+                    .map(|c| Point(coords.get(c)))
+                    .collect(),
+            )
+            .into(),
+            GeoArrowEncoding::MultiLineString => MultiLineString(
+                rings(&levels[0], &levels[1], index)
+                    .into_iter()
+                    .map(LineString)
+                    .collect(),
+            )
+            .into(),
+            GeoArrowEncoding::MultiPolygon => MultiPolygon(
+                levels[0]
+                    .range(index)
+                    .map(|p| polygon(rings(&levels[1], &levels[2], p)))
+                    .collect(),
+            )
+            .into(),
+            GeoArrowEncoding::Wkb => unreachable!("WKB is handled above"),
+        }
+    }
+}
+
+impl<'a> GeometryCoords for GeoArrowGeometries<'a> {
+    fn no_geometries(&self) -> usize {
+        self.len
+    }
+
+    fn for_each_path(&self, index: usize, f: &mut dyn FnMut(&mut dyn Iterator<Item = Coord>)) {
+        let (levels, coords) = match &self.source {
+            GeometrySource::Wkb { .. } => {
+                self.wkb_reader(index)
+                    .paths(f)
+                    .expect("WKB is checked when the column is opened");
+                return;
+            }
+            GeometrySource::Native { levels, coords } => (levels, coords),
+        };
+
+        match self.encoding {
+            GeoArrowEncoding::Point => f(&mut std::iter::once(coords.get(index))),
+            GeoArrowEncoding::MultiPoint => {
+                for c in levels[0].range(index) {
+                    f(&mut std::iter::once(coords.get(c)));
+                }
+            }
+            _ => {
+                // Narrow down to the innermost lists of this geometry, its rings or lines,
+                // each of which is a path
+                let (innermost, outer) = levels.split_last().expect("Lists have offsets");
+                let paths = outer
+                    .iter()
+                    .fold(index..index + 1, |range, offsets| offsets.span(range));
+                for path in paths {
+                    f(&mut innermost.range(path).map(|c| coords.get(c)));
+                }
+            }
+        }
+    }
+
+    fn representative_point(&self, index: usize) -> Option<Point> {
+        match (&self.source, self.encoding) {
+            (GeometrySource::Native { coords, .. }, GeoArrowEncoding::Point) => {
+                Some(Point(coords.get(index)))
+            }
+            _ => representative_point(&self.geometry(index)),
+        }
+    }
+}
+
+/// Decodes a GeoArrow geometry column in to geo_types geometries, so that it can be passed to
+/// any of the weight builders. See `GeoArrowGeometries` to build weights from the column
+/// without decoding it first.
+///
+/// # Arguments
+///
+/// * `array` - the geometry column
+/// * `field` - the field describing the column, used to find its GeoArrow extension name
+///
+pub fn geometries_from_arrow(array: &dyn Array, field: &Field) -> Result<Vec<Geometry>, String> {
+    let geometries = GeoArrowGeometries::try_new(array, field)?;
+    Ok((0..geometries.no_geometries())
+        .map(|index| geometries.geometry(index))
+        .collect())
+}
+
+fn polygon(rings: Vec<Vec<Coord>>) -> Polygon {
+    let mut rings = rings.into_iter().map(LineString);
+    let exterior = rings.next().unwrap_or_else(|| LineString(vec![]));
+    Polygon::new(exterior, rings.collect())
+}
+
+fn float_values(array: &dyn Array) -> Result<&[f64], String> {
+    match array.data_type() {
+        DataType::Float64 => Ok(array.as_primitive::<Float64Type>().values()),
+        other => Err(format!("Expected Float64 coordinates but got {}", other)),
+    }
+}
+
+/// Converts the parts of a WKB multi geometry, failing if any part is of the wrong type
+fn multi_parts<T>(parts: Vec<Geometry>, name: &str) -> Result<Vec<T>, String>
+where
+    T: TryFrom<Geometry>,
+{
+    parts
+        .into_iter()
+        .map(|part| {
+            T::try_from(part).map_err(|_| format!("{} contains a part of a different type", name))
+        })
+        .collect()
+}
+
+/// Minimal reader for ISO and extended WKB, dropping any Z and M values
+struct WkbReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+/// The byte order, base geometry type and number of values per coordinate of a WKB geometry
+struct WkbHeader {
+    little_endian: bool,
+    code: u32,
+    dims: usize,
+}
+
+impl<'a> WkbReader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + N)
+            .ok_or("Unexpected end of WKB")?;
+        self.position += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Result<u32, String> {
+        let bytes = self.take::<4>()?;
+        Ok(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn f64(&mut self, little_endian: bool) -> Result<f64, String> {
+        let bytes = self.take::<8>()?;
+        Ok(if little_endian {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    fn header(&mut self) -> Result<WkbHeader, String> {
+        let little_endian = match self.take::<1>()?[0] {
+            0 => false,
+            1 => true,
+            other => return Err(format!("Invalid byte order {}", other)),
+        };
+        let code = self.u32(little_endian)?;
+
+        // Extended WKB flags the dimensions and SRID in the high bits, ISO WKB adds 1000 for Z,
+        // 2000 for M and 3000 for ZM
+        let mut dims = 2;
+        if code & 0x8000_0000 != 0 {
+            dims += 1;
+        }
+        if code & 0x4000_0000 != 0 {
+            dims += 1;
+        }
+        if code & 0x2000_0000 != 0 {
+            self.u32(little_endian)?;
+        }
+        let code = code & 0x0fff_ffff;
+        dims += match code / 1000 {
+            1 | 2 => 1,
+            3 => 2,
+            _ => 0,
+        };
+        Ok(WkbHeader {
+            little_endian,
+            code: code % 1000,
+            dims,
+        })
+    }
+
+    fn coord(&mut self, header: &WkbHeader) -> Result<Coord, String> {
+        let x = self.f64(header.little_endian)?;
+        let y = self.f64(header.little_endian)?;
+        for _ in 2..header.dims {
+            self.f64(header.little_endian)?;
+        }
+        Ok(Coord { x, y })
+    }
+
+    fn coords(&mut self, header: &WkbHeader) -> Result<Vec<Coord>, String> {
+        let count = self.u32(header.little_endian)?;
+        (0..count).map(|_| self.coord(header)).collect()
+    }
+
+    /// Reads a counted list of coordinates, passing them to `f` as they are read
+    fn path(
+        &mut self,
+        header: &WkbHeader,
+        f: &mut dyn FnMut(&mut dyn Iterator<Item = Coord>),
+    ) -> Result<(), String> {
+        let count = self.u32(header.little_endian)?;
+        let mut error = None;
+        let mut coords = (0..count).map_while(|_| match self.coord(header) {
+            Ok(coord) => Some(coord),
+            Err(e) => {
+                error = Some(e);
+                None
+            }
+        });
+        f(&mut coords);
+        // Read anything left over so the position ends up past the path
+        coords.for_each(drop);
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Passes each path of the geometry to `f`, see `GeometryCoords`. Fails on malformed WKB
+    /// and on multi geometries with parts of the wrong type. Returns the geometry type code.
+    fn paths(&mut self, f: &mut dyn FnMut(&mut dyn Iterator<Item = Coord>)) -> Result<u32, String> {
+        let header = self.header()?;
+        match header.code {
+            1 => {
+                let coord = self.coord(&header)?;
+                f(&mut std::iter::once(coord));
+            }
+            2 => self.path(&header, f)?,
+            3 => {
+                for _ in 0..self.u32(header.little_endian)? {
+                    self.path(&header, f)?;
+                }
+            }
+            4..=7 => {
+                for _ in 0..self.u32(header.little_endian)? {
+                    let part = self.paths(f)?;
+                    if header.code != 7 && part != header.code - 3 {
+                        return Err(format!(
+                            "Multi geometry of type {} contains a part of type {}",
+                            header.code, part
+                        ));
+                    }
+                }
+            }
+            other => return Err(format!("Unsupported WKB geometry type {}", other)),
+        }
+        Ok(header.code)
+    }
+
+    fn geometry(&mut self) -> Result<Geometry, String> {
+        let header = self.header()?;
+        Ok(match header.code {
+            1 => Point(self.coord(&header)?).into(),
+            2 => LineString(self.coords(&header)?).into(),
+            3 => {
+                let rings = (0..self.u32(header.little_endian)?)
+                    .map(|_| self.coords(&header))
+                    .collect::<Result<Vec<_>, _>>()?;
+                polygon(rings).into()
+            }
+            4..=7 => {
+                let parts = (0..self.u32(header.little_endian)?)
+                    .map(|_| self.geometry())
+                    .collect::<Result<Vec<Geometry>, _>>()?;
+                match header.code {
+                    4 => MultiPoint(multi_parts(parts, "MultiPoint")?).into(),
+                    5 => MultiLineString(multi_parts(parts, "MultiLineString")?).into(),
+                    6 => MultiPolygon(multi_parts(parts, "MultiPolygon")?).into(),
+                    _ => Geometry::GeometryCollection(GeometryCollection(parts)),
+                }
+            }
+            other => return Err(format!("Unsupported WKB geometry type {}", other)),
+        })
+    }
+}
+
+impl Weights {
+    /// Returns the weights as an Arrow record batch with one row per link, sorted by origin then
+    /// dest, and the columns `origin` (UInt64), `dest` (UInt64) and `weight` (Float64)
+    pub fn to_record_batch(&self) -> Result<RecordBatch, String> {
+        let links = self.sorted_links();
+        let schema = Schema::new(vec![
+            Field::new("origin", DataType::UInt64, false),
+            Field::new("dest", DataType::UInt64, false),
+            Field::new("weight", DataType::Float64, false),
+        ]);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(
+                links.iter().map(|l| l.0 as u64),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                links.iter().map(|l| l.1 as u64),
+            )),
+            Arc::new(Float64Array::from_iter_values(links.iter().map(|l| l.2))),
+        ];
+        RecordBatch::try_new(Arc::new(schema), columns).map_err(|e| e.to_string())
+    }
+
+    /// Reads weights from a record batch with a row per link, as written by `to_record_batch`.
+    /// The `weight` column is optional and defaults to 1. Links are used as given, they are not
+    /// made symmetric.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch` - the record batch to read
+    /// * `no_elements` - the number of elements in the original geometry set
+    ///
+    pub fn from_record_batch(batch: &RecordBatch, no_elements: usize) -> Result<Weights, String> {
+        let ids = |name: &str| -> Result<&[u64], String> {
+            let column = batch
+                .column_by_name(name)
+                .ok_or_else(|| format!("Record batch has no {} column", name))?;
+            if column.null_count() > 0 || column.data_type() != &DataType::UInt64 {
+                return Err(format!("Column {} should be non null UInt64", name));
+            }
+            Ok(column.as_primitive::<UInt64Type>().values())
+        };
+        let (origins, dests) = (ids("origin")?, ids("dest")?);
+        let weights: Vec<f64> = match batch.column_by_name("weight") {
+            Some(column) => float_values(column.as_ref())?.to_vec(),
+            None => vec![1.0; origins.len()],
+        };
+
+        if let Some(id) = origins
+            .iter()
+            .chain(dests.iter())
+            .find(|id| **id as usize >= no_elements)
+        {
+            return Err(format!(
+                "Link references element {} but there are only {} elements",
+                id, no_elements
+            ));
+        }
+
+        let mut lookup: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+        for ((origin, dest), weight) in origins.iter().zip(dests).zip(weights) {
+            lookup
+                .entry(*origin as usize)
+                .or_default()
+                .insert(*dest as usize, weight);
+        }
+        Ok(Weights::new(lookup, no_elements))
+    }
+
+    /// Writes the weights to a Parquet file using the layout of `to_record_batch`
+    pub fn write_parquet<W: Write + Send>(&self, writer: W) -> Result<(), String> {
+        write_parquet(&self.to_record_batch()?, writer)
+    }
+}
+
+/// Writes a single record batch to a Parquet file
+///
+/// # Arguments
+///
+/// * `batch` - the record batch to write
+/// * `writer` - where to write the file
+///
+pub fn write_parquet<W: Write + Send>(batch: &RecordBatch, writer: W) -> Result<(), String> {
+    let mut writer =
+        ArrowWriter::try_new(writer, batch.schema(), None).map_err(|e| e.to_string())?;
+    writer.write(batch).map_err(|e| e.to_string())?;
+    writer.close().map_err(|e| e.to_string())?;
+    Ok(())
+}
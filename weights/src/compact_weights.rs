@@ -0,0 +1,302 @@
+use crate::weights::{ToSparseMatrix, TransformType, Weights};
+use nalgebra_sparse::csr::CsrMatrix;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// A memory efficient, read only representation of a weights matrix for very large datasets.
+/// Links are stored in compressed sparse row form with u32 indices and f32 weights, taking 8
+/// bytes per link compared to the several tens of bytes used by the nested hash maps in
+/// `Weights`. Weights are only widened to f64 when converted to a sparse matrix.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "CompactWeightsFields")]
+pub struct CompactWeights {
+    /// Position in `dests` and `values` where each origins links start, with a final entry
+    /// holding the total number of links
+    offsets: Vec<u32>,
+    dests: Vec<u32>,
+    values: Vec<f32>,
+}
+
+/// The unchecked fields of CompactWeights, so deserializing goes through
+/// `CompactWeights::from_csr`
+#[derive(Deserialize)]
+struct CompactWeightsFields {
+    offsets: Vec<u32>,
+    dests: Vec<u32>,
+    values: Vec<f32>,
+}
+
+impl TryFrom<CompactWeightsFields> for CompactWeights {
+    type Error = String;
+
+    fn try_from(fields: CompactWeightsFields) -> Result<Self, Self::Error> {
+        CompactWeights::from_csr(fields.offsets, fields.dests, fields.values)
+    }
+}
+
+impl CompactWeights {
+    /// Create compact weights from their compressed sparse row parts, checking they describe a
+    /// valid matrix so that the other methods can not panic.
+    ///
+    /// # Arguments
+    ///
+    /// * `offsets` - Position in `dests` where each origins links start, starting at 0 with a
+    ///   final entry holding the total number of links
+    /// * `dests` - The destination of each link, sorted and unique within each origin
+    /// * `values` - The weight of each link
+    ///
+    pub fn from_csr(offsets: Vec<u32>, dests: Vec<u32>, values: Vec<f32>) -> Result<Self, String> {
+        if offsets.first() != Some(&0) {
+            return Err("Offsets should start at 0".into());
+        }
+        if offsets.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err("Offsets should never decrease".into());
+        }
+        let no_links = *offsets.last().unwrap() as usize;
+        if no_links != dests.len() || no_links != values.len() {
+            return Err(format!(
+                "Offsets end at {} links but got {} dests and {} values",
+                no_links,
+                dests.len(),
+                values.len()
+            ));
+        }
+        let no_elements = offsets.len() - 1;
+        if let Some(dest) = dests.iter().find(|dest| **dest as usize >= no_elements) {
+            return Err(format!(
+                "Link references element {} but there are only {} elements",
+                dest, no_elements
+            ));
+        }
+        let unsorted = offsets.windows(2).any(|pair| {
+            dests[pair[0] as usize..pair[1] as usize]
+                .windows(2)
+                .any(|dests| dests[0] >= dests[1])
+        });
+        if unsorted {
+            return Err("Dests should be sorted and unique within each origin".into());
+        }
+        Ok(Self {
+            offsets,
+            dests,
+            values,
+        })
+    }
+
+    /// Create compact weights from a list of directed links. Unlike `Weights::from_list_rep`
+    /// the links are not made symmetric, and repeated links keep the last weight given.
+    ///
+    /// # Arguments
+    ///
+    /// * `origins` - A list of the origin ids
+    /// * `dests` - A list of the destination ids
+    /// * `weights` - A list of the weights
+    /// * `no_elements` - The number of elements in the original geometry set
+    ///
+    pub fn from_links(
+        origins: &[u32],
+        dests: &[u32],
+        weights: &[f32],
+        no_elements: usize,
+    ) -> Result<Self, String> {
+        if origins.len() != dests.len() || origins.len() != weights.len() {
+            return Err(format!(
+                "Expected the same number of origins, dests and weights but got {}, {} and {}",
+                origins.len(),
+                dests.len(),
+                weights.len()
+            ));
+        }
+        check_fits_u32("elements", no_elements)?;
+        check_fits_u32("links", origins.len())?;
+        if let Some(index) = origins
+            .iter()
+            .chain(dests.iter())
+            .find(|index| **index as usize >= no_elements)
+        {
+            return Err(format!(
+                "Link references element {} but there are only {} elements",
+                index, no_elements
+            ));
+        }
+
+        // Sort the links by origin then dest, keeping the order of repeated links so the last
+        // one can win
+        let mut order: Vec<usize> = (0..origins.len()).collect();
+        order.sort_by_key(|i| (origins[*i], dests[*i]));
+        order.dedup_by(|later, earlier| {
+            let repeated = (origins[*later], dests[*later]) == (origins[*earlier], dests[*earlier]);
+            if repeated {
+                *earlier = *later;
+            }
+            repeated
+        });
+
+        let mut offsets: Vec<u32> = vec![0; no_elements + 1];
+        for i in order.iter() {
+            offsets[origins[*i] as usize + 1] += 1;
+        }
+        for index in 0..no_elements {
+            offsets[index + 1] += offsets[index];
+        }
+
+        Ok(Self {
+            offsets,
+            dests: order.iter().map(|i| dests[*i]).collect(),
+            values: order.iter().map(|i| weights[*i]).collect(),
+        })
+    }
+
+    /// Create compact weights from links already sorted by origin then dest, with no repeats.
+    /// This is how the weight builders write their links without going through `Weights`.
+    pub(crate) fn from_sorted_links<I>(links: I, no_elements: usize) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (u32, u32, f32)>,
+    {
+        check_fits_u32("elements", no_elements)?;
+        let mut offsets: Vec<u32> = vec![0; no_elements + 1];
+        let mut dests: Vec<u32> = vec![];
+        let mut values: Vec<f32> = vec![];
+        for (origin, dest, value) in links {
+            if origin as usize >= no_elements || dest as usize >= no_elements {
+                return Err(format!(
+                    "Link references element {} but there are only {} elements",
+                    origin.max(dest),
+                    no_elements
+                ));
+            }
+            check_fits_u32("links", dests.len() + 1)?;
+            offsets[origin as usize + 1] += 1;
+            dests.push(dest);
+            values.push(value);
+        }
+        for index in 0..no_elements {
+            offsets[index + 1] += offsets[index];
+        }
+        Ok(Self {
+            offsets,
+            dests,
+            values,
+        })
+    }
+
+    /// Create compact weights from the links of each origin in turn, given in any order within
+    /// the row
+    pub(crate) fn from_rows(rows: Vec<Vec<(usize, f64)>>) -> Result<Self, String> {
+        let no_elements = rows.len();
+        check_fits_u32("elements", no_elements)?;
+        let links = rows.into_iter().enumerate().flat_map(|(origin, mut row)| {
+            row.sort_by_key(|(dest, _)| *dest);
+            row.into_iter()
+                .map(move |(dest, value)| (origin as u32, dest as u32, value as f32))
+        });
+        Self::from_sorted_links(links, no_elements)
+    }
+
+    /// Create compact weights from a `Weights` object, narrowing the weights to f32. Fails if
+    /// there are more elements or links than fit in a u32.
+    pub fn from_weights(weights: &Weights) -> Result<Self, String> {
+        check_fits_u32("elements", weights.no_elements())?;
+        let (origins, dests, values) = weights.to_list();
+        let origins: Vec<u32> = origins.into_iter().map(|o| o as u32).collect();
+        let dests: Vec<u32> = dests.into_iter().map(|d| d as u32).collect();
+        let values: Vec<f32> = values.into_iter().map(|v| v as f32).collect();
+        Self::from_links(&origins, &dests, &values, weights.no_elements())
+    }
+
+    /// Converts back to a `Weights` object
+    pub fn to_weights(&self) -> Weights {
+        let weights: HashMap<usize, HashMap<usize, f64>> = (0..self.no_elements())
+            .map(|origin| {
+                let (dests, values) = self.row(origin);
+                let row: HashMap<usize, f64> = dests
+                    .iter()
+                    .zip(values)
+                    .map(|(dest, value)| (*dest as usize, *value as f64))
+                    .collect();
+                (origin, row)
+            })
+            .collect();
+        Weights::new(weights, self.no_elements())
+    }
+
+    /// The number of elements in the original geometry set
+    pub fn no_elements(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// The total number of links
+    pub fn no_links(&self) -> usize {
+        self.dests.len()
+    }
+
+    /// Returns the destination ids and weights of the links from the given origin, sorted by
+    /// destination. Both are empty if the origin is out of range or has no neighbors.
+    pub fn row(&self, origin: usize) -> (&[u32], &[f32]) {
+        if origin >= self.no_elements() {
+            return (&[], &[]);
+        }
+        let start = self.offsets[origin] as usize;
+        let end = self.offsets[origin + 1] as usize;
+        (&self.dests[start..end], &self.values[start..end])
+    }
+
+    /// Returns the number of neighbors of the given origin
+    pub fn no_neighbors(&self, origin: usize) -> usize {
+        self.row(origin).0.len()
+    }
+
+    /// Returns the weights as a sparse f64 matrix, applying the transform if given. Matches
+    /// `Weights::as_sparse_matrix`.
+    pub fn as_sparse_matrix(&self, transform: Option<TransformType>) -> CsrMatrix<f64> {
+        let total: f64 = match transform {
+            Some(TransformType::DoublyStandardized) => self.values.iter().map(|v| *v as f64).sum(),
+            _ => 1.0,
+        };
+
+        let mut values: Vec<f64> = Vec::with_capacity(self.no_links());
+        for origin in 0..self.no_elements() {
+            let row = self.row(origin).1;
+            let norm: f64 = match transform {
+                Some(TransformType::Row) => row.iter().map(|v| *v as f64).sum(),
+                Some(TransformType::DoublyStandardized) => total,
+                _ => 1.0,
+            };
+            values.extend(row.iter().map(|v| match transform {
+                Some(TransformType::Binary) => 1.0,
+                _ => *v as f64 / norm,
+            }));
+        }
+
+        CsrMatrix::try_from_csr_data(
+            self.no_elements(),
+            self.no_elements(),
+            self.offsets.iter().map(|o| *o as usize).collect(),
+            self.dests.iter().map(|d| *d as usize).collect(),
+            values,
+        )
+        .expect("Compact weights should always form a valid CSR matrix")
+    }
+}
+
+impl ToSparseMatrix for CompactWeights {
+    fn no_elements(&self) -> usize {
+        CompactWeights::no_elements(self)
+    }
+
+    fn as_sparse_matrix(&self, transform: Option<TransformType>) -> CsrMatrix<f64> {
+        CompactWeights::as_sparse_matrix(self, transform)
+    }
+}
+
+pub(crate) fn check_fits_u32(name: &str, count: usize) -> Result<(), String> {
+    if count > u32::MAX as usize {
+        Err(format!(
+            "{} {} is more than compact weights can hold",
+            count, name
+        ))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use crate::utils::geometry_distance;
+use crate::weights::TransformType;
+use geo::{BoundingRect, GeoFloat, Intersects};
+use geo_types::Geometry;
+use nalgebra_sparse::{coo::CooMatrix, csr::CsrMatrix};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// The rule used to decide which targets are related to each source
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossWeightRule<A> {
+    /// Targets within `cutoff_dist` of the source, weighted by 1 or by the distance
+    Distance {
+        cutoff_dist: A,
+        use_distance_as_weight: bool,
+    },
+    /// The `k` closest targets to each source, each with a weight of 1
+    #[serde(rename = "knn")]
+    KNN { k: usize },
+    /// Targets whose geometry intersects the source geometry, each with a weight of 1
+    Intersects,
+}
+
+/// Builds weights relating a set of source geometries to a different set of target geometries,
+/// for example tracts to the schools near them. Distances are measured between the geometries
+/// themselves, so a school inside a tract is at a distance of 0 from it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CrossWeightBuilder<A>
+where
+    A: GeoFloat,
+{
+    rule: CrossWeightRule<A>,
+}
+
+/// Structure holding a non square weights matrix between a set of sources and a set of targets.
+/// Unlike Weights this is not symmetric, source and target ids index into different collections.
+#[derive(Debug)]
+pub struct CrossWeights {
+    weights: HashMap<usize, HashMap<usize, f64>>,
+    no_sources: usize,
+    no_targets: usize,
+}
+
+impl<A> CrossWeightBuilder<A>
+where
+    A: GeoFloat,
+{
+    pub fn new(rule: CrossWeightRule<A>) -> Self {
+        Self { rule }
+    }
+
+    /// Compute the weights between each source and target
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - The geometries making up the rows of the weights matrix
+    /// * `targets` - The geometries making up the columns of the weights matrix
+    ///
+    pub fn compute_cross_weights<S, T>(&self, sources: &S, targets: &T) -> CrossWeights
+    where
+        for<'a> &'a S: IntoIterator<Item = &'a Geometry<A>>,
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let no_sources = sources.into_iter().count();
+        let no_targets = targets.into_iter().count();
+        let mut weights: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+
+        match self.rule {
+            CrossWeightRule::Intersects => {
+                let target_bounds: Vec<_> = targets
+                    .into_iter()
+                    .map(|target| target.bounding_rect())
+                    .collect();
+
+                for (i, source) in sources.into_iter().enumerate() {
+                    let source_bounds = match source.bounding_rect() {
+                        Some(b) => b,
+                        None => continue,
+                    };
+                    for (j, target) in targets.into_iter().enumerate() {
+                        let bounds_overlap = target_bounds[j]
+                            .map(|b| b.intersects(&source_bounds))
+                            .unwrap_or(false);
+                        if bounds_overlap && source.intersects(target) {
+                            weights.entry(i).or_default().insert(j, 1.0);
+                        }
+                    }
+                }
+            }
+            CrossWeightRule::Distance {
+                cutoff_dist,
+                use_distance_as_weight,
+            } => {
+                for (i, source) in sources.into_iter().enumerate() {
+                    for (j, target) in targets.into_iter().enumerate() {
+                        if let Some(dist) = geometry_distance(source, target) {
+                            if dist < cutoff_dist {
+                                let weight = if use_distance_as_weight {
+                                    dist.to_f64().unwrap()
+                                } else {
+                                    1.0
+                                };
+                                weights.entry(i).or_default().insert(j, weight);
+                            }
+                        }
+                    }
+                }
+            }
+            CrossWeightRule::KNN { k } => {
+                for (i, source) in sources.into_iter().enumerate() {
+                    let mut dists: Vec<(usize, f64)> = targets
+                        .into_iter()
+                        .enumerate()
+                        .filter_map(|(j, target)| {
+                            geometry_distance(source, target).map(|d| (j, d.to_f64().unwrap()))
+                        })
+                        .collect();
+                    if dists.is_empty() {
+                        continue;
+                    }
+                    dists.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                    let entry = weights.entry(i).or_default();
+                    for (j, _dist) in dists.into_iter().take(k) {
+                        entry.insert(j, 1.0);
+                    }
+                }
+            }
+        }
+
+        CrossWeights::new(weights, no_sources, no_targets)
+    }
+}
+
+impl CrossWeights {
+    /// Create a new cross weights object from a hashmap of {source => target => weight}
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - A mapping of {source => target => weight}
+    /// * `no_sources` - The number of geometries in the source set
+    /// * `no_targets` - The number of geometries in the target set
+    ///
+    pub fn new(
+        weights: HashMap<usize, HashMap<usize, f64>>,
+        no_sources: usize,
+        no_targets: usize,
+    ) -> Self {
+        Self {
+            weights,
+            no_sources,
+            no_targets,
+        }
+    }
+
+    /// Return a reference to the hash map representation of the weights
+    pub fn weights(&self) -> &HashMap<usize, HashMap<usize, f64>> {
+        &self.weights
+    }
+
+    /// Return the number of geometries in the source set
+    pub fn no_sources(&self) -> usize {
+        self.no_sources
+    }
+
+    /// Return the number of geometries in the target set
+    pub fn no_targets(&self) -> usize {
+        self.no_targets
+    }
+
+    /// Returns the ids of the targets related to a given source
+    pub fn get_target_ids(&self, source: usize) -> Option<HashSet<usize>> {
+        self.weights
+            .get(&source)
+            .map(|targets| targets.keys().cloned().collect())
+    }
+
+    /// Returns the weights as a rectangular no_sources x no_targets sparse matrix
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` - what transform, if any to apply to the weights matrix, as in
+    ///   `Weights::as_sparse_matrix`
+    ///
+    pub fn as_sparse_matrix(&self, transform: Option<TransformType>) -> CsrMatrix<f64> {
+        let mut coo_matrix = CooMatrix::new(self.no_sources, self.no_targets);
+
+        let total: f64 = match &transform {
+            Some(TransformType::DoublyStandardized) => {
+                self.weights.values().flat_map(|vals| vals.values()).sum()
+            }
+            _ => 1.0,
+        };
+
+        for (source, targets) in self.weights.iter() {
+            let norm: f64 = match &transform {
+                Some(TransformType::Row) => targets.values().sum(),
+                Some(TransformType::DoublyStandardized) => total,
+                _ => 1.0,
+            };
+            for (target, weight) in targets.iter() {
+                let weight = match &transform {
+                    Some(TransformType::Binary) => 1.0,
+                    _ => *weight / norm,
+                };
+                coo_matrix.push(*source, *target, weight);
+            }
+        }
+
+        CsrMatrix::from(&coo_matrix)
+    }
+
+    /// Returns the weights in a list format
+    ///
+    /// Output format is a tuple of source ids, target ids, weight values
+    ///
+    pub fn to_list(&self) -> (Vec<usize>, Vec<usize>, Vec<f64>) {
+        let mut source_list: Vec<usize> = vec![];
+        let mut target_list: Vec<usize> = vec![];
+        let mut weight_list: Vec<f64> = vec![];
+
+        for (source, targets) in self.weights.iter() {
+            for (target, weight) in targets.iter() {
+                source_list.push(*source);
+                target_list.push(*target);
+                weight_list.push(*weight);
+            }
+        }
+        (source_list, target_list, weight_list)
+    }
+}
@@ -0,0 +1,125 @@
+use crate::weights::Weights;
+use geo::{GeoFloat, Line};
+use geo_types::Geometry;
+use polars::prelude::{Column, DataFrame, DataType, PolarsError};
+use std::collections::HashMap;
+
+fn polars_error(e: PolarsError) -> String {
+    format!("Polars error: {}", e)
+}
+
+/// Encodes a line as a little endian WKB LineString, the layout GeoPolars and GeoArrow use for
+/// binary geometry columns
+fn line_to_wkb<A: GeoFloat>(line: &Line<A>) -> Vec<u8> {
+    let mut wkb: Vec<u8> = Vec::with_capacity(41);
+    wkb.push(1);
+    wkb.extend_from_slice(&2u32.to_le_bytes());
+    wkb.extend_from_slice(&2u32.to_le_bytes());
+    for coord in [line.start, line.end] {
+        wkb.extend_from_slice(&coord.x.to_f64().unwrap().to_le_bytes());
+        wkb.extend_from_slice(&coord.y.to_f64().unwrap().to_le_bytes());
+    }
+    wkb
+}
+
+impl Weights {
+    /// Returns the weights as a Polars DataFrame with one row per link, sorted by origin then
+    /// dest, and the columns `origin` (u64), `dest` (u64) and `weight` (f64).
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - optionally the geometries used to build the weights, which adds a `geometry`
+    ///   column holding the line between each pair as WKB so it can be read by GeoPolars
+    ///
+    pub fn to_dataframe<A: GeoFloat>(
+        &self,
+        geoms: Option<&[Geometry<A>]>,
+    ) -> Result<DataFrame, String> {
+        let links = self.sorted_links();
+
+        let mut columns = vec![
+            Column::new(
+                "origin".into(),
+                links.iter().map(|l| l.0 as u64).collect::<Vec<u64>>(),
+            ),
+            Column::new(
+                "dest".into(),
+                links.iter().map(|l| l.1 as u64).collect::<Vec<u64>>(),
+            ),
+            Column::new(
+                "weight".into(),
+                links.iter().map(|l| l.2).collect::<Vec<f64>>(),
+            ),
+        ];
+
+        if let Some(geoms) = geoms {
+            let lines = links
+                .iter()
+                .map(|(origin, dest, _)| {
+                    self.link_line(geoms, *origin, *dest)
+                        .map(|line| line_to_wkb(&line))
+                })
+                .collect::<Result<Vec<Vec<u8>>, String>>()?;
+            columns.push(Column::new("geometry".into(), lines));
+        }
+
+        DataFrame::new(columns).map_err(polars_error)
+    }
+
+    /// Reads weights from a Polars DataFrame with a row per link. The `origin` and `dest`
+    /// columns can be any integer type and the optional `weight` column any numeric type,
+    /// missing weights default to 1. Links are used as given, they are not made symmetric.
+    ///
+    /// # Arguments
+    ///
+    /// * `df` - the DataFrame to read
+    /// * `no_elements` - the number of elements in the original geometry set, defaults to one
+    ///   more than the largest id
+    ///
+    pub fn from_dataframe(df: &DataFrame, no_elements: Option<usize>) -> Result<Weights, String> {
+        let ids = |name: &str| -> Result<Vec<usize>, String> {
+            let column = df
+                .column(name)
+                .map_err(polars_error)?
+                .cast(&DataType::UInt64)
+                .map_err(polars_error)?;
+            column
+                .u64()
+                .map_err(polars_error)?
+                .into_iter()
+                .map(|id| id.map(|id| id as usize))
+                .collect::<Option<Vec<usize>>>()
+                .ok_or_else(|| format!("Column {} contains null ids", name))
+        };
+        let origins = ids("origin")?;
+        let dests = ids("dest")?;
+
+        let weights: Vec<f64> = match df.column("weight") {
+            Ok(column) => column
+                .cast(&DataType::Float64)
+                .map_err(polars_error)?
+                .f64()
+                .map_err(polars_error)?
+                .into_iter()
+                .map(|w| w.unwrap_or(1.0))
+                .collect(),
+            Err(_) => vec![1.0; origins.len()],
+        };
+
+        let max_id = origins.iter().chain(dests.iter()).max().map(|id| id + 1);
+        let no_elements = no_elements.unwrap_or_else(|| max_id.unwrap_or(0));
+        if max_id.unwrap_or(0) > no_elements {
+            return Err(format!(
+                "Ids go up to {} but there are only {} elements",
+                max_id.unwrap() - 1,
+                no_elements
+            ));
+        }
+
+        let mut lookup: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+        for ((origin, dest), weight) in origins.into_iter().zip(dests).zip(weights) {
+            lookup.entry(origin).or_default().insert(dest, weight);
+        }
+        Ok(Weights::new(lookup, no_elements))
+    }
+}
@@ -1,11 +1,16 @@
-use crate::{weights::Weights, WeightBuilder};
-use geo::centroid::Centroid;
+use crate::{weights::Weights, CompactWeights, CoordsWeightBuilder, GeometryCoords, WeightBuilder};
 use geo::euclidean_distance::EuclideanDistance;
 use geo::GeoFloat;
 use geo_types::{Geometry, Point};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use rayon::prelude::*;
+
+/// Builds weights from the euclidean distance between geometries. Points are used directly and
+/// every other geometry type, including LineStrings and nested GeometryCollections, is
+/// represented by its centroid.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DistanceWeights<A>
 where
@@ -25,65 +30,121 @@ where
             use_distance_as_weight,
         }
     }
+
+    /// Checks the builder can produce weights, which needs either a cutoff or the distance
+    /// itself to be used as the weight
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self.cutoff_dist {
+            None if !self.use_distance_as_weight => {
+                Err("Need to specify either a cutoff or use dist as weight".into())
+            }
+            Some(cutoff) if cutoff.is_nan() => Err("Cutoff distance can not be NaN".into()),
+            _ => Ok(()),
+        }
+    }
 }
 
-impl<A> WeightBuilder<A> for DistanceWeights<A>
+impl<A> DistanceWeights<A>
 where
-    A: GeoFloat,
+    A: GeoFloat + Send + Sync,
 {
-    fn compute_weights<T>(&self, geoms: &T) -> Weights
-    where
-        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
-    {
-        let centroids: Vec<Point<A>> = geoms
-            .into_iter()
-            .map(|geom| match geom {
-                Geometry::Point(p) => *p,
-                Geometry::Polygon(p) => p
-                    .centroid()
-                    .expect("Polygon Geometry invalid, could not compute centroid"),
-                Geometry::MultiPolygon(p) => p
-                    .centroid()
-                    .expect("MultiPolygon Geometry invalid, could not compute centroid"),
-                _ => panic!("Geometry not supported"),
+    /// Returns the links from each geometry, indexed by the geometry, or an error if one of the
+    /// geometries is empty or the builder can't produce weights
+    fn rows<C: GeometryCoords + ?Sized>(
+        &self,
+        geoms: &C,
+    ) -> Result<Vec<Vec<(usize, f64)>>, String> {
+        self.validate()?;
+        let cutoff_dist = self.cutoff_dist.map(|cutoff| cutoff.to_f64().unwrap());
+        let centroids: Vec<Point> = (0..geoms.no_geometries())
+            .map(|index| {
+                geoms.representative_point(index).ok_or_else(|| {
+                    format!(
+                        "Geometry {} is empty, could not compute a representative point",
+                        index
+                    )
+                })
             })
-            .collect();
-        let mut weights: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
-
-        for i in 0..centroids.len() {
-            for j in 0..centroids.len() {
-                if i == j {
-                    continue;
-                }
-                let dist = centroids[i].euclidean_distance(&centroids[j]);
-                let weight: Option<A> = match (self.cutoff_dist, self.use_distance_as_weight) {
-                    (Some(cutoff), true) => {
-                        if dist < cutoff {
-                            Some(dist)
-                        } else {
-                            None
-                        }
+            .collect::<Result<_, String>>()?;
+        // Each row of the weights matrix is independent so they are computed in parallel
+        Ok(cfg_into_iter!(0..centroids.len(), 64)
+            .map(|i| {
+                let mut row: Vec<(usize, f64)> = vec![];
+                for j in 0..centroids.len() {
+                    if i == j {
+                        continue;
                     }
-                    (Some(cutoff), false) => {
-                        if dist < cutoff {
-                            Some(A::one())
-                        } else {
-                            None
+                    let dist = centroids[i].euclidean_distance(&centroids[j]);
+                    let weight: Option<f64> = match (cutoff_dist, self.use_distance_as_weight) {
+                        (Some(cutoff), true) => {
+                            if dist < cutoff {
+                                Some(dist)
+                            } else {
+                                None
+                            }
+                        }
+                        (Some(cutoff), false) => {
+                            if dist < cutoff {
+                                Some(1.0)
+                            } else {
+                                None
+                            }
                         }
+                        // validate has already ruled out having neither
+                        (None, _) => Some(dist),
+                    };
+                    if let Some(w) = weight {
+                        row.push((j, w));
                     }
-                    (None, true) => Some(dist),
-                    _ => panic!("Need to specify either a cutoff or use dist as weight"),
-                };
-                if let Some(w) = weight {
-                    weights
-                        .entry(i)
-                        .or_insert_with(HashMap::new)
-                        .entry(j)
-                        .or_insert_with(|| w.to_f64().unwrap());
                 }
-            }
-        }
+                row
+            })
+            .collect())
+    }
+}
 
-        Weights::new(weights, geoms.into_iter().count())
+impl<A> CoordsWeightBuilder for DistanceWeights<A>
+where
+    A: GeoFloat + Send + Sync,
+{
+    fn compute_weights_from_coords<C: GeometryCoords + ?Sized>(&self, geoms: &C) -> Weights {
+        let rows = self.rows(geoms).unwrap_or_else(|e| panic!("{}", e));
+        let no_elements = rows.len();
+        let weights: HashMap<usize, HashMap<usize, f64>> = rows
+            .into_iter()
+            .enumerate()
+            .filter(|(_i, row)| !row.is_empty())
+            .map(|(i, row)| (i, row.into_iter().collect()))
+            .collect();
+
+        Weights::new(weights, no_elements)
+    }
+
+    fn compute_compact_weights_from_coords<C: GeometryCoords + ?Sized>(
+        &self,
+        geoms: &C,
+    ) -> Result<CompactWeights, String> {
+        CompactWeights::from_rows(self.rows(geoms)?)
+    }
+}
+
+impl<A> WeightBuilder<A> for DistanceWeights<A>
+where
+    A: GeoFloat + Send + Sync,
+{
+    fn compute_weights<T: ?Sized>(&self, geoms: &T) -> Weights
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let geoms: Vec<&Geometry<A>> = geoms.into_iter().collect();
+        self.compute_weights_from_coords(geoms.as_slice())
+    }
+
+    fn compute_compact_weights<T: ?Sized>(&self, geoms: &T) -> Result<CompactWeights, String>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let geoms: Vec<&Geometry<A>> = geoms.into_iter().collect();
+        self.compute_compact_weights_from_coords(geoms.as_slice())
     }
 }
@@ -0,0 +1,269 @@
+use crate::utils::representative_point;
+use crate::weights::Weights;
+use geo::{GeoFloat, Line};
+use geo_types::Geometry;
+use geojson::{Feature, FeatureCollection};
+use std::fmt::{self, Display, Write};
+
+/// Escapes the characters which are not allowed in XML text and attribute values
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote or new line
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Weights {
+    /// Returns every link as (origin, dest, weight), sorted by origin then dest
+    pub(crate) fn sorted_links(&self) -> Vec<(usize, usize, f64)> {
+        let mut links: Vec<(usize, usize, f64)> = self
+            .weights()
+            .iter()
+            .flat_map(|(origin, dests)| {
+                dests
+                    .iter()
+                    .map(move |(dest, weight)| (*origin, *dest, *weight))
+            })
+            .collect();
+        links.sort_by_key(|link| (link.0, link.1));
+        links
+    }
+
+    /// Returns the links as a GeoJSON FeatureCollection of lines between the representative
+    /// points of each pair of geometries. Features are sorted by origin then dest and carry
+    /// numeric `origin` and `dest` properties along with the `weight`.
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - the geometries used to build the weights
+    /// * `dedupe_symmetric` - write a single feature, with origin < dest, for links which exist
+    ///   in both directions with the same weight
+    ///
+    pub fn links_feature_collection<A: GeoFloat>(
+        &self,
+        geoms: &[Geometry<A>],
+        dedupe_symmetric: bool,
+    ) -> Result<FeatureCollection, String> {
+        let mut features: Vec<Feature> = vec![];
+
+        for (origin, dest, weight) in self.sorted_links() {
+            if dedupe_symmetric && dest < origin && self.has_matching_reverse(origin, dest, weight)
+            {
+                continue;
+            }
+            let line: geojson::Geometry =
+                geojson::Value::from(&self.link_line(geoms, origin, dest)?).into();
+
+            let mut feature = Feature {
+                geometry: Some(line),
+                ..Default::default()
+            };
+            feature.set_property("origin", origin);
+            feature.set_property("dest", dest);
+            feature.set_property("weight", weight);
+            features.push(feature);
+        }
+
+        Ok(FeatureCollection {
+            features,
+            bbox: None,
+            foreign_members: None,
+        })
+    }
+
+    /// Returns the line between the representative points of the origin and dest geometries
+    pub(crate) fn link_line<A: GeoFloat>(
+        &self,
+        geoms: &[Geometry<A>],
+        origin: usize,
+        dest: usize,
+    ) -> Result<Line<A>, String> {
+        let point = |index: usize| {
+            geoms
+                .get(index)
+                .ok_or_else(|| {
+                    format!(
+                        "Link references geometry {} but only {} geometries were given",
+                        index,
+                        geoms.len()
+                    )
+                })
+                .and_then(|geom| {
+                    representative_point(geom).ok_or_else(|| format!("Geometry {} is empty", index))
+                })
+        };
+        Ok(Line::new(point(origin)?, point(dest)?))
+    }
+
+    fn has_matching_reverse(&self, origin: usize, dest: usize, weight: f64) -> bool {
+        self.weights()
+            .get(&dest)
+            .and_then(|dests| dests.get(&origin))
+            .map(|w| *w == weight)
+            .unwrap_or(false)
+    }
+
+    /// Returns the weights as a directed GraphML document, with a node for every element and an
+    /// edge for every link. Nodes and edges are written in index order so the output is stable.
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - optionally the geometries used to build the weights, which adds the x and y
+    ///   of each geometries representative point as node attributes
+    /// * `ids` - optionally the original id of each element, added as a node attribute
+    ///
+    pub fn to_graphml<A, I>(
+        &self,
+        geoms: Option<&[Geometry<A>]>,
+        ids: Option<&[I]>,
+    ) -> Result<String, String>
+    where
+        A: GeoFloat,
+        I: Display,
+    {
+        self.check_length("geometries", geoms.map(|g| g.len()))?;
+        self.check_length("ids", ids.map(|i| i.len()))?;
+
+        let mut out = String::new();
+        self.write_graphml(&mut out, geoms, ids)
+            .map_err(|e| e.to_string())?;
+        Ok(out)
+    }
+
+    fn write_graphml<A, I>(
+        &self,
+        out: &mut String,
+        geoms: Option<&[Geometry<A>]>,
+        ids: Option<&[I]>,
+    ) -> fmt::Result
+    where
+        A: GeoFloat,
+        I: Display,
+    {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        if ids.is_some() {
+            writeln!(
+                out,
+                r#"  <key id="id" for="node" attr.name="id" attr.type="string"/>"#
+            )?;
+        }
+        if geoms.is_some() {
+            writeln!(
+                out,
+                r#"  <key id="x" for="node" attr.name="x" attr.type="double"/>"#
+            )?;
+            writeln!(
+                out,
+                r#"  <key id="y" for="node" attr.name="y" attr.type="double"/>"#
+            )?;
+        }
+        writeln!(
+            out,
+            r#"  <key id="weight" for="edge" attr.name="weight" attr.type="double"/>"#
+        )?;
+        writeln!(out, r#"  <graph id="weights" edgedefault="directed">"#)?;
+
+        for index in 0..self.no_elements() {
+            writeln!(out, r#"    <node id="n{}">"#, index)?;
+            if let Some(ids) = ids {
+                writeln!(
+                    out,
+                    r#"      <data key="id">{}</data>"#,
+                    escape_xml(&ids[index].to_string())
+                )?;
+            }
+            if let Some(point) = geoms.and_then(|g| representative_point(&g[index])) {
+                writeln!(
+                    out,
+                    r#"      <data key="x">{}</data>"#,
+                    point.x().to_f64().unwrap()
+                )?;
+                writeln!(
+                    out,
+                    r#"      <data key="y">{}</data>"#,
+                    point.y().to_f64().unwrap()
+                )?;
+            }
+            writeln!(out, "    </node>")?;
+        }
+
+        for (origin, dest, weight) in self.sorted_links() {
+            writeln!(out, r#"    <edge source="n{}" target="n{}">"#, origin, dest)?;
+            writeln!(out, r#"      <data key="weight">{}</data>"#, weight)?;
+            writeln!(out, "    </edge>")?;
+        }
+
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")
+    }
+
+    /// Returns the weights as a CSV edge list with a header row, one row per link sorted by
+    /// origin then dest. Columns are `origin,dest,weight`, with `origin_id,dest_id` added when
+    /// ids are given.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - optionally the original id of each element
+    ///
+    pub fn to_csv_edge_list<I: Display>(&self, ids: Option<&[I]>) -> Result<String, String> {
+        self.check_length("ids", ids.map(|i| i.len()))?;
+
+        let mut out = String::new();
+        self.write_csv_edge_list(&mut out, ids)
+            .map_err(|e| e.to_string())?;
+        Ok(out)
+    }
+
+    fn write_csv_edge_list<I: Display>(&self, out: &mut String, ids: Option<&[I]>) -> fmt::Result {
+        match ids {
+            Some(_) => out.push_str("origin,dest,origin_id,dest_id,weight\n"),
+            None => out.push_str("origin,dest,weight\n"),
+        }
+        for (origin, dest, weight) in self.sorted_links() {
+            match ids {
+                Some(ids) => {
+                    writeln!(
+                        out,
+                        "{},{},{},{},{}",
+                        origin,
+                        dest,
+                        escape_csv(&ids[origin].to_string()),
+                        escape_csv(&ids[dest].to_string()),
+                        weight
+                    )?;
+                }
+                None => {
+                    writeln!(out, "{},{},{}", origin, dest, weight)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_length(&self, name: &str, length: Option<usize>) -> Result<(), String> {
+        match length {
+            Some(length) if length != self.no_elements() => Err(format!(
+                "Expected {} {} but got {}",
+                self.no_elements(),
+                name,
+                length
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::utils::representative_point;
+use crate::{weights::Weights, CompactWeights};
+use geo::GeoFloat;
+use geo_types::{Coord, Geometry, Point, Polygon};
+
+/// Read access to the coordinates of a set of geometries, so the weight builders can work
+/// straight from wherever the coordinates are stored, such as GeoArrow buffers, without first
+/// converting them to geo_types geometries. Each geometry is made up of paths: the rings of its
+/// polygons, its lines, or a path with a single coordinate for each of its points.
+pub trait GeometryCoords: Sync {
+    /// The number of geometries
+    fn no_geometries(&self) -> usize;
+
+    /// Calls `f` with the coordinates of each path of the geometry in turn
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the geometry to read
+    /// * `f` - called once per path with an iterator over its coordinates
+    ///
+    fn for_each_path(&self, index: usize, f: &mut dyn FnMut(&mut dyn Iterator<Item = Coord>));
+
+    /// Returns the point representing the geometry when computing distances, the point itself
+    /// for Points and the centroid for everything else. Returns None for empty geometries.
+    fn representative_point(&self, index: usize) -> Option<Point>;
+}
+
+/// Weight builders which read the geometries through `GeometryCoords`
+pub trait CoordsWeightBuilder {
+    /// Computes the weights for geometries read through `GeometryCoords`
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - the geometries to compute the weights for
+    ///
+    fn compute_weights_from_coords<C: GeometryCoords + ?Sized>(&self, geoms: &C) -> Weights;
+
+    /// Same as `compute_weights_from_coords` but writes the compact form directly
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - the geometries to compute the weights for
+    ///
+    fn compute_compact_weights_from_coords<C: GeometryCoords + ?Sized>(
+        &self,
+        geoms: &C,
+    ) -> Result<CompactWeights, String>;
+}
+
+fn to_f64<A: GeoFloat>(coord: Coord<A>) -> Coord {
+    Coord {
+        x: coord.x.to_f64().unwrap(),
+        y: coord.y.to_f64().unwrap(),
+    }
+}
+
+fn polygon_paths<A: GeoFloat>(
+    polygon: &Polygon<A>,
+    f: &mut dyn FnMut(&mut dyn Iterator<Item = Coord>),
+) {
+    for ring in std::iter::once(polygon.exterior()).chain(polygon.interiors()) {
+        f(&mut ring.0.iter().map(|c| to_f64(*c)));
+    }
+}
+
+fn geometry_paths<A: GeoFloat>(
+    geom: &Geometry<A>,
+    f: &mut dyn FnMut(&mut dyn Iterator<Item = Coord>),
+) {
+    match geom {
+        Geometry::Point(p) => f(&mut std::iter::once(to_f64(p.0))),
+        Geometry::Line(l) => f(&mut std::iter::once(l.start)
+            .chain(std::iter::once(l.end))
+            .map(to_f64)),
+        Geometry::LineString(ls) => f(&mut ls.0.iter().map(|c| to_f64(*c))),
+        Geometry::Polygon(p) => polygon_paths(p, f),
+        Geometry::MultiPoint(mp) => {
+            for p in mp.iter() {
+                f(&mut std::iter::once(to_f64(p.0)));
+            }
+        }
+        Geometry::MultiLineString(mls) => {
+            for ls in mls.iter() {
+                f(&mut ls.0.iter().map(|c| to_f64(*c)));
+            }
+        }
+        Geometry::MultiPolygon(mp) => {
+            for p in mp.iter() {
+                polygon_paths(p, f);
+            }
+        }
+        Geometry::Rect(r) => polygon_paths(&r.to_polygon(), f),
+        Geometry::Triangle(t) => polygon_paths(&t.to_polygon(), f),
+        Geometry::GeometryCollection(gc) => {
+            for g in gc.iter() {
+                geometry_paths(g, f);
+            }
+        }
+    }
+}
+
+impl<A> GeometryCoords for [&Geometry<A>]
+where
+    A: GeoFloat + Sync,
+{
+    fn no_geometries(&self) -> usize {
+        self.len()
+    }
+
+    fn for_each_path(&self, index: usize, f: &mut dyn FnMut(&mut dyn Iterator<Item = Coord>)) {
+        geometry_paths(self[index], f)
+    }
+
+    fn representative_point(&self, index: usize) -> Option<Point> {
+        representative_point(self[index]).map(|p| Point(to_f64(p.0)))
+    }
+}
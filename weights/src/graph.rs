@@ -0,0 +1,250 @@
+use crate::weights::Weights;
+use std::collections::{HashMap, VecDeque};
+
+/// Union find structure used to track which observations are already connected while building
+/// the minimum spanning tree
+struct DisjointSet {
+    parents: Vec<usize>,
+    ranks: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self {
+            parents: (0..size).collect(),
+            ranks: vec![0; size],
+        }
+    }
+
+    fn find(&mut self, item: usize) -> usize {
+        let mut root = item;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // Compress the path so future lookups are quicker
+        let mut current = item;
+        while self.parents[current] != root {
+            let next = self.parents[current];
+            self.parents[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Joins the sets containing a and b, returning false if they were already joined
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a == root_b {
+            return false;
+        }
+        match self.ranks[root_a].cmp(&self.ranks[root_b]) {
+            std::cmp::Ordering::Less => self.parents[root_a] = root_b,
+            std::cmp::Ordering::Greater => self.parents[root_b] = root_a,
+            std::cmp::Ordering::Equal => {
+                self.parents[root_b] = root_a;
+                self.ranks[root_a] += 1;
+            }
+        }
+        true
+    }
+}
+
+impl Weights {
+    /// Returns each undirected link once as (lower id, higher id), sorted. A link is included if
+    /// either direction is present in the weights.
+    fn undirected_links(&self) -> Vec<(usize, usize)> {
+        let mut links: Vec<(usize, usize)> = self
+            .weights()
+            .iter()
+            .flat_map(|(origin, dests)| {
+                dests
+                    .keys()
+                    .filter(move |dest| *dest != origin)
+                    .map(move |dest| (*origin.min(dest), *origin.max(dest)))
+            })
+            .collect();
+        links.sort_unstable();
+        links.dedup();
+        links
+    }
+
+    /// Returns the minimum spanning tree of the weights graph, where the cost of each link is
+    /// the euclidean distance between the attributes of the two observations. Links are treated
+    /// as undirected and the result is symmetric with the cost as the weight. If the graph is
+    /// not connected this is a minimum spanning forest with one tree per component. This is the
+    /// starting point for SKATER style regionalization.
+    ///
+    /// # Arguments
+    ///
+    /// * `attributes` - columns of attributes, each with one value per observation
+    ///
+    pub fn minimum_spanning_tree(&self, attributes: &[Vec<f64>]) -> Result<Weights, String> {
+        if let Some(column) = attributes.iter().find(|c| c.len() != self.no_elements()) {
+            return Err(format!(
+                "Expected {} values but got {}",
+                self.no_elements(),
+                column.len()
+            ));
+        }
+        if attributes.iter().flatten().any(|value| value.is_nan()) {
+            return Err("Attributes can not contain NaN values".into());
+        }
+        let dissimilarity = |a: usize, b: usize| -> f64 {
+            attributes
+                .iter()
+                .map(|column| (column[a] - column[b]).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        };
+
+        let mut links: Vec<(usize, usize, f64)> = self
+            .undirected_links()
+            .into_iter()
+            .filter(|(_a, b)| *b < self.no_elements())
+            .map(|(a, b)| (a, b, dissimilarity(a, b)))
+            .collect();
+        // Stable sort keeps ties in id order so the tree is deterministic
+        links.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+        let mut components = DisjointSet::new(self.no_elements());
+        let mut tree: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+        for (a, b, cost) in links {
+            if components.union(a, b) {
+                tree.entry(a).or_default().insert(b, cost);
+                tree.entry(b).or_default().insert(a, cost);
+            }
+        }
+        Ok(Weights::new(tree, self.no_elements()))
+    }
+
+    /// Returns the number of links on the shortest path from the origin to every observation,
+    /// or None for observations that can't be reached. Links are followed in the direction they
+    /// are stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the id of the observation to start from
+    ///
+    pub fn hop_distances(&self, origin: usize) -> Vec<Option<usize>> {
+        let mut distances: Vec<Option<usize>> = vec![None; self.no_elements()];
+        if origin >= self.no_elements() {
+            return distances;
+        }
+        distances[origin] = Some(0);
+        let mut queue = VecDeque::from([origin]);
+
+        while let Some(current) = queue.pop_front() {
+            let next_distance = distances[current].map(|d| d + 1);
+            if let Some(dests) = self.weights().get(&current) {
+                for dest in dests.keys() {
+                    if *dest < distances.len() && distances[*dest].is_none() {
+                        distances[*dest] = next_distance;
+                        queue.push_back(*dest);
+                    }
+                }
+            }
+        }
+        distances
+    }
+
+    /// Returns the number of links on the shortest path between two observations, or None if
+    /// there is no path
+    pub fn hop_distance(&self, origin: usize, dest: usize) -> Option<usize> {
+        self.hop_distances(origin).get(dest).copied().flatten()
+    }
+
+    /// Returns the number of links on the shortest path from the origin to each observation at
+    /// most `max_hops` links away. The search stops expanding at that depth and only keeps the
+    /// observations it reaches, so it stays cheap for low orders on large weights.
+    fn hops_within(&self, origin: usize, max_hops: usize) -> HashMap<usize, usize> {
+        let mut distances: HashMap<usize, usize> = HashMap::from([(origin, 0)]);
+        let mut queue = VecDeque::from([origin]);
+
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[&current];
+            if distance == max_hops {
+                continue;
+            }
+            if let Some(dests) = self.weights().get(&current) {
+                for dest in dests.keys() {
+                    if *dest < self.no_elements() && !distances.contains_key(dest) {
+                        distances.insert(*dest, distance + 1);
+                        queue.push_back(*dest);
+                    }
+                }
+            }
+        }
+        distances
+    }
+
+    /// Returns binary weights linking each observation to those exactly `order` links away, as
+    /// used for spatial correlograms. Order 1 gives the original neighbors.
+    pub fn higher_order(&self, order: usize) -> Weights {
+        let weights: HashMap<usize, HashMap<usize, f64>> = (0..self.no_elements())
+            .map(|origin| {
+                let dests: HashMap<usize, f64> = if order == 0 {
+                    HashMap::new()
+                } else {
+                    self.hops_within(origin, order)
+                        .into_iter()
+                        .filter(|(_, d)| *d == order)
+                        .map(|(dest, _)| (dest, 1.0))
+                        .collect()
+                };
+                (origin, dests)
+            })
+            .collect();
+        Weights::new(weights, self.no_elements())
+    }
+
+    /// Returns a copy of the weights keeping only the links for which `keep(origin, dest,
+    /// weight)` returns true
+    pub fn prune<F>(&self, keep: F) -> Weights
+    where
+        F: Fn(usize, usize, f64) -> bool,
+    {
+        let weights: HashMap<usize, HashMap<usize, f64>> = self
+            .weights()
+            .iter()
+            .map(|(origin, dests)| {
+                let dests: HashMap<usize, f64> = dests
+                    .iter()
+                    .filter(|(dest, weight)| keep(*origin, **dest, **weight))
+                    .map(|(dest, weight)| (*dest, *weight))
+                    .collect();
+                (*origin, dests)
+            })
+            .collect();
+        Weights::new(weights, self.no_elements())
+    }
+
+    /// Returns a copy of the weights with the given links removed in both directions, for
+    /// example to cut a spanning tree in to regions
+    pub fn remove_links(&self, links: &[(usize, usize)]) -> Weights {
+        self.prune(|origin, dest, _| {
+            !links
+                .iter()
+                .any(|(a, b)| (*a == origin && *b == dest) || (*a == dest && *b == origin))
+        })
+    }
+
+    /// Labels each observation with the id of the connected component it belongs to, treating
+    /// links as undirected. Components are numbered in order of their lowest observation id.
+    pub fn connected_components(&self) -> Vec<usize> {
+        let mut components = DisjointSet::new(self.no_elements());
+        for (a, b) in self.undirected_links() {
+            if a < self.no_elements() && b < self.no_elements() {
+                components.union(a, b);
+            }
+        }
+
+        let mut labels: HashMap<usize, usize> = HashMap::new();
+        (0..self.no_elements())
+            .map(|index| {
+                let root = components.find(index);
+                let next_label = labels.len();
+                *labels.entry(root).or_insert(next_label)
+            })
+            .collect()
+    }
+}
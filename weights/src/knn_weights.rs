@@ -0,0 +1,128 @@
+use crate::{weights::Weights, CompactWeights, CoordsWeightBuilder, GeometryCoords, WeightBuilder};
+use geo::euclidean_distance::EuclideanDistance;
+use geo::haversine_distance::HaversineDistance;
+use geo::GeoFloat;
+use geo_types::{Geometry, Point};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use rayon::prelude::*;
+
+/// The metric used to measure the distance between two points
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    /// Straight line distance in the units of the coordinates
+    #[default]
+    Euclidean,
+    /// Great circle distance in meters, treating coordinates as longitude / latitude
+    Haversine,
+}
+
+impl DistanceMetric {
+    /// Returns the distance between two points using this metric
+    pub fn distance<A: GeoFloat>(&self, a: &Point<A>, b: &Point<A>) -> f64 {
+        match self {
+            DistanceMetric::Euclidean => a.euclidean_distance(b).to_f64().unwrap(),
+            DistanceMetric::Haversine => {
+                let a = Point::new(a.x().to_f64().unwrap(), a.y().to_f64().unwrap());
+                let b = Point::new(b.x().to_f64().unwrap(), b.y().to_f64().unwrap());
+                a.haversine_distance(&b)
+            }
+        }
+    }
+}
+
+/// Builds weights linking each geometry to its k nearest neighbors, measured between the
+/// representative points of the geometries. The resulting weights are not symmetric. Ties are
+/// broken by picking the geometry with the lowest index.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KNNWeights {
+    k: usize,
+    #[serde(default)]
+    metric: DistanceMetric,
+}
+
+impl KNNWeights {
+    pub fn new(k: usize, metric: DistanceMetric) -> Self {
+        Self { k, metric }
+    }
+}
+
+impl KNNWeights {
+    /// Returns the links from each geometry, indexed by the geometry, with None for empty
+    /// geometries
+    fn rows<C: GeometryCoords + ?Sized>(&self, geoms: &C) -> Vec<Option<Vec<(usize, f64)>>> {
+        let points: Vec<Option<Point>> = (0..geoms.no_geometries())
+            .map(|index| geoms.representative_point(index))
+            .collect();
+
+        cfg_into_iter!(0..points.len(), 64)
+            .map(|i| {
+                let origin = points[i]?;
+                let mut dists: Vec<(usize, f64)> = points
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .filter_map(|(j, dest)| dest.map(|d| (j, self.metric.distance(&origin, &d))))
+                    .collect();
+                dists.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                Some(
+                    dists
+                        .into_iter()
+                        .take(self.k)
+                        .map(|(j, _dist)| (j, 1.0))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl CoordsWeightBuilder for KNNWeights {
+    fn compute_weights_from_coords<C: GeometryCoords + ?Sized>(&self, geoms: &C) -> Weights {
+        let rows = self.rows(geoms);
+        let no_elements = rows.len();
+        let weights: HashMap<usize, HashMap<usize, f64>> = rows
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, row)| Some((i, row?.into_iter().collect())))
+            .collect();
+
+        Weights::new(weights, no_elements)
+    }
+
+    fn compute_compact_weights_from_coords<C: GeometryCoords + ?Sized>(
+        &self,
+        geoms: &C,
+    ) -> Result<CompactWeights, String> {
+        CompactWeights::from_rows(
+            self.rows(geoms)
+                .into_iter()
+                .map(|row| row.unwrap_or_default())
+                .collect(),
+        )
+    }
+}
+
+impl<A> WeightBuilder<A> for KNNWeights
+where
+    A: GeoFloat + Send + Sync,
+{
+    fn compute_weights<T: ?Sized>(&self, geoms: &T) -> Weights
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let geoms: Vec<&Geometry<A>> = geoms.into_iter().collect();
+        self.compute_weights_from_coords(geoms.as_slice())
+    }
+
+    fn compute_compact_weights<T: ?Sized>(&self, geoms: &T) -> Result<CompactWeights, String>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let geoms: Vec<&Geometry<A>> = geoms.into_iter().collect();
+        self.compute_compact_weights_from_coords(geoms.as_slice())
+    }
+}
@@ -1,12 +1,61 @@
 extern crate num_traits;
 
+/// Utility to handle using rayons par iterator when the parallel feature is enabled and we are
+/// not targeting wasm
+macro_rules! cfg_into_iter {
+    ($e: expr, $min_len: expr) => {{
+        #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+        let result = $e.into_par_iter().with_min_len($min_len);
+
+        #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+        let result = $e.into_iter();
+
+        result
+    }};
+    ($e: expr) => {{
+        #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+        let result = $e.into_par_iter();
+
+        #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+        let result = $e.into_iter();
+
+        result
+    }};
+}
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod compact_weights;
+pub mod cross_weights;
+#[cfg(feature = "polars")]
+pub mod dataframe;
 pub mod distance_weights;
+pub mod export;
+pub mod geometry_coords;
+pub mod graph;
+pub mod knn_weights;
+pub mod network_weights;
 pub mod queens_weights;
+pub mod raster_weights;
 pub mod rook_weights;
+pub mod spectral;
 mod utils;
+pub mod validation;
 pub mod weights;
+pub mod weights_spec;
 
+#[cfg(feature = "arrow")]
+pub use arrow::*;
+pub use compact_weights::*;
+pub use cross_weights::*;
 pub use distance_weights::*;
+pub use geometry_coords::*;
+pub use knn_weights::*;
+pub use network_weights::*;
 pub use queens_weights::*;
+pub use raster_weights::*;
 pub use rook_weights::*;
+pub use spectral::*;
+pub use validation::*;
 pub use weights::*;
+pub use weights_spec::*;
@@ -0,0 +1,311 @@
+use crate::utils::{coords_to_tolerance, representative_point};
+use crate::{weights::Weights, WeightBuilder};
+use geo::euclidean_distance::EuclideanDistance;
+use geo::GeoFloat;
+use geo_types::{Coord, Geometry, LineString, Point};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Kernel functions used to convert a distance into a weight. Each is evaluated at the distance
+/// divided by the bandwidth, and all but the Gaussian are zero beyond the bandwidth.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KernelFunction {
+    Uniform,
+    Triangular,
+    Epanechnikov,
+    Gaussian,
+}
+
+impl KernelFunction {
+    /// Evaluate the kernel at the standardized distance `z` (distance / bandwidth)
+    pub fn evaluate(&self, z: f64) -> f64 {
+        match self {
+            KernelFunction::Gaussian => (-0.5 * z * z).exp() / (2.0 * std::f64::consts::PI).sqrt(),
+            _ if z > 1.0 => 0.0,
+            KernelFunction::Uniform => 0.5,
+            KernelFunction::Triangular => 1.0 - z,
+            KernelFunction::Epanechnikov => 0.75 * (1.0 - z * z),
+        }
+    }
+}
+
+/// Specifies how the network distance between two observations is turned into a weight
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkWeightType<A> {
+    /// Every observation reachable within the cutoff gets a weight of 1
+    Binary,
+    /// Weight is 1 / distance^power. Observations at zero distance from each other are skipped
+    InverseDistance { power: A },
+    /// Weight is the kernel evaluated at distance / bandwidth
+    Kernel {
+        function: KernelFunction,
+        bandwidth: A,
+    },
+}
+
+/// Builds weights from the shortest path distance between observations over a network of
+/// LineStrings. LineStrings are joined where they share a vertex (after hashing with the given
+/// tolerance), so the network is expected to be noded at intersections. Each observation is
+/// snapped to the nearest network vertex and the distance between two observations is the
+/// distance each travels to reach the network plus the shortest path between their vertices.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NetworkWeights<A>
+where
+    A: GeoFloat,
+{
+    network: Vec<LineString<A>>,
+    tolerance: A,
+    cutoff_dist: Option<A>,
+    weight_type: NetworkWeightType<A>,
+}
+
+/// Entry in the dijkstra priority queue, ordered so the smallest distance is popped first
+#[derive(PartialEq)]
+struct QueueEntry {
+    dist: f64,
+    node: usize,
+}
+
+impl Eq for QueueEntry {}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .dist
+            .partial_cmp(&self.dist)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Network represented as an adjacency list along with the location of each node
+struct NetworkGraph<A>
+where
+    A: GeoFloat,
+{
+    nodes: Vec<Point<A>>,
+    edges: Vec<Vec<(usize, f64)>>,
+}
+
+impl<A> NetworkWeights<A>
+where
+    A: GeoFloat,
+{
+    /// Create a new network weights builder
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - The LineStrings making up the network
+    /// * `tolerance` - Multiplier used when hashing vertices to decide if they are the same node
+    /// * `cutoff_dist` - Maximum network distance at which observations are neighbors
+    /// * `weight_type` - How network distances are converted into weights
+    ///
+    pub fn new(
+        network: Vec<LineString<A>>,
+        tolerance: A,
+        cutoff_dist: Option<A>,
+        weight_type: NetworkWeightType<A>,
+    ) -> Self {
+        Self {
+            network,
+            tolerance,
+            cutoff_dist,
+            weight_type,
+        }
+    }
+
+    /// Checks the tolerance, cutoff and weight type can be used to build weights
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if !(self.tolerance.is_finite() && self.tolerance >= A::one()) {
+            return Err(format!(
+                "Tolerance should be a multiplier of at least 1, not a distance, but got {:?}",
+                self.tolerance
+            ));
+        }
+        if self.cutoff_dist.is_some_and(|cutoff| cutoff.is_nan()) {
+            return Err("Cutoff distance can not be NaN".into());
+        }
+        match self.weight_type {
+            NetworkWeightType::Kernel { bandwidth, .. }
+                if bandwidth.is_nan() || bandwidth <= A::zero() =>
+            {
+                Err(format!(
+                    "Kernel bandwidth should be positive but got {:?}",
+                    bandwidth
+                ))
+            }
+            NetworkWeightType::InverseDistance { power } if !power.is_finite() => Err(format!(
+                "Inverse distance power should be finite but got {:?}",
+                power
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn build_graph(&self) -> NetworkGraph<A> {
+        let tolerance = self.tolerance.to_f64().unwrap();
+        let mut node_lookup: HashMap<(isize, isize), usize> = HashMap::new();
+        let mut nodes: Vec<Point<A>> = vec![];
+        let mut edges: Vec<Vec<(usize, f64)>> = vec![];
+
+        let mut node_id = |coord: Coord<A>, edges: &mut Vec<Vec<(usize, f64)>>| {
+            *node_lookup
+                .entry(coords_to_tolerance(coord, tolerance))
+                .or_insert_with(|| {
+                    nodes.push(coord.into());
+                    edges.push(vec![]);
+                    nodes.len() - 1
+                })
+        };
+
+        for line_string in self.network.iter() {
+            for line in line_string.lines() {
+                let start = node_id(line.start, &mut edges);
+                let end = node_id(line.end, &mut edges);
+                if start == end {
+                    continue;
+                }
+                let length = line.start_point().euclidean_distance(&line.end_point());
+                let length = length.to_f64().unwrap();
+                edges[start].push((end, length));
+                edges[end].push((start, length));
+            }
+        }
+
+        NetworkGraph { nodes, edges }
+    }
+
+    /// Shortest path distances from `source` to every node within `max_dist`
+    fn shortest_paths(
+        graph: &NetworkGraph<A>,
+        source: usize,
+        max_dist: f64,
+    ) -> HashMap<usize, f64> {
+        let mut dists: HashMap<usize, f64> = HashMap::from([(source, 0.0)]);
+        let mut queue = BinaryHeap::from([QueueEntry {
+            dist: 0.0,
+            node: source,
+        }]);
+
+        while let Some(QueueEntry { dist, node }) = queue.pop() {
+            if dist > *dists.get(&node).unwrap_or(&f64::INFINITY) {
+                continue;
+            }
+            for (next, length) in graph.edges[node].iter() {
+                let next_dist = dist + length;
+                if next_dist <= max_dist && next_dist < *dists.get(next).unwrap_or(&f64::INFINITY) {
+                    dists.insert(*next, next_dist);
+                    queue.push(QueueEntry {
+                        dist: next_dist,
+                        node: *next,
+                    });
+                }
+            }
+        }
+        dists
+    }
+
+    fn distance_to_weight(&self, dist: f64) -> Option<f64> {
+        match self.weight_type {
+            NetworkWeightType::Binary => Some(1.0),
+            NetworkWeightType::InverseDistance { power } => {
+                if dist > 0.0 {
+                    Some(1.0 / dist.powf(power.to_f64().unwrap()))
+                } else {
+                    None
+                }
+            }
+            NetworkWeightType::Kernel {
+                function,
+                bandwidth,
+            } => {
+                let weight = function.evaluate(dist / bandwidth.to_f64().unwrap());
+                if weight > 0.0 {
+                    Some(weight)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl<A> WeightBuilder<A> for NetworkWeights<A>
+where
+    A: GeoFloat,
+{
+    fn compute_weights<T: ?Sized>(&self, geoms: &T) -> Weights
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let graph = self.build_graph();
+        let max_dist = self
+            .cutoff_dist
+            .map(|c| c.to_f64().unwrap())
+            .unwrap_or(f64::INFINITY);
+
+        // Snap each observation to the closest node in the network, recording how far it had to
+        // travel to get there.
+        let snapped: Vec<Option<(usize, f64)>> = geoms
+            .into_iter()
+            .map(|geom| {
+                let point = representative_point(geom)?;
+                graph
+                    .nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(node, node_point)| {
+                        (node, point.euclidean_distance(node_point).to_f64().unwrap())
+                    })
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            })
+            .collect();
+
+        let mut observations_at_node: HashMap<usize, Vec<(usize, f64)>> = HashMap::new();
+        for (index, snap) in snapped.iter().enumerate() {
+            if let Some((node, snap_dist)) = snap {
+                observations_at_node
+                    .entry(*node)
+                    .or_default()
+                    .push((index, *snap_dist));
+            }
+        }
+
+        let mut weights: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+        for (index, snap) in snapped.iter().enumerate() {
+            let (node, snap_dist) = match snap {
+                Some(s) => *s,
+                None => continue,
+            };
+            let entry = weights.entry(index).or_default();
+            let path_dists = Self::shortest_paths(&graph, node, max_dist - snap_dist);
+
+            for (reached, path_dist) in path_dists.iter() {
+                let others = match observations_at_node.get(reached) {
+                    Some(others) => others,
+                    None => continue,
+                };
+                for (other, other_snap_dist) in others.iter() {
+                    let dist = snap_dist + path_dist + other_snap_dist;
+                    if *other == index || dist > max_dist {
+                        continue;
+                    }
+                    if let Some(w) = self.distance_to_weight(dist) {
+                        entry.insert(*other, w);
+                    }
+                }
+            }
+        }
+
+        Weights::new(weights, geoms.into_iter().count())
+    }
+}
@@ -1,11 +1,20 @@
+use crate::compact_weights::CompactWeights;
+use crate::utils::{compact_weights_from_hash, coords_to_tolerance, weights_from_hash};
 use crate::weights::Weights;
-use crate::{utils::coords_to_tolerance, WeightBuilder};
-use geo::algorithm::coords_iter::CoordsIter;
+use crate::{CoordsWeightBuilder, GeometryCoords, WeightBuilder};
 use geo::GeoFloat;
 use geo_types::Geometry;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use rayon::prelude::*;
+
+/// Builds contiguity weights where geometries are neighbors if they share at least one vertex.
+/// This applies to any geometry type, so LineStrings touching at an endpoint or Points at the
+/// same location are also treated as neighbors.
+/// Coordinates are multiplied by the tolerance and floored before being compared, so a tolerance
+/// of 10000.0 matches coordinates that agree to four decimal places.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct QueensWeights<A>
 where
@@ -21,44 +30,92 @@ where
     pub fn new(tolerance: A) -> Self {
         Self { tolerance }
     }
+
+    /// Checks the tolerance can be used to hash coordinates. Multipliers below 1 are rejected as
+    /// they are most likely a distance such as 0.0001, which would merge nearby vertices.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.tolerance.is_finite() && self.tolerance >= A::one() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Tolerance should be a multiplier of at least 1, not a distance, but got {:?}",
+                self.tolerance
+            ))
+        }
+    }
 }
 
-impl<A> WeightBuilder<A> for QueensWeights<A>
+impl<A> QueensWeights<A>
 where
-    A: GeoFloat,
+    A: GeoFloat + Send + Sync,
 {
-    fn compute_weights<T>(&self, geoms: &T) -> Weights
-    where
-        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
-    {
-        let mut coord_hash: HashMap<(isize, isize), Vec<usize>> = HashMap::new();
+    /// Groups the geometries by the hashed coordinates of their vertices
+    fn coord_hash<C: GeometryCoords + ?Sized>(
+        &self,
+        geoms: &C,
+    ) -> HashMap<(isize, isize), Vec<usize>> {
+        let tolerance = self.tolerance.to_f64().unwrap();
+
+        // Hashing the coordinates is the expensive part so it's done for each geometry in
+        // parallel, with the results merged in to a single lookup afterwards.
+        let hashed_geoms: Vec<Vec<(isize, isize)>> = cfg_into_iter!(0..geoms.no_geometries(), 64)
+            .map(|index| {
+                let mut hashed = vec![];
+                geoms.for_each_path(index, &mut |path| {
+                    hashed.extend(path.map(|coords| coords_to_tolerance(coords, tolerance)))
+                });
+                hashed
+            })
+            .collect();
 
-        for (index, geom) in geoms.into_iter().enumerate() {
-            for coords in geom.coords_iter() {
-                let hashed_coords = coords_to_tolerance(coords, 1000.0);
+        let mut coord_hash: HashMap<(isize, isize), Vec<usize>> = HashMap::new();
+        for (index, hashed_coords) in hashed_geoms.into_iter().enumerate() {
+            for hashed_coords in hashed_coords {
                 coord_hash
                     .entry(hashed_coords)
                     .and_modify(|v| v.push(index))
                     .or_insert_with(|| vec![index]);
             }
         }
+        coord_hash
+    }
+}
 
-        let mut weights: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
-        for (_coords, values) in coord_hash.iter() {
-            for index in values.iter() {
-                weights
-                    .entry(*index)
-                    .and_modify(|entry| {
-                        for index2 in values.iter() {
-                            if index != index2 {
-                                entry.insert(*index2, 1.0);
-                            }
-                        }
-                    })
-                    .or_insert_with(HashMap::new);
-            }
-        }
+impl<A> CoordsWeightBuilder for QueensWeights<A>
+where
+    A: GeoFloat + Send + Sync,
+{
+    fn compute_weights_from_coords<C: GeometryCoords + ?Sized>(&self, geoms: &C) -> Weights {
+        let weights = weights_from_hash(&self.coord_hash(geoms));
+
+        Weights::new(weights, geoms.no_geometries())
+    }
 
-        Weights::new(weights, geoms.into_iter().count())
+    fn compute_compact_weights_from_coords<C: GeometryCoords + ?Sized>(
+        &self,
+        geoms: &C,
+    ) -> Result<CompactWeights, String> {
+        compact_weights_from_hash(&self.coord_hash(geoms), geoms.no_geometries())
+    }
+}
+
+impl<A> WeightBuilder<A> for QueensWeights<A>
+where
+    A: GeoFloat + Send + Sync,
+{
+    fn compute_weights<T: ?Sized>(&self, geoms: &T) -> Weights
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let geoms: Vec<&Geometry<A>> = geoms.into_iter().collect();
+        self.compute_weights_from_coords(geoms.as_slice())
+    }
+
+    fn compute_compact_weights<T: ?Sized>(&self, geoms: &T) -> Result<CompactWeights, String>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let geoms: Vec<&Geometry<A>> = geoms.into_iter().collect();
+        self.compute_compact_weights_from_coords(geoms.as_slice())
     }
 }
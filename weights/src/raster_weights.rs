@@ -0,0 +1,210 @@
+use crate::weights::Weights;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// How raster cells are related to each other
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RasterContiguity {
+    /// Cells sharing an edge
+    Rook,
+    /// Cells sharing an edge or a corner
+    Queen,
+    /// Cells whose centers are closer than the cutoff, given in map units like the cell size
+    Distance {
+        cutoff_dist: f64,
+        use_distance_as_weight: bool,
+    },
+}
+
+/// Builds weights between the cells of a raster directly from its shape, without converting the
+/// cells to polygons. Cells flagged in the nodata mask are left out entirely, so position `i` in
+/// the resulting Weights is the i-th valid cell in row major order. Use `valid_cells` and
+/// `cell_position` to map between positions and (row, col).
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(try_from = "RasterWeightsFields")]
+pub struct RasterWeights {
+    rows: usize,
+    cols: usize,
+    cell_size: (f64, f64),
+    nodata_mask: Option<Vec<bool>>,
+    contiguity: RasterContiguity,
+}
+
+/// The unchecked fields of RasterWeights, so deserializing goes through `RasterWeights::new`
+#[derive(Deserialize)]
+struct RasterWeightsFields {
+    rows: usize,
+    cols: usize,
+    cell_size: (f64, f64),
+    nodata_mask: Option<Vec<bool>>,
+    contiguity: RasterContiguity,
+}
+
+impl TryFrom<RasterWeightsFields> for RasterWeights {
+    type Error = String;
+
+    fn try_from(fields: RasterWeightsFields) -> Result<Self, Self::Error> {
+        RasterWeights::new(
+            fields.rows,
+            fields.cols,
+            fields.cell_size,
+            fields.nodata_mask,
+            fields.contiguity,
+        )
+    }
+}
+
+impl RasterWeights {
+    /// Create a new raster weights builder
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The number of rows in the raster
+    /// * `cols` - The number of columns in the raster
+    /// * `cell_size` - The width and height of each cell
+    /// * `nodata_mask` - Optional row major mask, with true marking cells that have no data
+    /// * `contiguity` - How cells are related to each other
+    ///
+    pub fn new(
+        rows: usize,
+        cols: usize,
+        cell_size: (f64, f64),
+        nodata_mask: Option<Vec<bool>>,
+        contiguity: RasterContiguity,
+    ) -> Result<Self, String> {
+        let (width, height) = cell_size;
+        if !(width.is_finite() && width > 0.0 && height.is_finite() && height > 0.0) {
+            return Err(format!(
+                "Cell size should be positive but got {:?}",
+                cell_size
+            ));
+        }
+        if let RasterContiguity::Distance { cutoff_dist, .. } = contiguity {
+            if !(cutoff_dist.is_finite() && cutoff_dist > 0.0) {
+                return Err(format!(
+                    "Cutoff distance should be positive but got {:?}",
+                    cutoff_dist
+                ));
+            }
+        }
+        if let Some(mask) = &nodata_mask {
+            if mask.len() != rows * cols {
+                return Err(format!(
+                    "Nodata mask has {} entries but the raster has {} cells",
+                    mask.len(),
+                    rows * cols
+                ));
+            }
+        }
+        Ok(Self {
+            rows,
+            cols,
+            cell_size,
+            nodata_mask,
+            contiguity,
+        })
+    }
+
+    fn is_valid(&self, row: usize, col: usize) -> bool {
+        match &self.nodata_mask {
+            Some(mask) => !mask[row * self.cols + col],
+            None => true,
+        }
+    }
+
+    /// Returns the (row, col) of each valid cell, indexed by its position in the weights
+    pub fn valid_cells(&self) -> Vec<(usize, usize)> {
+        (0..self.rows)
+            .flat_map(|row| (0..self.cols).map(move |col| (row, col)))
+            .filter(|(row, col)| self.is_valid(*row, *col))
+            .collect()
+    }
+
+    /// Returns the position of a cell in the weights, or None if it's nodata or out of bounds
+    pub fn cell_position(&self, row: usize, col: usize) -> Option<usize> {
+        if row >= self.rows || col >= self.cols || !self.is_valid(row, col) {
+            return None;
+        }
+        match &self.nodata_mask {
+            Some(mask) => {
+                let cell = row * self.cols + col;
+                Some(mask[..cell].iter().filter(|nodata| !**nodata).count())
+            }
+            None => Some(row * self.cols + col),
+        }
+    }
+
+    /// The (row, col) offsets to search around each cell along with the distance to each
+    fn neighbor_offsets(&self) -> Vec<(isize, isize, f64)> {
+        let (width, height) = self.cell_size;
+        let offset_dist = |dr: isize, dc: isize| {
+            ((dr as f64 * height).powi(2) + (dc as f64 * width).powi(2)).sqrt()
+        };
+
+        let (max_row, max_col) = match self.contiguity {
+            RasterContiguity::Rook | RasterContiguity::Queen => (1, 1),
+            RasterContiguity::Distance { cutoff_dist, .. } => (
+                (cutoff_dist / height).ceil() as isize,
+                (cutoff_dist / width).ceil() as isize,
+            ),
+        };
+
+        let mut offsets = vec![];
+        for dr in -max_row..=max_row {
+            for dc in -max_col..=max_col {
+                if dr == 0 && dc == 0 {
+                    continue;
+                }
+                let dist = offset_dist(dr, dc);
+                let include = match self.contiguity {
+                    RasterContiguity::Rook => dr == 0 || dc == 0,
+                    RasterContiguity::Queen => true,
+                    RasterContiguity::Distance { cutoff_dist, .. } => dist < cutoff_dist,
+                };
+                if include {
+                    offsets.push((dr, dc, dist));
+                }
+            }
+        }
+        offsets
+    }
+
+    /// Compute the weights between the valid cells of the raster
+    pub fn compute_weights(&self) -> Weights {
+        let cells = self.valid_cells();
+        let mut positions: Vec<Option<usize>> = vec![None; self.rows * self.cols];
+        for (position, (row, col)) in cells.iter().enumerate() {
+            positions[row * self.cols + col] = Some(position);
+        }
+
+        let use_distance_as_weight = matches!(
+            self.contiguity,
+            RasterContiguity::Distance {
+                use_distance_as_weight: true,
+                ..
+            }
+        );
+        let offsets = self.neighbor_offsets();
+
+        let mut weights: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+        for (position, (row, col)) in cells.iter().enumerate() {
+            let entry = weights.entry(position).or_default();
+            for (dr, dc, dist) in offsets.iter() {
+                let row2 = *row as isize + dr;
+                let col2 = *col as isize + dc;
+                if row2 < 0 || col2 < 0 || row2 >= self.rows as isize || col2 >= self.cols as isize
+                {
+                    continue;
+                }
+                if let Some(position2) = positions[row2 as usize * self.cols + col2 as usize] {
+                    let weight = if use_distance_as_weight { *dist } else { 1.0 };
+                    entry.insert(position2, weight);
+                }
+            }
+        }
+
+        Weights::new(weights, cells.len())
+    }
+}
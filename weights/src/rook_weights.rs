@@ -1,14 +1,26 @@
+use crate::compact_weights::CompactWeights;
+use crate::utils::{compact_weights_from_hash, coords_to_tolerance, weights_from_hash};
 use crate::weights::Weights;
-use crate::{utils::coords_to_tolerance, WeightBuilder};
-use geo::algorithm::coords_iter::CoordsIter;
+use crate::{CoordsWeightBuilder, GeometryCoords, WeightBuilder};
 use geo::GeoFloat;
 use geo_types::Geometry;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use rayon::prelude::*;
+
 // T is the type being used to index our geometries
 // A is the type of the weight we are computing
 
+/// Builds contiguity weights where geometries are neighbors if they share at least one segment.
+/// Polygon rings and lines are compared segment by segment, so two lines traversing the same
+/// segment are neighbors while two only touching at an endpoint are not. Points and MultiPoints
+/// have no segments and so never have rook neighbors.
+/// Segments are matched on their end points after hashing them with the tolerance, as in
+/// QueensWeights, so a shared segment has to have the same two vertices in both geometries.
+/// Collinear segments that overlap without sharing both vertices, such as a road split at
+/// different points in two datasets, are not detected.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RookWeights<A>
 where
@@ -24,51 +36,107 @@ where
     pub fn new(tolerance: A) -> Self {
         Self { tolerance }
     }
+
+    /// Checks the tolerance can be used to hash coordinates. Multipliers below 1 are rejected as
+    /// they are most likely a distance such as 0.0001, which would merge nearby vertices.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.tolerance.is_finite() && self.tolerance >= A::one() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Tolerance should be a multiplier of at least 1, not a distance, but got {:?}",
+                self.tolerance
+            ))
+        }
+    }
 }
 
-impl<A> WeightBuilder<A> for RookWeights<A>
+impl<A> RookWeights<A>
 where
-    A: GeoFloat,
+    A: GeoFloat + Send + Sync,
 {
-    fn compute_weights<T>(&self, geoms: &T) -> Weights
-    where
-        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
-    {
-        let mut coord_hash: HashMap<[isize; 4], Vec<usize>> = HashMap::new();
+    /// Groups the geometries by the hashed end points of their segments
+    fn segment_hash<C: GeometryCoords + ?Sized>(
+        &self,
+        geoms: &C,
+    ) -> HashMap<[isize; 4], Vec<usize>> {
+        let tolerance = self.tolerance.to_f64().unwrap();
+
+        // Hashing the segments is the expensive part so it's done for each geometry in
+        // parallel, with the results merged in to a single lookup afterwards.
+        let hashed_geoms: Vec<Vec<[isize; 4]>> = cfg_into_iter!(0..geoms.no_geometries(), 64)
+            .map(|index| {
+                let mut hashed = vec![];
+                geoms.for_each_path(index, &mut |path| {
+                    let mut previous: Option<(isize, isize)> = None;
+                    for coords in path {
+                        let end = coords_to_tolerance(coords, tolerance);
+                        let start = match previous.replace(end) {
+                            Some(start) if start != end => start,
+                            _ => continue,
+                        };
+                        // Order the end points so a segment shared by two geometries is matched
+                        // regardless of the direction each geometry traverses it in.
+                        let (first, second) = if start < end {
+                            (start, end)
+                        } else {
+                            (end, start)
+                        };
+                        hashed.push([first.0, first.1, second.0, second.1]);
+                    }
+                });
+                hashed
+            })
+            .collect();
 
-        for (index, geom) in geoms.into_iter().enumerate() {
-            for (prev_coords, next_coords) in geom.coords_iter().zip(geom.coords_iter().skip(1)) {
-                let prev_coords_hashed = coords_to_tolerance(prev_coords, 1000.0);
-                let next_coords_hashed = coords_to_tolerance(next_coords, 1000.0);
-                let hashed_coords = [
-                    prev_coords_hashed.0,
-                    prev_coords_hashed.1,
-                    next_coords_hashed.0,
-                    next_coords_hashed.1,
-                ];
+        let mut coord_hash: HashMap<[isize; 4], Vec<usize>> = HashMap::new();
+        for (index, hashed_segments) in hashed_geoms.into_iter().enumerate() {
+            for hashed_coords in hashed_segments {
                 coord_hash
                     .entry(hashed_coords)
                     .and_modify(|v| v.push(index))
                     .or_insert_with(|| vec![index]);
             }
         }
+        coord_hash
+    }
+}
 
-        let mut weights: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
-        for (_coords, values) in coord_hash.iter() {
-            for index in values.iter() {
-                weights
-                    .entry(*index)
-                    .and_modify(|entry| {
-                        for index2 in values.iter() {
-                            if index != index2 {
-                                entry.insert(*index2, 1.0);
-                            }
-                        }
-                    })
-                    .or_insert_with(HashMap::new);
-            }
-        }
+impl<A> CoordsWeightBuilder for RookWeights<A>
+where
+    A: GeoFloat + Send + Sync,
+{
+    fn compute_weights_from_coords<C: GeometryCoords + ?Sized>(&self, geoms: &C) -> Weights {
+        let weights = weights_from_hash(&self.segment_hash(geoms));
+
+        Weights::new(weights, geoms.no_geometries())
+    }
 
-        Weights::new(weights, geoms.into_iter().count())
+    fn compute_compact_weights_from_coords<C: GeometryCoords + ?Sized>(
+        &self,
+        geoms: &C,
+    ) -> Result<CompactWeights, String> {
+        compact_weights_from_hash(&self.segment_hash(geoms), geoms.no_geometries())
+    }
+}
+
+impl<A> WeightBuilder<A> for RookWeights<A>
+where
+    A: GeoFloat + Send + Sync,
+{
+    fn compute_weights<T: ?Sized>(&self, geoms: &T) -> Weights
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let geoms: Vec<&Geometry<A>> = geoms.into_iter().collect();
+        self.compute_weights_from_coords(geoms.as_slice())
+    }
+
+    fn compute_compact_weights<T: ?Sized>(&self, geoms: &T) -> Result<CompactWeights, String>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        let geoms: Vec<&Geometry<A>> = geoms.into_iter().collect();
+        self.compute_compact_weights_from_coords(geoms.as_slice())
     }
 }
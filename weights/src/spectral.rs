@@ -0,0 +1,257 @@
+use crate::weights::{TransformType, Weights};
+use nalgebra::{DMatrix, DVector, SymmetricEigen};
+use nalgebra_sparse::csr::CsrMatrix;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Computes ln|I - ρW| for each ρ from the eigenvalues of W
+///
+/// # Arguments
+///
+/// * `eigenvalues` - the eigenvalues of the weights matrix
+/// * `rhos` - the values of ρ to compute the log determinant for
+///
+pub fn log_det_from_eigenvalues(eigenvalues: &[f64], rhos: &[f64]) -> Vec<f64> {
+    rhos.iter()
+        .map(|rho| eigenvalues.iter().map(|l| (1.0 - rho * l).ln()).sum())
+        .collect()
+}
+
+impl Weights {
+    /// Returns true if every weight w_ij has a matching w_ji
+    pub fn is_symmetric(&self) -> bool {
+        self.weights().iter().all(|(origin, dests)| {
+            dests.iter().all(|(dest, weight)| {
+                self.weights()
+                    .get(dest)
+                    .and_then(|d| d.get(origin))
+                    .map(|w| (w - weight).abs() <= 1e-12 * weight.abs().max(1.0))
+                    .unwrap_or(false)
+            })
+        })
+    }
+
+    /// Computes all the eigenvalues of the weights matrix, in ascending order, using a dense
+    /// symmetric eigen decomposition. This is O(n³) so is only suitable for a few thousand
+    /// observations, use `log_det_chebyshev` or `log_det_monte_carlo` for larger sets.
+    ///
+    /// The weights need to be symmetric. A row standardized matrix is not symmetric but is
+    /// similar to D^-½ W D^-½, where D holds the row sums, so shares its eigenvalues.
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` - the transform to apply to the weights before computing the eigenvalues
+    ///
+    pub fn eigenvalues(&self, transform: Option<TransformType>) -> Result<Vec<f64>, String> {
+        if !self.is_symmetric() {
+            return Err("Eigenvalues can only be computed for symmetric weights".into());
+        }
+
+        let n = self.no_elements();
+        let matrix = match transform {
+            Some(TransformType::Row) => {
+                let inv_sqrt_sums: Vec<f64> = (0..n)
+                    .map(|i| {
+                        let sum: f64 = self
+                            .weights()
+                            .get(&i)
+                            .map(|dests| dests.values().sum())
+                            .unwrap_or(0.0);
+                        if sum > 0.0 {
+                            1.0 / sum.sqrt()
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect();
+                let mut matrix = DMatrix::zeros(n, n);
+                for (origin, dests) in self.weights().iter() {
+                    for (dest, weight) in dests.iter() {
+                        matrix[(*origin, *dest)] =
+                            weight * inv_sqrt_sums[*origin] * inv_sqrt_sums[*dest];
+                    }
+                }
+                matrix
+            }
+            _ => DMatrix::from(&self.as_sparse_matrix(transform)),
+        };
+
+        let mut eigenvalues: Vec<f64> = SymmetricEigen::new(matrix)
+            .eigenvalues
+            .iter()
+            .copied()
+            .collect();
+        eigenvalues.sort_by(|a, b| a.total_cmp(b));
+        Ok(eigenvalues)
+    }
+
+    /// Computes ln|I - ρW| exactly for each ρ using the eigenvalues of the weights matrix. See
+    /// `eigenvalues` for the restrictions on which weights this works for.
+    ///
+    /// # Arguments
+    ///
+    /// * `rhos` - the values of ρ to compute the log determinant for
+    /// * `transform` - the transform to apply to the weights
+    ///
+    pub fn log_det(
+        &self,
+        rhos: &[f64],
+        transform: Option<TransformType>,
+    ) -> Result<Vec<f64>, String> {
+        let eigenvalues = self.eigenvalues(transform)?;
+        Ok(log_det_from_eigenvalues(&eigenvalues, rhos))
+    }
+
+    /// Approximates ln|I - ρW| for each ρ using the Monte Carlo estimator of Barry and Pace
+    /// (1999), expanding ln|I - ρW| = -Σ ρᵏ tr(Wᵏ) / k and estimating the traces with random
+    /// probe vectors. The first two traces are computed exactly. The series only converges when
+    /// the spectral radius of ρW is below 1, which holds for |ρ| < 1 with row standardized
+    /// weights.
+    ///
+    /// # Arguments
+    ///
+    /// * `rhos` - the values of ρ to compute the log determinant for
+    /// * `transform` - the transform to apply to the weights
+    /// * `order` - the number of terms of the series to use
+    /// * `iterations` - the number of random probe vectors to average over
+    /// * `seed` - seed for the random probe vectors
+    ///
+    pub fn log_det_monte_carlo(
+        &self,
+        rhos: &[f64],
+        transform: Option<TransformType>,
+        order: usize,
+        iterations: usize,
+        seed: u64,
+    ) -> Vec<f64> {
+        let w_matrix = self.as_sparse_matrix(transform);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        // traces[k - 1] holds the estimate of tr(Wᵏ)
+        let mut traces = vec![0.0; order];
+        for _ in 0..iterations {
+            let probe = rademacher_vector(&mut rng, w_matrix.nrows());
+            let mut power = probe.clone();
+            for trace in traces.iter_mut() {
+                power = &w_matrix * &power;
+                *trace += probe.dot(&power) / iterations as f64;
+            }
+        }
+        if order > 0 {
+            traces[0] = trace(&w_matrix);
+        }
+        if order > 1 {
+            traces[1] = trace_of_square(&w_matrix);
+        }
+
+        rhos.iter()
+            .map(|rho| {
+                -traces
+                    .iter()
+                    .enumerate()
+                    .map(|(k, t)| rho.powi(k as i32 + 1) * t / (k as f64 + 1.0))
+                    .sum::<f64>()
+            })
+            .collect()
+    }
+
+    /// Approximates ln|I - ρW| for each ρ with a Chebyshev polynomial expansion of ln(1 - ρx)
+    /// (Pace and LeSage 2004), evaluating the traces of the Chebyshev polynomials of W with
+    /// random probe vectors beyond the second order. The expansion is over [-1, 1] so the
+    /// eigenvalues of W need to lie in that range, as they do for row standardized weights, and
+    /// |ρ| needs to be below 1.
+    ///
+    /// # Arguments
+    ///
+    /// * `rhos` - the values of ρ to compute the log determinant for
+    /// * `transform` - the transform to apply to the weights
+    /// * `order` - the degree of the Chebyshev polynomial
+    /// * `iterations` - the number of random probe vectors to average over
+    /// * `seed` - seed for the random probe vectors
+    ///
+    pub fn log_det_chebyshev(
+        &self,
+        rhos: &[f64],
+        transform: Option<TransformType>,
+        order: usize,
+        iterations: usize,
+        seed: u64,
+    ) -> Vec<f64> {
+        let w_matrix = self.as_sparse_matrix(transform);
+        let n = w_matrix.nrows();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        // traces[j] holds the estimate of tr(Tⱼ(W))
+        let mut traces = vec![0.0; order + 1];
+        for _ in 0..iterations {
+            let probe = rademacher_vector(&mut rng, n);
+            let mut prev = probe.clone();
+            let mut current = &w_matrix * &probe;
+            for trace in traces.iter_mut().skip(2) {
+                let next = (&w_matrix * &current) * 2.0 - &prev;
+                *trace += probe.dot(&next) / iterations as f64;
+                prev = current;
+                current = next;
+            }
+        }
+        traces[0] = n as f64;
+        if order > 0 {
+            traces[1] = trace(&w_matrix);
+        }
+        if order > 1 {
+            traces[2] = 2.0 * trace_of_square(&w_matrix) - n as f64;
+        }
+
+        // Chebyshev nodes and the polynomials evaluated at them
+        let nodes: Vec<f64> = (0..=order)
+            .map(|k| (std::f64::consts::PI * (k as f64 + 0.5) / (order as f64 + 1.0)).cos())
+            .collect();
+
+        rhos.iter()
+            .map(|rho| {
+                let coefficients: Vec<f64> = (0..=order)
+                    .map(|j| {
+                        nodes
+                            .iter()
+                            .map(|x| (1.0 - rho * x).ln() * (j as f64 * x.acos()).cos())
+                            .sum::<f64>()
+                            * 2.0
+                            / (order as f64 + 1.0)
+                    })
+                    .collect();
+                coefficients
+                    .iter()
+                    .zip(traces.iter())
+                    .map(|(c, t)| c * t)
+                    .sum::<f64>()
+                    - coefficients[0] * n as f64 / 2.0
+            })
+            .collect()
+    }
+}
+
+fn rademacher_vector(rng: &mut StdRng, n: usize) -> DVector<f64> {
+    DVector::from_fn(n, |_, _| if rng.gen::<bool>() { 1.0 } else { -1.0 })
+}
+
+/// tr(W)
+fn trace(w_matrix: &CsrMatrix<f64>) -> f64 {
+    w_matrix
+        .triplet_iter()
+        .filter(|(i, j, _)| i == j)
+        .map(|(_, _, v)| v)
+        .sum()
+}
+
+/// tr(W²) = Σᵢⱼ wᵢⱼ wⱼᵢ
+fn trace_of_square(w_matrix: &CsrMatrix<f64>) -> f64 {
+    w_matrix
+        .triplet_iter()
+        .map(|(i, j, v)| {
+            w_matrix
+                .get_entry(j, i)
+                .map(|e| e.into_value() * v)
+                .unwrap_or(0.0)
+        })
+        .sum()
+}
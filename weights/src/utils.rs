@@ -1,4 +1,10 @@
-use geo::{Coordinate, GeoFloat};
+use crate::compact_weights::{check_fits_u32, CompactWeights};
+use geo::coords_iter::CoordsIter;
+use geo::euclidean_distance::EuclideanDistance;
+use geo::lines_iter::LinesIter;
+use geo::{Centroid, Coordinate, GeoFloat, Intersects, Line, Point};
+use geo_types::Geometry;
+use std::collections::HashMap;
 
 pub fn coords_to_tolerance<T>(coords: Coordinate<T>, tolerance: f64) -> (isize, isize)
 where
@@ -10,6 +16,143 @@ where
     )
 }
 
+/// Returns the segments making up a geometry. Points and MultiPoints have no segments, nested
+/// GeometryCollections are flattened.
+pub fn geometry_segments<A>(geom: &Geometry<A>) -> Vec<Line<A>>
+where
+    A: GeoFloat,
+{
+    match geom {
+        Geometry::Point(_) | Geometry::MultiPoint(_) => vec![],
+        Geometry::Line(l) => vec![*l],
+        Geometry::LineString(ls) => ls.lines_iter().collect(),
+        Geometry::MultiLineString(mls) => mls.lines_iter().collect(),
+        Geometry::Polygon(p) => p.lines_iter().collect(),
+        Geometry::MultiPolygon(mp) => mp.lines_iter().collect(),
+        Geometry::Rect(r) => r.lines_iter().collect(),
+        Geometry::Triangle(t) => t.lines_iter().collect(),
+        Geometry::GeometryCollection(gc) => gc.iter().flat_map(geometry_segments).collect(),
+    }
+}
+
+/// Returns the point used to represent a geometry when computing distances. This is the point
+/// itself for Points and the centroid for everything else. Returns None for empty geometries.
+pub fn representative_point<A>(geom: &Geometry<A>) -> Option<Point<A>>
+where
+    A: GeoFloat,
+{
+    match geom {
+        Geometry::Point(p) => Some(*p),
+        _ => geom.centroid(),
+    }
+}
+
+/// Returns the points of a geometry that are not part of any segment, the Points and MultiPoints
+/// including those nested in GeometryCollections
+fn geometry_points<A>(geom: &Geometry<A>) -> Vec<Point<A>>
+where
+    A: GeoFloat,
+{
+    match geom {
+        Geometry::Point(p) => vec![*p],
+        Geometry::MultiPoint(mp) => mp.0.clone(),
+        Geometry::GeometryCollection(gc) => gc.iter().flat_map(geometry_points).collect(),
+        _ => vec![],
+    }
+}
+
+/// Returns the euclidean distance between two geometries, 0 if they intersect. Otherwise the
+/// closest pair of points lies on a vertex of one of the geometries, so this is the smallest
+/// distance from a vertex of either geometry to a point or segment of the other. Returns None
+/// if either geometry is empty.
+pub fn geometry_distance<A>(a: &Geometry<A>, b: &Geometry<A>) -> Option<A>
+where
+    A: GeoFloat,
+{
+    if a.coords_count() == 0 || b.coords_count() == 0 {
+        return None;
+    }
+    if a.intersects(b) {
+        return Some(A::zero());
+    }
+
+    let (points_a, segments_a) = (geometry_points(a), geometry_segments(a));
+    let (points_b, segments_b) = (geometry_points(b), geometry_segments(b));
+    let vertices = |points: &[Point<A>], segments: &[Line<A>]| -> Vec<Point<A>> {
+        points
+            .iter()
+            .copied()
+            .chain(
+                segments
+                    .iter()
+                    .flat_map(|s| [s.start_point(), s.end_point()]),
+            )
+            .collect()
+    };
+
+    let to_other = |vertices: Vec<Point<A>>, points: &[Point<A>], segments: &[Line<A>]| {
+        vertices
+            .iter()
+            .flat_map(|v| {
+                points
+                    .iter()
+                    .map(move |p| v.euclidean_distance(p))
+                    .chain(segments.iter().map(move |s| v.euclidean_distance(s)))
+            })
+            .fold(None, |min: Option<A>, d| Some(min.map_or(d, |m| m.min(d))))
+    };
+
+    let a_to_b = to_other(vertices(&points_a, &segments_a), &points_b, &segments_b);
+    let b_to_a = to_other(vertices(&points_b, &segments_b), &points_a, &segments_a);
+    match (a_to_b, b_to_a) {
+        (Some(x), Some(y)) => Some(x.min(y)),
+        (x, y) => x.or(y),
+    }
+}
+
+/// Converts a lookup of hashed coordinates (or segments) to the geometries that share them into
+/// a weights lookup where every geometry sharing a key is a neighbor of every other.
+pub fn weights_from_hash<K>(
+    coord_hash: &HashMap<K, Vec<usize>>,
+) -> HashMap<usize, HashMap<usize, f64>> {
+    let mut weights: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+    for values in coord_hash.values() {
+        for index in values.iter() {
+            let entry = weights.entry(*index).or_default();
+            for index2 in values.iter() {
+                if index != index2 {
+                    entry.insert(*index2, 1.0);
+                }
+            }
+        }
+    }
+    weights
+}
+
+/// Same as `weights_from_hash` but writes the links straight into compact weights
+pub fn compact_weights_from_hash<K>(
+    coord_hash: &HashMap<K, Vec<usize>>,
+    no_elements: usize,
+) -> Result<CompactWeights, String> {
+    check_fits_u32("elements", no_elements)?;
+    let mut links: Vec<(u32, u32)> = vec![];
+    for values in coord_hash.values() {
+        for index in values.iter() {
+            for index2 in values.iter() {
+                if index != index2 {
+                    links.push((*index as u32, *index2 as u32));
+                }
+            }
+        }
+    }
+    links.sort_unstable();
+    links.dedup();
+    CompactWeights::from_sorted_links(
+        links.into_iter().map(|(origin, dest)| (origin, dest, 1.0)),
+        no_elements,
+    )
+}
+
 #[cfg(test)]
 mod test {
     use geo::Coordinate;
@@ -40,4 +183,38 @@ mod test {
             "Should get correct transformed object for hash"
         );
     }
+
+    #[test]
+    fn geometry_segments_should_flatten_collections_and_skip_points() {
+        use super::geometry_segments;
+        use geo::{line_string, Geometry, GeometryCollection, Point};
+
+        let geom: Geometry<f64> = Geometry::GeometryCollection(GeometryCollection(vec![
+            Point::new(0.0, 0.0).into(),
+            line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0), (x: 1.0, y: 1.0)].into(),
+        ]));
+        assert_eq!(geometry_segments(&geom).len(), 2);
+    }
+
+    #[test]
+    fn geometry_distance_should_use_closest_parts() {
+        use super::geometry_distance;
+        use geo::{line_string, polygon, Geometry, MultiPoint, Point};
+
+        let square: Geometry<f64> =
+            polygon![(x: 0.0, y: 0.0), (x: 2.0, y: 0.0), (x: 2.0, y: 2.0), (x: 0.0, y: 2.0)].into();
+        let line: Geometry<f64> = line_string![(x: 5.0, y: -1.0), (x: 5.0, y: 3.0)].into();
+        let points: Geometry<f64> =
+            MultiPoint(vec![Point::new(10.0, 10.0), Point::new(1.0, 4.0)]).into();
+        let inside: Geometry<f64> = Point::new(1.0, 1.0).into();
+
+        assert_eq!(geometry_distance(&square, &line), Some(3.0));
+        assert_eq!(geometry_distance(&line, &square), Some(3.0));
+        assert_eq!(geometry_distance(&square, &points), Some(2.0));
+        assert_eq!(geometry_distance(&square, &inside), Some(0.0));
+        assert_eq!(
+            geometry_distance(&square, &Geometry::MultiPoint(MultiPoint(vec![]))),
+            None
+        );
+    }
 }
@@ -0,0 +1,327 @@
+use geo::line_intersection::LineIntersection;
+use geo::orient::{Direction, Orient};
+use geo::sweep::{Cross, Intersections, LineOrPoint};
+use geo::{CoordsIter, GeoFloat};
+use geo_types::{Geometry, Line, LineString, MultiPolygon, Polygon};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+/// A problem found with a single geometry
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum GeometryIssue {
+    /// The geometry has no coordinates, e.g. an empty MultiPolygon
+    Empty,
+    /// The geometry contains a NaN or infinite coordinate
+    NonFiniteCoordinate,
+    /// A polygon ring with fewer than 4 coordinates or a LineString with fewer than 2
+    TooFewCoordinates,
+    /// A polygon ring which crosses or overlaps itself
+    SelfIntersection,
+    /// A polygon ring whose last coordinate is not its first. Polygon::new always closes its
+    /// rings, but deserialized polygons can still have unclosed ones
+    UnclosedRing,
+    /// The geometry is identical to an earlier geometry, whose index is given
+    Duplicate(usize),
+}
+
+impl GeometryIssue {
+    /// Duplicates are reported but still produce well defined weights, everything else is
+    /// treated as an error.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, GeometryIssue::Duplicate(_))
+    }
+}
+
+/// An issue found along with the index of the geometry it was found in
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub index: usize,
+    pub issue: GeometryIssue,
+}
+
+/// The result of validating a set of geometries
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns true if no errors were found. Duplicates do not count as errors.
+    pub fn is_valid(&self) -> bool {
+        !self.issues.iter().any(|i| i.issue.is_error())
+    }
+
+    /// Returns the indices of geometries with at least one error, in ascending order
+    pub fn invalid_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self
+            .issues
+            .iter()
+            .filter(|i| i.issue.is_error())
+            .map(|i| i.index)
+            .collect();
+        indices.dedup();
+        indices
+    }
+
+    /// Returns the indices of empty geometries
+    pub fn empty_indices(&self) -> Vec<usize> {
+        self.indices_matching(|issue| *issue == GeometryIssue::Empty)
+    }
+
+    /// Returns the indices of geometries which duplicate an earlier geometry
+    pub fn duplicate_indices(&self) -> Vec<usize> {
+        self.indices_matching(|issue| matches!(issue, GeometryIssue::Duplicate(_)))
+    }
+
+    fn indices_matching<F>(&self, predicate: F) -> Vec<usize>
+    where
+        F: Fn(&GeometryIssue) -> bool,
+    {
+        self.issues
+            .iter()
+            .filter(|i| predicate(&i.issue))
+            .map(|i| i.index)
+            .collect()
+    }
+}
+
+/// Options controlling which repairs are applied by `repair_geometries`
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct RepairOptions {
+    /// Remove consecutive repeated coordinates
+    pub remove_duplicate_vertices: bool,
+    /// Orient polygons with counter clockwise exteriors and clockwise interiors
+    pub normalize_orientation: bool,
+    /// Close polygon rings whose last coordinate is not their first by repeating the first
+    pub close_rings: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            remove_duplicate_vertices: true,
+            normalize_orientation: true,
+            close_rings: true,
+        }
+    }
+}
+
+/// Checks each geometry for problems that would cause the weight builders to panic or produce
+/// the wrong neighbors, reporting them by the index of the geometry.
+///
+/// # Arguments
+///
+/// * `geoms` - the geometries to validate
+///
+pub fn validate_geometries<A, T: ?Sized>(geoms: &T) -> ValidationReport
+where
+    A: GeoFloat,
+    for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+{
+    let mut issues: Vec<ValidationIssue> = vec![];
+    let mut seen: HashMap<Vec<(u64, u64)>, usize> = HashMap::new();
+
+    for (index, geom) in geoms.into_iter().enumerate() {
+        let mut geom_issues = geometry_issues(geom);
+
+        // Compare the exact bit pattern of the coordinates to find duplicates
+        let key: Vec<(u64, u64)> = geom
+            .coords_iter()
+            .map(|c| {
+                (
+                    c.x.to_f64().unwrap().to_bits(),
+                    c.y.to_f64().unwrap().to_bits(),
+                )
+            })
+            .collect();
+        if !key.is_empty() {
+            match seen.get(&key) {
+                Some(first) => geom_issues.push(GeometryIssue::Duplicate(*first)),
+                None => {
+                    seen.insert(key, index);
+                }
+            }
+        }
+
+        issues.extend(
+            geom_issues
+                .into_iter()
+                .map(|issue| ValidationIssue { index, issue }),
+        );
+    }
+
+    ValidationReport { issues }
+}
+
+fn geometry_issues<A: GeoFloat>(geom: &Geometry<A>) -> Vec<GeometryIssue> {
+    let mut issues = vec![];
+
+    if geom.coords_count() == 0 {
+        issues.push(GeometryIssue::Empty);
+        return issues;
+    }
+
+    if geom
+        .coords_iter()
+        .any(|c| !c.x.is_finite() || !c.y.is_finite())
+    {
+        issues.push(GeometryIssue::NonFiniteCoordinate);
+    }
+
+    match geom {
+        Geometry::LineString(ls) => {
+            if ls.0.len() < 2 {
+                issues.push(GeometryIssue::TooFewCoordinates);
+            }
+        }
+        Geometry::MultiLineString(mls) => {
+            if mls.iter().any(|ls| ls.0.len() < 2) {
+                issues.push(GeometryIssue::TooFewCoordinates);
+            }
+        }
+        Geometry::Polygon(p) => issues.extend(polygon_issues(p)),
+        Geometry::MultiPolygon(mp) => {
+            for p in mp.iter() {
+                issues.extend(polygon_issues(p));
+            }
+        }
+        Geometry::GeometryCollection(gc) => {
+            for g in gc.iter() {
+                issues.extend(geometry_issues(g));
+            }
+        }
+        _ => {}
+    }
+
+    // Nested geometries can report the same issue more than once
+    let mut unique = vec![];
+    for issue in issues {
+        if !unique.contains(&issue) {
+            unique.push(issue);
+        }
+    }
+    unique
+}
+
+fn polygon_issues<A: GeoFloat>(polygon: &Polygon<A>) -> Vec<GeometryIssue> {
+    let mut issues = vec![];
+    for ring in std::iter::once(polygon.exterior()).chain(polygon.interiors().iter()) {
+        if !ring.is_closed() {
+            issues.push(GeometryIssue::UnclosedRing);
+        }
+        if ring.0.len() < 4 {
+            issues.push(GeometryIssue::TooFewCoordinates);
+        } else if ring_self_intersects(ring) {
+            issues.push(GeometryIssue::SelfIntersection);
+        }
+    }
+    issues
+}
+
+/// A segment of a ring along with its position in the ring, for the sweep line
+#[derive(Debug, Clone)]
+struct RingSegment<A: GeoFloat> {
+    position: usize,
+    line: Line<A>,
+}
+
+impl<A: GeoFloat> Cross for RingSegment<A> {
+    type Scalar = A;
+
+    fn line(&self) -> LineOrPoint<A> {
+        self.line.into()
+    }
+}
+
+/// Finds the intersecting segments of a ring with a sweep line, which takes O((n + k) log n)
+/// for n segments and k intersections rather than comparing every pair. Neighboring segments
+/// are allowed to share their common vertex but nothing else.
+fn ring_self_intersects<A: GeoFloat>(ring: &LineString<A>) -> bool {
+    let segments: Vec<RingSegment<A>> = ring
+        .lines()
+        .filter(|l| l.start != l.end)
+        .enumerate()
+        .map(|(position, line)| RingSegment { position, line })
+        .collect();
+    let n = segments.len();
+
+    Intersections::from_iter(segments).any(|(a, b, intersection)| {
+        let (i, j) = (a.position.min(b.position), a.position.max(b.position));
+        let adjacent = j == i + 1 || (i == 0 && j == n - 1);
+        !(adjacent && matches!(intersection, LineIntersection::SinglePoint { .. }))
+    })
+}
+
+/// Returns repaired copies of the geometries, with the repairs controlled by the options.
+/// Geometries which can not be repaired, such as empty or self intersecting ones, are returned
+/// as they are so that `validate_geometries` still reports them.
+///
+/// # Arguments
+///
+/// * `geoms` - the geometries to repair
+/// * `options` - which repairs to apply
+///
+pub fn repair_geometries<A: GeoFloat>(
+    geoms: &[Geometry<A>],
+    options: &RepairOptions,
+) -> Vec<Geometry<A>> {
+    geoms.iter().map(|g| repair_geometry(g, options)).collect()
+}
+
+fn repair_geometry<A: GeoFloat>(geom: &Geometry<A>, options: &RepairOptions) -> Geometry<A> {
+    match geom {
+        Geometry::LineString(ls) => Geometry::LineString(repair_line_string(ls, options)),
+        Geometry::MultiLineString(mls) => {
+            let mut mls = mls.clone();
+            for ls in mls.0.iter_mut() {
+                *ls = repair_line_string(ls, options);
+            }
+            Geometry::MultiLineString(mls)
+        }
+        Geometry::Polygon(p) => Geometry::Polygon(repair_polygon(p, options)),
+        Geometry::MultiPolygon(mp) => Geometry::MultiPolygon(MultiPolygon(
+            mp.iter().map(|p| repair_polygon(p, options)).collect(),
+        )),
+        Geometry::GeometryCollection(gc) => {
+            let mut gc = gc.clone();
+            for g in gc.0.iter_mut() {
+                *g = repair_geometry(g, options);
+            }
+            Geometry::GeometryCollection(gc)
+        }
+        _ => geom.clone(),
+    }
+}
+
+fn repair_line_string<A: GeoFloat>(ls: &LineString<A>, options: &RepairOptions) -> LineString<A> {
+    let mut ls = ls.clone();
+    if options.remove_duplicate_vertices {
+        ls.0.dedup();
+    }
+    ls
+}
+
+fn repair_polygon<A: GeoFloat>(polygon: &Polygon<A>, options: &RepairOptions) -> Polygon<A> {
+    // Rebuilding the polygon with Polygon::new closes its rings, so unclosed ones are left
+    // untouched unless they should be closed
+    let is_closed = std::iter::once(polygon.exterior())
+        .chain(polygon.interiors().iter())
+        .all(|ring| ring.is_closed());
+    if !is_closed && !options.close_rings {
+        return polygon.clone();
+    }
+
+    let exterior = repair_line_string(polygon.exterior(), options);
+    let interiors: Vec<LineString<A>> = polygon
+        .interiors()
+        .iter()
+        .map(|ring| repair_line_string(ring, options))
+        .collect();
+    let polygon = Polygon::new(exterior, interiors);
+    if options.normalize_orientation {
+        polygon.orient(Direction::Default)
+    } else {
+        polygon
+    }
+}
@@ -1,24 +1,98 @@
-use geo::{Centroid, GeoFloat, Line};
+use crate::compact_weights::CompactWeights;
+use crate::validation::{repair_geometries, validate_geometries, RepairOptions, ValidationReport};
+use geo::GeoFloat;
 use geo_types::Geometry;
 use geojson::{Feature, FeatureCollection};
+use nalgebra::DMatrix;
 use nalgebra_sparse::{coo::CooMatrix, csr::CsrMatrix};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::iter::IntoIterator;
 
+/// Transforms which can be applied to the weights when converting them to a matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TransformType {
+    /// Each row is divided by its sum so the weights of each observation sum to 1
     Row,
+    /// Every non zero weight is set to 1
     Binary,
+    /// Every weight is divided by the sum of all weights so the whole matrix sums to 1
     DoublyStandardized,
 }
 
+/// How to resolve a tie between the most common neighboring labels in `Weights::lag_categorical`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Pick the smallest of the tied labels
+    Lowest,
+    /// Pick the observations own label if it's one of the tied labels, otherwise the smallest
+    PreferOwn,
+    /// Return None for the observation
+    NoLabel,
+}
+
+/// Implemented by each representation of a weights matrix so that the statistics can work on
+/// whichever one is in use, only materialising the f64 sparse matrix when it's needed.
+pub trait ToSparseMatrix {
+    /// The number of elements in the original geometry set
+    fn no_elements(&self) -> usize;
+
+    /// Returns the weights as a sparse f64 matrix with the given transform applied
+    fn as_sparse_matrix(&self, transform: Option<TransformType>) -> CsrMatrix<f64>;
+}
+
 pub trait WeightBuilder<A>
 where
     A: GeoFloat,
 {
-    fn compute_weights<T>(&self, geoms: &T) -> Weights
+    fn compute_weights<T: ?Sized>(&self, geoms: &T) -> Weights
     where
         for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>;
+
+    /// Computes the weights straight into the compact form, which keeps the peak memory down
+    /// for large datasets. Builders without a direct path build `Weights` and convert them.
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - the geometries to compute the weights for
+    ///
+    fn compute_compact_weights<T: ?Sized>(&self, geoms: &T) -> Result<CompactWeights, String>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        CompactWeights::from_weights(&self.compute_weights(geoms))
+    }
+
+    /// Validates the geometries, optionally repairing them first, and only computes the weights
+    /// if they are all valid. Otherwise the validation report is returned as the error.
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - the geometries to compute the weights for
+    /// * `repair` - the repairs to apply before validating, if any
+    ///
+    fn try_compute_weights(
+        &self,
+        geoms: &[Geometry<A>],
+        repair: Option<&RepairOptions>,
+    ) -> Result<Weights, ValidationReport> {
+        // Only repairing needs copies of the geometries, otherwise they are borrowed as is
+        let repaired: Vec<Geometry<A>>;
+        let geoms: &[Geometry<A>] = match repair {
+            Some(options) => {
+                repaired = repair_geometries(geoms, options);
+                &repaired
+            }
+            None => geoms,
+        };
+
+        let report = validate_geometries(geoms);
+        if report.is_valid() {
+            Ok(self.compute_weights(geoms))
+        } else {
+            Err(report)
+        }
+    }
 }
 
 /// Structure holding and providing methods to access and query a weights matrix. These are either
@@ -29,6 +103,16 @@ pub struct Weights {
     no_elements: usize,
 }
 
+impl ToSparseMatrix for Weights {
+    fn no_elements(&self) -> usize {
+        self.no_elements
+    }
+
+    fn as_sparse_matrix(&self, transform: Option<TransformType>) -> CsrMatrix<f64> {
+        Weights::as_sparse_matrix(self, transform)
+    }
+}
+
 impl fmt::Display for Weights {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "({:#?})", self.weights())
@@ -129,24 +213,146 @@ impl Weights {
     /// # Arguments
     ///
     /// * `transfrom` - what transform, if any to apply to the weights matrix as we  transform.
-    /// Only TransformType::Row for row normalized is currently implemented.
     ///
     pub fn as_sparse_matrix(&self, transform: Option<TransformType>) -> CsrMatrix<f64> {
         let mut coo_matrix = CooMatrix::new(self.no_elements, self.no_elements);
 
+        let total: f64 = match &transform {
+            Some(TransformType::DoublyStandardized) => {
+                self.weights.values().flat_map(|vals| vals.values()).sum()
+            }
+            _ => 1.0,
+        };
+
         for (key, vals) in self.weights.iter() {
             let norm: f64 = match &transform {
                 Some(TransformType::Row) => vals.values().sum(),
+                Some(TransformType::DoublyStandardized) => total,
                 _ => 1.0,
             };
             for (key2, weight) in vals.iter() {
-                coo_matrix.push(*key, *key2, *weight / norm);
+                let weight = match &transform {
+                    Some(TransformType::Binary) => 1.0,
+                    _ => *weight / norm,
+                };
+                coo_matrix.push(*key, *key2, weight);
             }
         }
 
         CsrMatrix::from(&coo_matrix)
     }
 
+    /// Returns the spatial lag W·x of the values, the weighted sum of each observations
+    /// neighboring values. Observations with no neighbors have a lag of 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - one value for each observation
+    /// * `transform` - what transform, if any to apply to the weights before computing the lag.
+    ///   With TransformType::Row this is the weighted average of the neighboring values.
+    ///
+    pub fn lag(
+        &self,
+        values: &[f64],
+        transform: Option<TransformType>,
+    ) -> Result<Vec<f64>, String> {
+        let mut lags = self.lag_columns(&[values.to_vec()], transform)?;
+        Ok(lags.remove(0))
+    }
+
+    /// Returns the spatial lag of several columns of values at once, building the weights matrix
+    /// only once.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - each column has one value for each observation
+    /// * `transform` - what transform, if any to apply to the weights before computing the lag
+    ///
+    pub fn lag_columns(
+        &self,
+        columns: &[Vec<f64>],
+        transform: Option<TransformType>,
+    ) -> Result<Vec<Vec<f64>>, String> {
+        if let Some(column) = columns.iter().find(|c| c.len() != self.no_elements) {
+            return Err(format!(
+                "Expected {} values but got {}",
+                self.no_elements,
+                column.len()
+            ));
+        }
+        let w_matrix = self.as_sparse_matrix(transform);
+        let values = DMatrix::from_fn(self.no_elements, columns.len(), |row, col| {
+            columns[col][row]
+        });
+        let lags: DMatrix<f64> = &w_matrix * &values;
+
+        Ok(lags
+            .column_iter()
+            .map(|column| column.iter().copied().collect())
+            .collect())
+    }
+
+    /// Returns the categorical spatial lag of the labels, which is the label carrying the most
+    /// weight among each observations neighbors. Observations with no neighbors get None.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - one label for each observation
+    /// * `tie_break` - how to pick a label when several carry the same weight
+    ///
+    pub fn lag_categorical<L>(
+        &self,
+        labels: &[L],
+        tie_break: TieBreak,
+    ) -> Result<Vec<Option<L>>, String>
+    where
+        L: Ord + Clone,
+    {
+        if labels.len() != self.no_elements {
+            return Err(format!(
+                "Expected {} labels but got {}",
+                self.no_elements,
+                labels.len()
+            ));
+        }
+
+        let mut result = Vec::with_capacity(self.no_elements);
+        for (index, own_label) in labels.iter().enumerate() {
+            // A BTreeMap keeps the labels sorted so the lowest tied label is found first
+            let mut label_weights: BTreeMap<&L, f64> = BTreeMap::new();
+            if let Some(neighbors) = self.weights.get(&index) {
+                for (neighbor, weight) in neighbors.iter() {
+                    let label = labels.get(*neighbor).ok_or_else(|| {
+                        format!("Neighbor {} is out of range of the labels", neighbor)
+                    })?;
+                    *label_weights.entry(label).or_insert(0.0) += weight;
+                }
+            }
+
+            let max_weight = label_weights.values().cloned().fold(f64::NAN, f64::max);
+            let tied: Vec<&L> = label_weights
+                .iter()
+                .filter(|(_, w)| **w == max_weight)
+                .map(|(label, _)| *label)
+                .collect();
+
+            let label = match (tied.len(), tie_break) {
+                (0, _) => None,
+                (1, _) | (_, TieBreak::Lowest) => Some(tied[0].clone()),
+                (_, TieBreak::PreferOwn) => {
+                    if tied.contains(&own_label) {
+                        Some(own_label.clone())
+                    } else {
+                        Some(tied[0].clone())
+                    }
+                }
+                (_, TieBreak::NoLabel) => None,
+            };
+            result.push(label);
+        }
+        Ok(result)
+    }
+
     /// Returns the weights matrix in a list format
     ///
     /// Output format is a tuple of origin ids, dest ids, weight values
@@ -169,7 +375,7 @@ impl Weights {
     /// Returns the weights matrix in a list format with geometries
     ///
     /// Output format is a tuple of origin ids, dest ids, weight values, geometry linking origin
-    /// and destination
+    /// and destination, sorted by origin then dest
     ///
     /// # Arguments
     ///
@@ -182,93 +388,57 @@ impl Weights {
         let mut dest_list: Vec<usize> = vec![];
         let mut weight_list: Vec<f64> = vec![];
         let mut link_geoms: Vec<Geometry<A>> = vec![];
-        let no_geoms = geoms.len();
 
-        for (origin, dests) in self.weights.iter() {
-            for (dest, weight) in dests.iter() {
-                origin_list.push(*origin);
-                dest_list.push(*dest);
-                weight_list.push(*weight);
-                let origin_centroid = geoms
-                    .get(*origin)
-                    .ok_or_else(|| format!("Failed to get origin {} {}", origin, no_geoms))?
-                    .centroid()
-                    .unwrap();
-                let dest_centroid = geoms
-                    .get(*dest)
-                    .ok_or_else(|| format!("Failed to get origin {} {}", dest, no_geoms))?
-                    .centroid()
-                    .unwrap();
-                let line: geo::Geometry<A> =
-                    geo::Geometry::Line(Line::new(origin_centroid, dest_centroid));
-                link_geoms.push(line);
-            }
+        for (origin, dest, weight) in self.sorted_links() {
+            link_geoms.push(Geometry::Line(self.link_line(geoms, origin, dest)?));
+            origin_list.push(origin);
+            dest_list.push(dest);
+            weight_list.push(weight);
         }
         Ok((origin_list, dest_list, weight_list, link_geoms))
     }
 
     /// Returns the weights matrix in a GeoJson format with lines between the origin and
-    /// destinations
+    /// destinations. The `origin` and `dest` properties are strings, see
+    /// `links_feature_collection` for numeric ids and the weight. Panics if a link references a
+    /// missing or empty geometry, use `try_links_geojson` to get an error instead.
     ///
     /// # Arguments
     ///
     /// * `geoms` - the list of geometries originally used to generate the weights matrix.
     pub fn links_geojson<A: GeoFloat>(&self, geoms: &[Geometry<A>]) -> FeatureCollection {
+        self.try_links_geojson(geoms).unwrap()
+    }
+
+    /// Returns the same FeatureCollection as `links_geojson`, sorted by origin then dest, or an
+    /// error if a link references a missing or empty geometry
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - the list of geometries originally used to generate the weights matrix.
+    pub fn try_links_geojson<A: GeoFloat>(
+        &self,
+        geoms: &[Geometry<A>],
+    ) -> Result<FeatureCollection, String> {
         let mut features: Vec<Feature> = vec![];
 
-        for (origin, dests) in self.weights.iter() {
-            for (dest, _weight) in dests.iter() {
-                let origin_centroid = geoms.get(*origin).unwrap().centroid().unwrap();
-                let dest_centroid = geoms.get(*dest).unwrap().centroid().unwrap();
-                let line: geojson::Geometry =
-                    geojson::Value::from(&Line::new(origin_centroid, dest_centroid)).into();
-
-                let mut feature = Feature {
-                    geometry: Some(line),
-                    ..Default::default()
-                };
-                feature.set_property("origin", format!("{}", origin));
-                feature.set_property("dest", format!("{}", dest));
-                features.push(feature);
-            }
+        for (origin, dest, _weight) in self.sorted_links() {
+            let line: geojson::Geometry =
+                geojson::Value::from(&self.link_line(geoms, origin, dest)?).into();
+
+            let mut feature = Feature {
+                geometry: Some(line),
+                ..Default::default()
+            };
+            feature.set_property("origin", format!("{}", origin));
+            feature.set_property("dest", format!("{}", dest));
+            features.push(feature);
         }
-        FeatureCollection {
+
+        Ok(FeatureCollection {
             features,
             bbox: None,
             foreign_members: None,
-        }
+        })
     }
-
-    // pub fn as_geopoalrs(&self, geoms: &[Geometry<A>], ids: &Vec<T>)->Result<DataFrame, Error>{
-    //    use polars::io::{SerWriter, SerReader};
-    //    use polars::prelude::NamedFromOwned;
-    //    use geopolars::geoseries::GeoSeries;
-
-    //    let mut origin_ids : Vec<i32> = Vec::with_capacity(geoms.len());
-    //    let mut dests_ids: Vec<i32> = Vec::with_capacity(geoms.len());
-    //    let mut weights: Vec<f32> = Vec::with_capacity(geoms.len());
-    //    let mut lines: Vec<Line> = Vec::with_capacity(geoms.len());
-
-    //    for (origin, dests) in self.weights().unwrap().iter(){
-    //         for (dest, _weight) in dests.iter(){
-    //             origin_ids.push(origin);
-    //             dest_ids.push(dest);
-    //             weights.push(weight);
-    //             let origin_index =  ids.iter().position(|a| a == origin).unwrap();
-    //             let dest_index =  ids.iter().position(|a| a == dest).unwrap();
-    //             let origin_centroid = geoms.get(origin_index).unwrap().centroid().unwrap();
-    //             let dest_centroid = geoms.get(dest_index).unwrap().centroid().unwrap();
-    //             let line  = Line::new(origin_centroid, dest_centroid);
-    //             geoms.push(line);
-    //         }
-    //    }
-    //    let geom_col = Series::from_geom_vec(&lines);
-    //    let result = DataFrame:::new([
-    //         Series::from_vec("origin_id", origin_ids),
-    //         Series::from_vec("dest_id", dests_ids),
-    //         Series::from_vec("weight", weights),
-    //         Series::from_vec("geom", geoms),
-    //    ]);
-    //    result
-    // }
 }
@@ -0,0 +1,126 @@
+use crate::utils::representative_point;
+use crate::{
+    weights::Weights, CompactWeights, DistanceWeights, KNNWeights, NetworkWeights, QueensWeights,
+    RasterWeights, RookWeights, WeightBuilder,
+};
+use geo::GeoFloat;
+use geo_types::Geometry;
+use serde::{Deserialize, Serialize};
+
+/// A serializable description of how to build a weights matrix, wrapping each of the weight
+/// builders. The builder type is given by the `type` key and the remaining keys are the builders
+/// fields, for example
+///
+/// ```json
+/// {"type": "knn", "k": 6, "metric": "haversine"}
+/// {"type": "queen", "tolerance": 10000.0}
+/// {"type": "distance", "cutoff_dist": 1000.0, "use_distance_as_weight": false}
+/// ```
+///
+/// The tolerance of the contiguity and network builders is the multiplier applied to
+/// coordinates before they are hashed, so larger values match vertices more strictly. It has to
+/// be at least 1, so a distance such as `"tolerance": 0.0001` is rejected when building.
+/// Enum fields such as a raster's `contiguity` or a network's `weight_type` are snake case too.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WeightsSpec<A>
+where
+    A: GeoFloat,
+{
+    Queen(QueensWeights<A>),
+    Rook(RookWeights<A>),
+    Distance(DistanceWeights<A>),
+    Knn(KNNWeights),
+    Network(NetworkWeights<A>),
+    /// Raster weights are built from the shape of the raster alone, the geometries are only
+    /// used to check there is one for each valid cell
+    Raster(RasterWeights),
+}
+
+impl<A> WeightsSpec<A>
+where
+    A: GeoFloat + Send + Sync,
+{
+    /// Build the weights matrix described by this spec for the given geometries. Returns an
+    /// error if the spec can not produce weights for them, rather than panicking.
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - the geometries to compute the weights for
+    ///
+    pub fn build<T>(&self, geoms: &T) -> Result<Weights, String>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        self.check(geoms)?;
+        Ok(match self {
+            WeightsSpec::Queen(builder) => builder.compute_weights(geoms),
+            WeightsSpec::Rook(builder) => builder.compute_weights(geoms),
+            WeightsSpec::Distance(builder) => builder.compute_weights(geoms),
+            WeightsSpec::Knn(builder) => builder.compute_weights(geoms),
+            WeightsSpec::Network(builder) => builder.compute_weights(geoms),
+            WeightsSpec::Raster(builder) => builder.compute_weights(),
+        })
+    }
+
+    /// Same as `build` but produces the compact form directly, for large datasets
+    ///
+    /// # Arguments
+    ///
+    /// * `geoms` - the geometries to compute the weights for
+    ///
+    pub fn build_compact<T>(&self, geoms: &T) -> Result<CompactWeights, String>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        self.check(geoms)?;
+        match self {
+            WeightsSpec::Queen(builder) => builder.compute_compact_weights(geoms),
+            WeightsSpec::Rook(builder) => builder.compute_compact_weights(geoms),
+            WeightsSpec::Distance(builder) => builder.compute_compact_weights(geoms),
+            WeightsSpec::Knn(builder) => builder.compute_compact_weights(geoms),
+            WeightsSpec::Network(builder) => builder.compute_compact_weights(geoms),
+            WeightsSpec::Raster(builder) => {
+                CompactWeights::from_weights(&builder.compute_weights())
+            }
+        }
+    }
+
+    /// Checks the spec can produce weights for the geometries
+    fn check<T>(&self, geoms: &T) -> Result<(), String>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Geometry<A>>,
+    {
+        match self {
+            WeightsSpec::Queen(builder) => builder.validate(),
+            WeightsSpec::Rook(builder) => builder.validate(),
+            WeightsSpec::Distance(builder) => {
+                builder.validate()?;
+                match geoms
+                    .into_iter()
+                    .position(|geom| representative_point(geom).is_none())
+                {
+                    Some(index) => Err(format!(
+                        "Geometry {} is empty, could not compute a representative point",
+                        index
+                    )),
+                    None => Ok(()),
+                }
+            }
+            WeightsSpec::Knn(_) => Ok(()),
+            WeightsSpec::Network(builder) => builder.validate(),
+            WeightsSpec::Raster(builder) => {
+                let no_cells = builder.valid_cells().len();
+                let no_geoms = geoms.into_iter().count();
+                if no_geoms != no_cells {
+                    Err(format!(
+                        "Raster has {} valid cells but got {} geometries",
+                        no_cells, no_geoms
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+#![cfg(feature = "arrow")]
+
+use arrow_array::builder::{Float64Builder, ListBuilder, StructBuilder};
+use arrow_array::{Array, BinaryArray};
+use arrow_schema::{DataType, Field, Fields};
+use geo_types::{Geometry, LineString, Point, Polygon};
+use geo_weights::{
+    geometries_from_arrow, CoordsWeightBuilder, DistanceMetric, DistanceWeights,
+    GeoArrowGeometries, KNNWeights, QueensWeights, RookWeights, WeightBuilder, Weights,
+};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::collections::HashMap;
+
+fn point_wkb(x: f64, y: f64) -> Vec<u8> {
+    let mut wkb = vec![1];
+    wkb.extend_from_slice(&1u32.to_le_bytes());
+    wkb.extend_from_slice(&x.to_le_bytes());
+    wkb.extend_from_slice(&y.to_le_bytes());
+    wkb
+}
+
+#[test]
+fn wkb_column_should_decode_to_geometries() {
+    // A big endian polygon as written by some GeoParquet writers
+    let mut polygon_wkb = vec![0];
+    polygon_wkb.extend_from_slice(&3u32.to_be_bytes());
+    polygon_wkb.extend_from_slice(&1u32.to_be_bytes());
+    polygon_wkb.extend_from_slice(&4u32.to_be_bytes());
+    for (x, y) in [(0.0f64, 0.0f64), (1.0, 0.0), (0.0, 1.0), (0.0, 0.0)] {
+        polygon_wkb.extend_from_slice(&x.to_be_bytes());
+        polygon_wkb.extend_from_slice(&y.to_be_bytes());
+    }
+
+    let array = BinaryArray::from_vec(vec![&point_wkb(1.0, 2.0), &polygon_wkb]);
+    let field = Field::new("geometry", DataType::Binary, false);
+    let geoms = geometries_from_arrow(&array, &field).unwrap();
+
+    assert_eq!(geoms[0], Geometry::Point(Point::new(1.0, 2.0)));
+    assert_eq!(
+        geoms[1],
+        Geometry::Polygon(Polygon::new(
+            LineString::from(vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (0.0, 0.0)]),
+            vec![]
+        ))
+    );
+
+    let truncated = BinaryArray::from_vec(vec![&point_wkb(1.0, 2.0)[..12]]);
+    assert!(geometries_from_arrow(&truncated, &field).is_err());
+
+    // A MultiPolygon whose only part is a Point
+    let mut mixed_wkb = vec![1];
+    mixed_wkb.extend_from_slice(&6u32.to_le_bytes());
+    mixed_wkb.extend_from_slice(&1u32.to_le_bytes());
+    mixed_wkb.extend_from_slice(&point_wkb(1.0, 2.0));
+    let mixed = BinaryArray::from_vec(vec![&mixed_wkb]);
+    assert!(geometries_from_arrow(&mixed, &field).is_err());
+    assert!(GeoArrowGeometries::try_new(&mixed, &field).is_err());
+}
+
+#[test]
+fn native_polygons_should_be_usable_by_builders() {
+    let coord_fields = Fields::from(vec![
+        Field::new("x", DataType::Float64, false),
+        Field::new("y", DataType::Float64, false),
+    ]);
+    let coords = StructBuilder::new(
+        coord_fields,
+        vec![
+            Box::new(Float64Builder::new()),
+            Box::new(Float64Builder::new()),
+        ],
+    );
+    let mut builder = ListBuilder::new(ListBuilder::new(coords));
+
+    // Two unit squares sharing the edge x = 1
+    for offset in [0.0, 1.0] {
+        let rings = builder.values();
+        let ring = rings.values();
+        for (x, y) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)] {
+            ring.field_builder::<Float64Builder>(0)
+                .unwrap()
+                .append_value(x + offset);
+            ring.field_builder::<Float64Builder>(1)
+                .unwrap()
+                .append_value(y);
+            ring.append(true);
+        }
+        rings.append(true);
+        builder.append(true);
+    }
+    let array = builder.finish();
+
+    let metadata = HashMap::from([(
+        "ARROW:extension:name".to_string(),
+        "geoarrow.polygon".to_string(),
+    )]);
+    let field = Field::new("geometry", array.data_type().clone(), false).with_metadata(metadata);
+    let geoms = geometries_from_arrow(&array, &field).unwrap();
+    assert_eq!(geoms.len(), 2);
+
+    let weights = QueensWeights::new(10000.0).compute_weights(&geoms);
+    assert!(weights.are_neighbors(0, 1));
+
+    // Reading the coordinates straight from the Arrow buffers gives the same weights
+    let columns = GeoArrowGeometries::try_new(&array, &field).unwrap();
+    assert_eq!(columns.geometry(1), geoms[1]);
+    let from_coords = QueensWeights::new(10000.0).compute_weights_from_coords(&columns);
+    assert_eq!(from_coords.weights(), weights.weights());
+    let rook = RookWeights::new(10000.0)
+        .compute_compact_weights_from_coords(&columns)
+        .unwrap();
+    assert_eq!(rook.row(0), (&[1u32][..], &[1.0f32][..]));
+}
+
+#[test]
+fn wkb_columns_should_be_read_by_builders_without_decoding() {
+    let array = BinaryArray::from_vec(vec![
+        &point_wkb(0.0, 0.0),
+        &point_wkb(1.0, 0.0),
+        &point_wkb(5.0, 0.0),
+    ]);
+    let field = Field::new("geometry", DataType::Binary, false);
+    let columns = GeoArrowGeometries::try_new(&array, &field).unwrap();
+
+    let weights =
+        KNNWeights::new(1, DistanceMetric::Euclidean).compute_weights_from_coords(&columns);
+    assert_eq!(weights.get_neighbor_ids(2).unwrap(), [1].into());
+    let weights = DistanceWeights::new(Some(2.0), false).compute_weights_from_coords(&columns);
+    assert_eq!(weights.get_neighbor_ids(0).unwrap(), [1].into());
+}
+
+#[test]
+fn weights_should_round_trip_through_parquet() {
+    let weights = Weights::from_list_rep(&vec![0, 1], &vec![1, 2], &vec![1.0, 0.5], 3);
+
+    let batch = weights.to_record_batch().unwrap();
+    assert_eq!(batch.num_rows(), 4);
+    let from_batch = Weights::from_record_batch(&batch, 3).unwrap();
+    assert_eq!(from_batch.weights(), weights.weights());
+    assert!(Weights::from_record_batch(&batch, 2).is_err());
+
+    let path = std::env::temp_dir().join("geo_weights_round_trip.parquet");
+    weights
+        .write_parquet(std::fs::File::create(&path).unwrap())
+        .unwrap();
+    let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches: Vec<_> = reader.map(|b| b.unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(batches[0], batch);
+}
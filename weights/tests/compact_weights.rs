@@ -0,0 +1,113 @@
+use geo_types::{polygon, Geometry};
+use geo_weights::{
+    CompactWeights, DistanceMetric, DistanceWeights, KNNWeights, QueensWeights, RookWeights,
+    TransformType, WeightBuilder, Weights, WeightsSpec,
+};
+
+fn weights() -> Weights {
+    Weights::from_list_rep(&vec![0, 1], &vec![1, 2], &vec![1.0, 0.5], 4)
+}
+
+#[test]
+fn compact_weights_should_round_trip_and_match_sparse_matrix() {
+    let weights = weights();
+    let compact = CompactWeights::from_weights(&weights).unwrap();
+    assert_eq!(compact.no_elements(), 4);
+    assert_eq!(compact.no_links(), 4);
+    assert_eq!(compact.row(1), (&[0u32, 2][..], &[1.0f32, 0.5][..]));
+    assert_eq!(compact.no_neighbors(3), 0);
+
+    for transform in [
+        None,
+        Some(TransformType::Row),
+        Some(TransformType::Binary),
+        Some(TransformType::DoublyStandardized),
+    ] {
+        let expected = weights.as_sparse_matrix(transform);
+        let actual = compact.as_sparse_matrix(transform);
+        for (i, j, v) in expected.triplet_iter() {
+            let compact_value = actual.get_entry(i, j).unwrap().into_value();
+            assert!((compact_value - v).abs() < 1e-6);
+        }
+        assert_eq!(expected.nnz(), actual.nnz());
+    }
+
+    let round_trip = compact.to_weights();
+    assert_eq!(round_trip.no_elements(), 4);
+    assert_eq!(round_trip.get_neighbor_ids(2).unwrap(), [1].into());
+}
+
+#[test]
+fn from_links_should_keep_last_repeated_link_and_validate_ids() {
+    let compact = CompactWeights::from_links(&[2, 0, 2], &[0, 1, 0], &[1.0, 2.0, 3.0], 3).unwrap();
+    assert_eq!(compact.no_links(), 2);
+    assert_eq!(compact.row(2), (&[0u32][..], &[3.0f32][..]));
+    assert_eq!(compact.no_neighbors(1), 0);
+
+    assert!(CompactWeights::from_links(&[0], &[3], &[1.0], 3).is_err());
+    assert!(CompactWeights::from_links(&[0], &[1, 2], &[1.0], 3).is_err());
+}
+
+#[test]
+fn compact_weights_should_validate_when_deserialized() {
+    let compact = CompactWeights::from_weights(&weights()).unwrap();
+    let json = serde_json::to_string(&compact).unwrap();
+    assert_eq!(
+        serde_json::from_str::<CompactWeights>(&json).unwrap(),
+        compact
+    );
+
+    for invalid in [
+        // No offsets at all
+        r#"{"offsets": [], "dests": [], "values": []}"#,
+        // Not starting at 0
+        r#"{"offsets": [1, 1], "dests": [0], "values": [1.0]}"#,
+        // Decreasing
+        r#"{"offsets": [0, 2, 1, 2], "dests": [1, 2], "values": [1.0, 1.0]}"#,
+        // Last offset not matching the number of links
+        r#"{"offsets": [0, 1, 1], "dests": [1, 0], "values": [1.0, 1.0]}"#,
+        r#"{"offsets": [0, 1, 2], "dests": [1, 0], "values": [1.0]}"#,
+        // Dest beyond the number of elements
+        r#"{"offsets": [0, 1, 1], "dests": [2], "values": [1.0]}"#,
+        // Dests out of order within an origin
+        r#"{"offsets": [0, 2, 2, 2], "dests": [2, 1], "values": [1.0, 1.0]}"#,
+    ] {
+        assert!(
+            serde_json::from_str::<CompactWeights>(invalid).is_err(),
+            "{}",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn builders_should_write_compact_weights_directly() {
+    let squares: Vec<Geometry<f64>> = vec![
+        polygon![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0), (x: 1.0, y: 1.0), (x: 0.0, y: 1.0)].into(),
+        polygon![(x: 1.0, y: 0.0), (x: 2.0, y: 0.0), (x: 2.0, y: 1.0), (x: 1.0, y: 1.0)].into(),
+        polygon![(x: 2.0, y: 1.0), (x: 3.0, y: 1.0), (x: 3.0, y: 2.0), (x: 2.0, y: 2.0)].into(),
+        polygon![(x: 5.0, y: 5.0), (x: 6.0, y: 5.0), (x: 6.0, y: 6.0), (x: 5.0, y: 6.0)].into(),
+    ];
+
+    fn check<B: WeightBuilder<f64>>(builder: B, geoms: &[Geometry<f64>]) {
+        let expected = CompactWeights::from_weights(&builder.compute_weights(geoms)).unwrap();
+        assert_eq!(builder.compute_compact_weights(geoms).unwrap(), expected);
+    }
+    check(QueensWeights::new(10000.0), &squares);
+    check(RookWeights::new(10000.0), &squares);
+    check(DistanceWeights::new(Some(2.0), true), &squares);
+    check(KNNWeights::new(2, DistanceMetric::Euclidean), &squares);
+
+    let queen = QueensWeights::new(10000.0)
+        .compute_compact_weights(&squares)
+        .unwrap();
+    assert_eq!(queen.row(1), (&[0u32, 2][..], &[1.0f32, 1.0][..]));
+    assert_eq!(queen.no_neighbors(3), 0);
+
+    let spec: WeightsSpec<f64> =
+        serde_json::from_str(r#"{"type": "rook", "tolerance": 10000.0}"#).unwrap();
+    assert_eq!(spec.build_compact(&squares).unwrap().no_links(), 2);
+    assert!(DistanceWeights::new(None, false)
+        .compute_compact_weights(&squares)
+        .is_err());
+}
@@ -0,0 +1,107 @@
+use geo_types::{polygon, Geometry, Point};
+use geo_weights::{CrossWeightBuilder, CrossWeightRule, CrossWeights, TransformType};
+use std::collections::{HashMap, HashSet};
+
+fn tracts() -> Vec<Geometry<f64>> {
+    vec![
+        polygon![(x: 0.0, y: 0.0), (x: 2.0, y: 0.0), (x: 2.0, y: 2.0), (x: 0.0, y: 2.0)].into(),
+        polygon![(x: 2.0, y: 0.0), (x: 4.0, y: 0.0), (x: 4.0, y: 2.0), (x: 2.0, y: 2.0)].into(),
+    ]
+}
+
+fn schools() -> Vec<Geometry<f64>> {
+    vec![
+        Point::new(0.5, 0.5).into(),
+        Point::new(3.0, 1.0).into(),
+        Point::new(10.0, 10.0).into(),
+    ]
+}
+
+#[test]
+fn cross_weights_should_relate_sources_to_targets_within_distance() {
+    let builder = CrossWeightBuilder::new(CrossWeightRule::Distance {
+        cutoff_dist: 2.5,
+        use_distance_as_weight: false,
+    });
+    let weights = builder.compute_cross_weights(&tracts(), &schools());
+
+    assert_eq!(weights.no_sources(), 2);
+    assert_eq!(weights.no_targets(), 3);
+    // Distances are to the tracts themselves, so school 0 is 1.5 from the edge of tract 1
+    assert_eq!(weights.get_target_ids(0), Some(HashSet::from([0, 1])));
+    assert_eq!(weights.get_target_ids(1), Some(HashSet::from([0, 1])));
+
+    let matrix = weights.as_sparse_matrix(Some(TransformType::Row));
+    assert_eq!(matrix.nrows(), 2);
+    assert_eq!(matrix.ncols(), 3);
+    assert_eq!(matrix.get_entry(0, 1).unwrap().into_value(), 0.5);
+}
+
+#[test]
+fn cross_weights_should_measure_distance_between_geometries() {
+    // A long tract whose centroid is far from a school just past its end
+    let tract: Vec<Geometry<f64>> = vec![polygon![
+        (x: 0.0, y: 0.0),
+        (x: 100.0, y: 0.0),
+        (x: 100.0, y: 1.0),
+        (x: 0.0, y: 1.0)
+    ]
+    .into()];
+    let school: Vec<Geometry<f64>> = vec![Point::new(101.0, 0.5).into()];
+
+    let weights = CrossWeightBuilder::new(CrossWeightRule::Distance {
+        cutoff_dist: 2.0,
+        use_distance_as_weight: true,
+    })
+    .compute_cross_weights(&tract, &school);
+    assert_eq!(weights.weights()[&0][&0], 1.0);
+
+    let inside = CrossWeightBuilder::new(CrossWeightRule::Distance {
+        cutoff_dist: 2.0,
+        use_distance_as_weight: true,
+    })
+    .compute_cross_weights(&tract, &vec![Point::new(50.0, 0.5).into()]);
+    assert_eq!(inside.weights()[&0][&0], 0.0);
+}
+
+#[test]
+fn cross_weights_should_support_knn_and_intersection_rules() {
+    let knn = CrossWeightBuilder::new(CrossWeightRule::KNN { k: 1 })
+        .compute_cross_weights(&schools(), &tracts());
+    assert_eq!(knn.get_target_ids(0), Some(HashSet::from([0])));
+    assert_eq!(knn.get_target_ids(1), Some(HashSet::from([1])));
+    assert_eq!(knn.get_target_ids(2), Some(HashSet::from([1])));
+
+    let intersects = CrossWeightBuilder::<f64>::new(CrossWeightRule::Intersects)
+        .compute_cross_weights(&schools(), &tracts());
+    assert_eq!(intersects.get_target_ids(0), Some(HashSet::from([0])));
+    assert_eq!(intersects.get_target_ids(1), Some(HashSet::from([1])));
+    assert_eq!(intersects.get_target_ids(2), None);
+}
+
+#[test]
+fn cross_weights_should_apply_every_transform() {
+    let mut weights = HashMap::new();
+    weights.insert(0, HashMap::from([(0, 2.0), (1, 6.0)]));
+    weights.insert(1, HashMap::from([(2, 2.0)]));
+    let weights = CrossWeights::new(weights, 2, 3);
+
+    let raw = weights.as_sparse_matrix(None);
+    assert_eq!(raw.get_entry(0, 1).unwrap().into_value(), 6.0);
+
+    let binary = weights.as_sparse_matrix(Some(TransformType::Binary));
+    assert!(binary.values().iter().all(|w| *w == 1.0));
+    assert_eq!(binary.nnz(), 3);
+
+    let doubly = weights.as_sparse_matrix(Some(TransformType::DoublyStandardized));
+    assert_eq!(doubly.get_entry(0, 1).unwrap().into_value(), 0.6);
+    assert_eq!(doubly.values().iter().sum::<f64>(), 1.0);
+}
+
+#[test]
+fn cross_weight_rules_should_use_snake_case_names() {
+    let rule: CrossWeightRule<f64> = serde_json::from_str(r#"{"knn": {"k": 2}}"#).unwrap();
+    assert!(matches!(rule, CrossWeightRule::KNN { k: 2 }));
+    let rule: CrossWeightRule<f64> = serde_json::from_str(r#""intersects""#).unwrap();
+    assert!(matches!(rule, CrossWeightRule::Intersects));
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "polars")]
+
+use geo_types::{Geometry, Point};
+use geo_weights::Weights;
+use polars::prelude::{Column, DataFrame};
+
+#[test]
+fn dataframe_should_round_trip_weights() {
+    let weights = Weights::from_list_rep(&vec![0, 1], &vec![1, 2], &vec![1.0, 0.5], 4);
+    let geoms: Vec<Geometry<f64>> = vec![
+        Point::new(0.0, 0.0).into(),
+        Point::new(1.0, 0.0).into(),
+        Point::new(2.0, 0.0).into(),
+        Point::new(3.0, 0.0).into(),
+    ];
+
+    let df = weights.to_dataframe(Some(&geoms)).unwrap();
+    assert_eq!(df.shape(), (4, 4));
+    let origins: Vec<Option<u64>> = df
+        .column("origin")
+        .unwrap()
+        .u64()
+        .unwrap()
+        .into_iter()
+        .collect();
+    assert_eq!(origins, vec![Some(0), Some(1), Some(1), Some(2)]);
+    let wkb = df
+        .column("geometry")
+        .unwrap()
+        .binary()
+        .unwrap()
+        .get(0)
+        .unwrap()
+        .to_vec();
+    assert_eq!(wkb.len(), 41);
+    assert_eq!(&wkb[..5], &[1, 2, 0, 0, 0]);
+
+    let round_trip = Weights::from_dataframe(&df, Some(4)).unwrap();
+    assert_eq!(round_trip.no_elements(), 4);
+    assert_eq!(round_trip.get_neighbor_ids(1).unwrap(), [0, 2].into());
+    assert_eq!(round_trip.weights()[&2][&1], 0.5);
+
+    assert!(weights.to_dataframe(Some(&geoms[..2])).is_err());
+}
+
+#[test]
+fn from_dataframe_should_default_weights_and_size() {
+    let df = DataFrame::new(vec![
+        Column::new("origin".into(), vec![0i32, 2]),
+        Column::new("dest".into(), vec![2i32, 0]),
+    ])
+    .unwrap();
+    let weights = Weights::from_dataframe(&df, None).unwrap();
+    assert_eq!(weights.no_elements(), 3);
+    assert_eq!(weights.weights()[&0][&2], 1.0);
+
+    assert!(Weights::from_dataframe(&df, Some(2)).is_err());
+}
@@ -1,4 +1,4 @@
-use geo_types::{Geometry, Point};
+use geo_types::{coord, line_string, Geometry, GeometryCollection, MultiPoint, Point, Triangle};
 use geo_weights::{DistanceWeights, WeightBuilder};
 
 #[test]
@@ -38,3 +38,27 @@ fn weighted_euclid_weights_should_compute_correct_weight() {
 
     let weights = weight_builder.compute_weights(&points);
 }
+
+#[test]
+fn distance_weights_should_use_centroids_for_lines_and_collections() {
+    let weight_builder: DistanceWeights<f64> = DistanceWeights::new(Some(2.0), false);
+    let geoms: Vec<Geometry<f64>> = vec![
+        line_string![(x: 0.0, y: 0.0), (x: 2.0, y: 0.0)].into(),
+        Triangle::new(
+            coord! { x: 1.0, y: 1.0 },
+            coord! { x: 2.0, y: 1.0 },
+            coord! { x: 1.5, y: 2.0 },
+        )
+        .into(),
+        Geometry::GeometryCollection(GeometryCollection(vec![
+            Point::new(50.0, 50.0).into(),
+            MultiPoint::from(vec![(52.0, 50.0)]).into(),
+        ])),
+    ];
+
+    let weights = weight_builder.compute_weights(&geoms);
+
+    assert!(weights.are_neighbors(0, 1));
+    assert!(weights.are_neighbors(1, 0));
+    assert_eq!(weights.get_neighbor_ids(2), None);
+}
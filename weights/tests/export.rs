@@ -0,0 +1,92 @@
+use geo_types::{Geometry, Point};
+use geo_weights::Weights;
+
+fn weights() -> Weights {
+    Weights::from_list_rep(&vec![0, 1], &vec![1, 2], &vec![1.0, 0.5], 3)
+}
+
+#[test]
+fn csv_edge_list_should_be_sorted_with_a_header() {
+    let csv = weights().to_csv_edge_list::<String>(None).unwrap();
+    assert_eq!(csv, "origin,dest,weight\n0,1,1\n1,0,1\n1,2,0.5\n2,1,0.5\n");
+
+    let ids = vec!["a", "b,c", "d"];
+    let csv = weights().to_csv_edge_list(Some(&ids)).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "origin,dest,origin_id,dest_id,weight");
+    assert_eq!(lines[1], "0,1,a,\"b,c\",1");
+
+    assert!(weights().to_csv_edge_list(Some(&ids[..2])).is_err());
+}
+
+#[test]
+fn graphml_should_include_node_attributes_and_weighted_edges() {
+    let geoms: Vec<Geometry<f64>> = vec![
+        Point::new(1.0, 2.0).into(),
+        Point::new(3.0, 4.0).into(),
+        Point::new(5.0, 6.0).into(),
+    ];
+    let ids = vec!["a", "b&c", "d"];
+
+    let graphml = weights().to_graphml(Some(&geoms), Some(&ids)).unwrap();
+
+    assert!(graphml.contains(r#"<key id="x" for="node" attr.name="x" attr.type="double"/>"#));
+    assert!(graphml.contains(
+        "    <node id=\"n1\">\n      <data key=\"id\">b&amp;c</data>\n      <data key=\"x\">3</data>\n      <data key=\"y\">4</data>\n    </node>"
+    ));
+    assert!(graphml.contains(
+        "    <edge source=\"n1\" target=\"n2\">\n      <data key=\"weight\">0.5</data>\n    </edge>"
+    ));
+    assert_eq!(graphml.matches("<edge ").count(), 4);
+}
+
+#[test]
+fn links_feature_collection_should_be_sorted_with_numeric_properties() {
+    let geoms: Vec<Geometry<f64>> = vec![
+        Point::new(0.0, 0.0).into(),
+        Point::new(1.0, 0.0).into(),
+        Point::new(2.0, 0.0).into(),
+    ];
+
+    let fc = weights().links_feature_collection(&geoms, false).unwrap();
+    let links: Vec<(u64, u64, f64)> = fc
+        .features
+        .iter()
+        .map(|f| {
+            (
+                f.property("origin").unwrap().as_u64().unwrap(),
+                f.property("dest").unwrap().as_u64().unwrap(),
+                f.property("weight").unwrap().as_f64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        links,
+        vec![(0, 1, 1.0), (1, 0, 1.0), (1, 2, 0.5), (2, 1, 0.5)]
+    );
+
+    let fc = weights().links_feature_collection(&geoms, true).unwrap();
+    assert_eq!(fc.features.len(), 2);
+    assert_eq!(fc.features[1].property("origin").unwrap(), 1);
+    assert_eq!(fc.features[1].property("dest").unwrap(), 2);
+
+    assert!(weights()
+        .links_feature_collection(&geoms[..2], false)
+        .is_err());
+}
+
+#[test]
+fn links_geojson_should_keep_string_ids() {
+    let geoms: Vec<Geometry<f64>> = vec![
+        Point::new(0.0, 0.0).into(),
+        Point::new(1.0, 0.0).into(),
+        Point::new(2.0, 0.0).into(),
+    ];
+
+    let fc = weights().links_geojson(&geoms);
+    assert_eq!(fc.features[0].property("origin").unwrap(), "0");
+    assert_eq!(fc.features[0].property("dest").unwrap(), "1");
+    assert!(fc.features[0].property("weight").is_none());
+
+    assert!(weights().try_links_geojson(&geoms[..2]).is_err());
+}
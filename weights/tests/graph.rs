@@ -0,0 +1,84 @@
+use geo_weights::Weights;
+
+fn chain_weights(size: usize) -> Weights {
+    // 0 - 1 - 2 - ... - size-1
+    let origins: Vec<usize> = (0..size - 1).collect();
+    let dests: Vec<usize> = (1..size).collect();
+    let weights = vec![1.0; size - 1];
+    Weights::from_list_rep(&origins, &dests, &weights, size)
+}
+
+#[test]
+fn minimum_spanning_tree_should_keep_the_cheapest_links() {
+    // A triangle 0-1-2 with 2 also linked to 3
+    let weights = Weights::from_list_rep(
+        &vec![0, 1, 0, 2],
+        &vec![1, 2, 2, 3],
+        &vec![1.0, 1.0, 1.0, 1.0],
+        4,
+    );
+    let values = vec![vec![0.0, 1.0, 5.0, 5.5]];
+
+    let tree = weights.minimum_spanning_tree(&values).unwrap();
+    assert!(tree.is_symmetric());
+    assert_eq!(tree.get_neighbor_ids(0).unwrap(), [1].into());
+    assert_eq!(tree.get_neighbor_ids(1).unwrap(), [0, 2].into());
+    assert_eq!(tree.get_neighbor_ids(2).unwrap(), [1, 3].into());
+    assert_eq!(tree.weights()[&1][&2], 4.0);
+    assert_eq!(tree.weights()[&2][&3], 0.5);
+
+    assert!(weights.minimum_spanning_tree(&[vec![1.0]]).is_err());
+}
+
+#[test]
+fn minimum_spanning_tree_should_skip_out_of_range_links_and_reject_nan() {
+    // The link to 5 refers to an observation past the end of the weights
+    let weights = Weights::from_list_rep(&vec![0, 1], &vec![1, 5], &vec![1.0, 1.0], 3);
+    let tree = weights
+        .minimum_spanning_tree(&[vec![0.0, 1.0, 2.0]])
+        .unwrap();
+    assert_eq!(tree.get_neighbor_ids(1).unwrap(), [0].into());
+
+    assert!(weights
+        .minimum_spanning_tree(&[vec![0.0, f64::NAN, 2.0]])
+        .is_err());
+}
+
+#[test]
+fn hop_distances_should_count_links_on_shortest_path() {
+    let weights = chain_weights(5);
+    assert_eq!(
+        weights.hop_distances(1),
+        vec![Some(1), Some(0), Some(1), Some(2), Some(3)]
+    );
+    assert_eq!(weights.hop_distance(0, 4), Some(4));
+
+    let split = weights.remove_links(&[(2, 3)]);
+    assert_eq!(split.hop_distance(0, 4), None);
+    assert_eq!(split.connected_components(), vec![0, 0, 0, 1, 1]);
+}
+
+#[test]
+fn higher_order_should_link_observations_at_exact_order() {
+    let second = chain_weights(5).higher_order(2);
+    assert_eq!(second.get_neighbor_ids(0).unwrap(), [2].into());
+    assert_eq!(second.get_neighbor_ids(2).unwrap(), [0, 4].into());
+
+    let fourth = chain_weights(5).higher_order(4);
+    assert_eq!(fourth.get_neighbor_ids(0).unwrap(), [4].into());
+    assert!(fourth.get_neighbor_ids(2).unwrap().is_empty());
+
+    // Orders beyond the longest shortest path and order 0 link nothing
+    for order in [0, 5] {
+        let weights = chain_weights(5).higher_order(order);
+        assert!((0..5).all(|i| weights.get_neighbor_ids(i).unwrap().is_empty()));
+    }
+}
+
+#[test]
+fn prune_should_drop_links_failing_predicate() {
+    let weights = Weights::from_list_rep(&vec![0, 1], &vec![1, 2], &vec![1.0, 3.0], 3);
+    let pruned = weights.prune(|_, _, weight| weight < 2.0);
+    assert_eq!(pruned.get_neighbor_ids(1).unwrap(), [0].into());
+    assert!(pruned.get_neighbor_ids(2).unwrap().is_empty());
+}
@@ -0,0 +1,35 @@
+use geo_types::{Geometry, Point};
+use geo_weights::{DistanceMetric, KNNWeights, WeightBuilder};
+use std::collections::HashSet;
+
+#[test]
+fn knn_weights_should_pick_the_k_nearest_neighbors() {
+    let weight_builder = KNNWeights::new(2, DistanceMetric::Euclidean);
+    let points: Vec<Geometry<f64>> = vec![
+        Point::new(0.0, 0.0).into(),
+        Point::new(1.0, 0.0).into(),
+        Point::new(3.0, 0.0).into(),
+        Point::new(10.0, 0.0).into(),
+    ];
+
+    let weights = weight_builder.compute_weights(&points);
+
+    assert_eq!(weights.get_neighbor_ids(0), Some(HashSet::from([1, 2])));
+    assert_eq!(weights.get_neighbor_ids(2), Some(HashSet::from([0, 1])));
+    assert_eq!(weights.get_neighbor_ids(3), Some(HashSet::from([1, 2])));
+}
+
+#[test]
+fn knn_weights_should_support_haversine_distances() {
+    let weight_builder = KNNWeights::new(1, DistanceMetric::Haversine);
+    // Close in degrees of longitude near the pole but far apart in degrees at the equator
+    let points: Vec<Geometry<f64>> = vec![
+        Point::new(0.0, 89.0).into(),
+        Point::new(40.0, 89.0).into(),
+        Point::new(0.0, 80.0).into(),
+    ];
+
+    let weights = weight_builder.compute_weights(&points);
+
+    assert_eq!(weights.get_neighbor_ids(0), Some(HashSet::from([1])));
+}
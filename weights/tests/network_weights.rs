@@ -0,0 +1,65 @@
+use geo_types::{line_string, Geometry, LineString, Point};
+use geo_weights::{KernelFunction, NetworkWeightType, NetworkWeights, WeightBuilder};
+use std::collections::HashSet;
+
+fn street_network() -> Vec<LineString<f64>> {
+    // An L shaped street running along the x axis and then up the y axis, plus a separate street
+    // which is not connected to the rest of the network.
+    vec![
+        line_string![(x: 0.0, y: 0.0), (x: 5.0, y: 0.0), (x: 10.0, y: 0.0)],
+        line_string![(x: 10.0, y: 0.0), (x: 10.0, y: 10.0)],
+        line_string![(x: 0.0, y: 2.0), (x: 0.0, y: 3.0)],
+    ]
+}
+
+#[test]
+fn network_weights_should_use_distance_along_the_network() {
+    let weight_builder = NetworkWeights::new(
+        street_network(),
+        1000.0,
+        Some(16.0),
+        NetworkWeightType::Binary,
+    );
+    let points: Vec<Geometry<f64>> = vec![
+        Point::new(0.0, 0.0).into(),
+        Point::new(10.0, 10.0).into(),
+        Point::new(5.0, 0.5).into(),
+        Point::new(0.0, 3.0).into(),
+    ];
+
+    let weights = weight_builder.compute_weights(&points);
+
+    // 0 and 1 are 14 apart in a straight line but 20 apart along the network
+    assert_eq!(weights.get_neighbor_ids(0), Some(HashSet::from([2])));
+    assert_eq!(weights.get_neighbor_ids(1), Some(HashSet::from([2])));
+    assert_eq!(weights.get_neighbor_ids(2), Some(HashSet::from([0, 1])));
+    // 3 is close to 0 in a straight line but on a disconnected street
+    assert_eq!(weights.get_neighbor_ids(3), Some(HashSet::new()));
+}
+
+#[test]
+fn network_weights_should_apply_inverse_distance_and_kernel_weighting() {
+    let points: Vec<Geometry<f64>> =
+        vec![Point::new(0.0, 0.0).into(), Point::new(10.0, 0.0).into()];
+
+    let inverse = NetworkWeights::new(
+        street_network(),
+        1000.0,
+        None,
+        NetworkWeightType::InverseDistance { power: 2.0 },
+    )
+    .compute_weights(&points);
+    assert!((inverse.weights()[&0][&1] - 0.01).abs() < 1e-12);
+
+    let kernel = NetworkWeights::new(
+        street_network(),
+        1000.0,
+        None,
+        NetworkWeightType::Kernel {
+            function: KernelFunction::Triangular,
+            bandwidth: 20.0,
+        },
+    )
+    .compute_weights(&points);
+    assert!((kernel.weights()[&1][&0] - 0.5).abs() < 1e-12);
+}
@@ -0,0 +1,106 @@
+use geo_types::{polygon, Geometry};
+use geo_weights::{DistanceWeights, QueensWeights, RookWeights, WeightBuilder, Weights};
+use std::collections::{HashMap, HashSet};
+
+const SIDE: usize = 20;
+
+/// A SIDE x SIDE grid of unit squares, large enough for the parallel iterators to split the
+/// work, numbered row by row
+fn grid() -> Vec<Geometry<f64>> {
+    let mut geoms = vec![];
+    for row in 0..SIDE {
+        for col in 0..SIDE {
+            let (x, y) = (col as f64, row as f64);
+            geoms.push(
+                polygon![
+                    (x: x, y: y),
+                    (x: x + 1.0, y: y),
+                    (x: x + 1.0, y: y + 1.0),
+                    (x: x, y: y + 1.0)
+                ]
+                .into(),
+            );
+        }
+    }
+    geoms
+}
+
+/// The cells around each cell, with the diagonals for queen contiguity
+fn grid_neighbors(diagonals: bool) -> HashMap<usize, HashSet<usize>> {
+    let side = SIDE as isize;
+    let mut neighbors = HashMap::new();
+    for row in 0..side {
+        for col in 0..side {
+            let mut cells = HashSet::new();
+            for (dr, dc) in [
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ] {
+                let (r, c) = (row + dr, col + dc);
+                let is_diagonal = dr != 0 && dc != 0;
+                if (0..side).contains(&r) && (0..side).contains(&c) && (diagonals || !is_diagonal) {
+                    cells.insert((r * side + c) as usize);
+                }
+            }
+            neighbors.insert((row * side + col) as usize, cells);
+        }
+    }
+    neighbors
+}
+
+fn neighbor_sets(weights: &Weights) -> HashMap<usize, HashSet<usize>> {
+    (0..weights.no_elements())
+        .map(|index| (index, weights.get_neighbor_ids(index).unwrap_or_default()))
+        .collect()
+}
+
+/// Builds the queen, rook and distance weights for the grid
+fn build_all() -> Vec<Weights> {
+    let geoms = grid();
+    vec![
+        QueensWeights::new(1000.0).compute_weights(&geoms),
+        RookWeights::new(1000.0).compute_weights(&geoms),
+        DistanceWeights::new(Some(1.5), true).compute_weights(&geoms),
+    ]
+}
+
+// Runs on whichever path is compiled in, so `cargo test --no-default-features` checks the
+// sequential path against the same expectations
+#[test]
+fn grid_weights_should_match_the_expected_neighbors() {
+    let weights = build_all();
+
+    assert_eq!(neighbor_sets(&weights[0]), grid_neighbors(true));
+    assert_eq!(neighbor_sets(&weights[1]), grid_neighbors(false));
+    assert_eq!(neighbor_sets(&weights[2]), grid_neighbors(true));
+
+    // Cell 21 is one row up and one column along from cell 0
+    assert_eq!(weights[2].weights()[&0][&1], 1.0);
+    assert_eq!(weights[2].weights()[&0][&(SIDE + 1)], 2.0_f64.sqrt());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_weights_should_match_sequential_weights() {
+    // A single thread runs the iterators in order, as the sequential path does
+    let build_on = |threads: usize| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(build_all)
+    };
+    let sequential = build_on(1);
+    let parallel = build_on(4);
+
+    for (sequential, parallel) in sequential.iter().zip(parallel.iter()) {
+        assert_eq!(sequential.weights(), parallel.weights());
+        assert_eq!(sequential.no_elements(), parallel.no_elements());
+    }
+}
@@ -1,7 +1,8 @@
 mod test_data;
 
-use geo_types::{polygon, Geometry};
+use geo_types::{line_string, polygon, Geometry, MultiPoint, Rect};
 use geo_weights::{QueensWeights, WeightBuilder};
+use std::collections::HashSet;
 use test_data::tracts;
 
 #[test]
@@ -65,3 +66,21 @@ fn queens_we_should_get_the_correct_weights() {
     assert!(n4.contains(&1));
     assert!(n4.contains(&0));
 }
+
+#[test]
+fn queens_lines_and_points_should_be_neighbors_when_sharing_a_vertex() {
+    let weight_builder = QueensWeights::new(10000.0);
+    let geoms: Vec<Geometry<f64>> = vec![
+        line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0)].into(),
+        line_string![(x: 1.0, y: 0.0), (x: 1.0, y: 1.0)].into(),
+        MultiPoint::from(vec![(5.0, 5.0), (1.0, 1.0)]).into(),
+        Rect::new((5.0, 5.0), (6.0, 6.0)).into(),
+    ];
+
+    let weights = weight_builder.compute_weights(&geoms);
+
+    assert_eq!(weights.get_neighbor_ids(0), Some(HashSet::from([1])));
+    assert_eq!(weights.get_neighbor_ids(1), Some(HashSet::from([0, 2])));
+    assert_eq!(weights.get_neighbor_ids(2), Some(HashSet::from([1, 3])));
+    assert_eq!(weights.get_neighbor_ids(3), Some(HashSet::from([2])));
+}
@@ -0,0 +1,105 @@
+use geo_weights::{RasterContiguity, RasterWeights};
+use std::collections::HashSet;
+
+// A 3x3 raster with the center cell missing
+//  0 1 2
+//  3 X 4
+//  5 6 7
+fn nodata_mask() -> Option<Vec<bool>> {
+    Some(vec![
+        false, false, false, false, true, false, false, false, false,
+    ])
+}
+
+#[test]
+fn raster_weights_should_skip_nodata_cells() {
+    let raster =
+        RasterWeights::new(3, 3, (1.0, 1.0), nodata_mask(), RasterContiguity::Rook).unwrap();
+    let weights = raster.compute_weights();
+
+    assert_eq!(weights.no_elements(), 8);
+    assert_eq!(raster.cell_position(1, 1), None);
+    assert_eq!(raster.cell_position(1, 2), Some(4));
+    assert_eq!(raster.valid_cells()[5], (2, 0));
+    assert_eq!(weights.get_neighbor_ids(1), Some(HashSet::from([0, 2])));
+    assert_eq!(weights.get_neighbor_ids(3), Some(HashSet::from([0, 5])));
+}
+
+#[test]
+fn raster_weights_should_support_queen_and_distance_contiguity() {
+    let queen =
+        RasterWeights::new(3, 3, (1.0, 1.0), nodata_mask(), RasterContiguity::Queen).unwrap();
+    let weights = queen.compute_weights();
+    assert_eq!(weights.get_neighbor_ids(0), Some(HashSet::from([1, 3])));
+    assert_eq!(
+        weights.get_neighbor_ids(1),
+        Some(HashSet::from([0, 2, 3, 4]))
+    );
+
+    let distance = RasterWeights::new(
+        3,
+        3,
+        (2.0, 1.0),
+        None,
+        RasterContiguity::Distance {
+            cutoff_dist: 2.5,
+            use_distance_as_weight: true,
+        },
+    )
+    .unwrap();
+    let weights = distance.compute_weights();
+    assert_eq!(
+        weights.get_neighbor_ids(0),
+        Some(HashSet::from([1, 3, 4, 6]))
+    );
+    assert_eq!(weights.weights()[&0][&6], 2.0);
+}
+
+#[test]
+fn raster_weights_should_reject_a_mask_of_the_wrong_size() {
+    let result = RasterWeights::new(2, 2, (1.0, 1.0), Some(vec![false]), RasterContiguity::Rook);
+    assert!(result.is_err());
+}
+
+#[test]
+fn raster_weights_should_validate_when_deserialized() {
+    let short_mask = r#"{
+        "rows": 2,
+        "cols": 2,
+        "cell_size": [1.0, 1.0],
+        "nodata_mask": [false],
+        "contiguity": "rook"
+    }"#;
+    assert!(serde_json::from_str::<RasterWeights>(short_mask).is_err());
+
+    let zero_cell = r#"{
+        "rows": 2,
+        "cols": 2,
+        "cell_size": [0.0, 1.0],
+        "nodata_mask": null,
+        "contiguity": "queen"
+    }"#;
+    assert!(serde_json::from_str::<RasterWeights>(zero_cell).is_err());
+
+    let negative_cutoff = r#"{
+        "rows": 2,
+        "cols": 2,
+        "cell_size": [1.0, 1.0],
+        "nodata_mask": null,
+        "contiguity": {"distance": {"cutoff_dist": -1.0, "use_distance_as_weight": false}}
+    }"#;
+    assert!(serde_json::from_str::<RasterWeights>(negative_cutoff).is_err());
+    let positive_cutoff = negative_cutoff.replace("-1.0", "1.5");
+    assert!(serde_json::from_str::<RasterWeights>(&positive_cutoff).is_ok());
+}
+
+#[test]
+fn raster_weights_should_reject_a_cutoff_that_is_not_positive() {
+    for cutoff_dist in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let contiguity = RasterContiguity::Distance {
+            cutoff_dist,
+            use_distance_as_weight: false,
+        };
+        assert!(RasterWeights::new(2, 2, (1.0, 1.0), None, contiguity).is_err());
+    }
+}
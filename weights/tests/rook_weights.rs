@@ -1,5 +1,7 @@
-use geo_types::{polygon, Geometry};
+use geo_types::{line_string, polygon, Geometry, GeometryCollection, Point};
 use geo_weights::{RookWeights, WeightBuilder};
+use std::collections::HashSet;
+
 #[test]
 fn we_should_get_the_correct_weights() {
     let weight_builder = RookWeights::new(10000.0);
@@ -40,8 +42,29 @@ fn we_should_get_the_correct_weights() {
     let n3 = weights.get_neighbor_ids(2).unwrap();
     let n4 = weights.get_neighbor_ids(3).unwrap();
 
-    assert!(n1.is_empty());
-    assert!(n2.is_empty());
+    assert_eq!(n1, HashSet::from([3]));
+    assert_eq!(n2, HashSet::from([3]));
     assert!(n3.is_empty());
-    assert!(n4.is_empty());
+    assert_eq!(n4, HashSet::from([0, 1]));
+}
+
+#[test]
+fn rook_line_strings_should_be_neighbors_only_when_sharing_a_segment() {
+    let weight_builder = RookWeights::new(10000.0);
+    let lines: Vec<Geometry<f64>> = vec![
+        line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0), (x: 2.0, y: 0.0)].into(),
+        line_string![(x: 2.0, y: 0.0), (x: 1.0, y: 0.0), (x: 1.0, y: 1.0)].into(),
+        line_string![(x: 2.0, y: 0.0), (x: 3.0, y: 0.0)].into(),
+        Geometry::GeometryCollection(GeometryCollection(vec![
+            Point::new(5.0, 5.0).into(),
+            line_string![(x: 1.0, y: 1.0), (x: 1.0, y: 0.0)].into(),
+        ])),
+    ];
+
+    let weights = weight_builder.compute_weights(&lines);
+
+    assert_eq!(weights.get_neighbor_ids(0), Some(HashSet::from([1])));
+    assert_eq!(weights.get_neighbor_ids(1), Some(HashSet::from([0, 3])));
+    assert_eq!(weights.get_neighbor_ids(2), Some(HashSet::new()));
+    assert_eq!(weights.get_neighbor_ids(3), Some(HashSet::from([1])));
 }
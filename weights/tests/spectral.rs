@@ -0,0 +1,71 @@
+use geo_weights::{log_det_from_eigenvalues, TransformType, Weights};
+
+fn lattice_weights(size: usize) -> Weights {
+    // Rook contiguity on a size x size lattice
+    let mut origins: Vec<usize> = vec![];
+    let mut dests: Vec<usize> = vec![];
+    for row in 0..size {
+        for col in 0..size {
+            let index = row * size + col;
+            if col + 1 < size {
+                origins.push(index);
+                dests.push(index + 1);
+            }
+            if row + 1 < size {
+                origins.push(index);
+                dests.push(index + size);
+            }
+        }
+    }
+    let weights = vec![1.0; origins.len()];
+    Weights::from_list_rep(&origins, &dests, &weights, size * size)
+}
+
+#[test]
+fn eigenvalues_should_be_computed_for_symmetric_and_row_standardized_weights() {
+    let weights = Weights::from_list_rep(&vec![0], &vec![1], &vec![2.0], 2);
+
+    let eigenvalues = weights.eigenvalues(None).unwrap();
+    assert!((eigenvalues[0] + 2.0).abs() < 1e-12);
+    assert!((eigenvalues[1] - 2.0).abs() < 1e-12);
+
+    let eigenvalues = weights.eigenvalues(Some(TransformType::Row)).unwrap();
+    assert!((eigenvalues[0] + 1.0).abs() < 1e-12);
+    assert!((eigenvalues[1] - 1.0).abs() < 1e-12);
+
+    let log_dets = log_det_from_eigenvalues(&eigenvalues, &[0.5]);
+    assert!((log_dets[0] - (0.5f64 * 1.5).ln()).abs() < 1e-12);
+}
+
+#[test]
+fn eigenvalues_should_fail_for_asymmetric_weights() {
+    let mut dict = std::collections::HashMap::new();
+    dict.insert(0, std::collections::HashMap::from([(1, 1.0)]));
+    let weights = Weights::new(dict, 2);
+    assert!(weights.eigenvalues(None).is_err());
+}
+
+#[test]
+fn approximate_log_dets_should_be_close_to_the_exact_values() {
+    let weights = lattice_weights(10);
+    let rhos = vec![-0.5, 0.1, 0.5, 0.8];
+
+    let exact = weights.log_det(&rhos, Some(TransformType::Row)).unwrap();
+    let monte_carlo = weights.log_det_monte_carlo(&rhos, Some(TransformType::Row), 30, 50, 42);
+    let chebyshev = weights.log_det_chebyshev(&rhos, Some(TransformType::Row), 10, 50, 42);
+
+    for i in 0..rhos.len() {
+        assert!(
+            (exact[i] - monte_carlo[i]).abs() < 0.05 * exact[i].abs().max(1.0),
+            "monte carlo {} vs exact {}",
+            monte_carlo[i],
+            exact[i]
+        );
+        assert!(
+            (exact[i] - chebyshev[i]).abs() < 0.05 * exact[i].abs().max(1.0),
+            "chebyshev {} vs exact {}",
+            chebyshev[i],
+            exact[i]
+        );
+    }
+}
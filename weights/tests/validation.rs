@@ -0,0 +1,144 @@
+use geo_types::{line_string, polygon, Geometry, LineString, MultiPolygon, Point, Polygon};
+use geo_weights::{
+    repair_geometries, validate_geometries, DistanceWeights, GeometryIssue, RepairOptions,
+    ValidationIssue, WeightBuilder,
+};
+
+#[test]
+fn validation_should_report_empty_self_intersecting_and_duplicate_geometries() {
+    let square: Geometry<f64> =
+        polygon![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0), (x: 1.0, y: 1.0), (x: 0.0, y: 1.0)].into();
+    let bow_tie: Geometry<f64> =
+        polygon![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0), (x: 1.0, y: 0.0), (x: 0.0, y: 1.0)].into();
+    let geoms: Vec<Geometry<f64>> = vec![
+        square.clone(),
+        MultiPolygon::<f64>(vec![]).into(),
+        bow_tie,
+        square,
+        Point::new(f64::NAN, 0.0).into(),
+        line_string![(x: 0.0, y: 0.0)].into(),
+    ];
+
+    let report = validate_geometries(&geoms);
+
+    assert!(!report.is_valid());
+    assert_eq!(report.invalid_indices(), vec![1, 2, 4, 5]);
+    assert_eq!(report.empty_indices(), vec![1]);
+    assert_eq!(report.duplicate_indices(), vec![3]);
+    assert!(report.issues.contains(&ValidationIssue {
+        index: 2,
+        issue: GeometryIssue::SelfIntersection
+    }));
+    assert!(report.issues.contains(&ValidationIssue {
+        index: 3,
+        issue: GeometryIssue::Duplicate(0)
+    }));
+}
+
+#[test]
+fn repair_should_remove_duplicate_vertices_and_normalize_orientation() {
+    // Clockwise exterior with a repeated vertex
+    let polygon = Polygon::new(
+        LineString::from(vec![
+            (0.0, 0.0),
+            (0.0, 1.0),
+            (0.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 0.0),
+        ]),
+        vec![],
+    );
+    let repaired = repair_geometries(&[polygon.into()], &RepairOptions::default());
+
+    let expected: Geometry<f64> = polygon![
+        (x: 0.0, y: 0.0),
+        (x: 1.0, y: 0.0),
+        (x: 1.0, y: 1.0),
+        (x: 0.0, y: 1.0),
+        (x: 0.0, y: 0.0)
+    ]
+    .into();
+    assert_eq!(repaired[0], expected);
+}
+
+#[test]
+fn try_compute_weights_should_return_the_report_instead_of_panicking() {
+    let weight_builder: DistanceWeights<f64> = DistanceWeights::new(Some(20.0), false);
+    let geoms: Vec<Geometry<f64>> = vec![
+        Point::new(0.0, 0.0).into(),
+        MultiPolygon::<f64>(vec![]).into(),
+    ];
+
+    let report = weight_builder
+        .try_compute_weights(&geoms, None)
+        .unwrap_err();
+    assert_eq!(report.empty_indices(), vec![1]);
+
+    let weights = weight_builder
+        .try_compute_weights(&geoms[..1], Some(&RepairOptions::default()))
+        .unwrap();
+    assert_eq!(weights.no_elements(), 1);
+}
+
+#[test]
+fn repair_should_close_unclosed_rings_when_asked() {
+    // Polygon::new closes rings, so an unclosed one can only come from deserializing
+    let unclosed: Vec<Geometry<f64>> = vec![Geometry::Polygon(
+        serde_json::from_str(
+            r#"{
+                "exterior": [{"x": 0.0, "y": 0.0}, {"x": 1.0, "y": 0.0}, {"x": 1.0, "y": 1.0}],
+                "interiors": []
+            }"#,
+        )
+        .unwrap(),
+    )];
+    let report = validate_geometries(&unclosed);
+    assert!(report.issues.contains(&ValidationIssue {
+        index: 0,
+        issue: GeometryIssue::UnclosedRing
+    }));
+
+    let options = RepairOptions {
+        close_rings: false,
+        ..RepairOptions::default()
+    };
+    let untouched = repair_geometries(&unclosed, &options);
+    assert_eq!(untouched, unclosed);
+
+    let closed = repair_geometries(&unclosed, &RepairOptions::default());
+    assert!(validate_geometries(&closed).is_valid());
+}
+
+#[test]
+fn validation_should_find_self_intersections_between_distant_segments() {
+    // A ring whose first segment is crossed by its fourth, and one folding back on itself
+    let crossing: Geometry<f64> = polygon![
+        (x: 0.0, y: 0.0),
+        (x: 4.0, y: 0.0),
+        (x: 4.0, y: 2.0),
+        (x: 2.0, y: -1.0),
+        (x: 0.0, y: 2.0)
+    ]
+    .into();
+    let spike: Geometry<f64> = polygon![
+        (x: 0.0, y: 0.0),
+        (x: 4.0, y: 0.0),
+        (x: 2.0, y: 0.0),
+        (x: 2.0, y: 2.0)
+    ]
+    .into();
+    let star: Geometry<f64> = polygon![
+        (x: 0.0, y: 0.0),
+        (x: 2.0, y: 1.0),
+        (x: 4.0, y: 0.0),
+        (x: 3.0, y: 2.0),
+        (x: 4.0, y: 4.0),
+        (x: 2.0, y: 3.0),
+        (x: 0.0, y: 4.0),
+        (x: 1.0, y: 2.0)
+    ]
+    .into();
+
+    let report = validate_geometries(&[crossing, spike, star]);
+    assert_eq!(report.invalid_indices(), vec![0, 1]);
+}
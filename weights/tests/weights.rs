@@ -1,4 +1,4 @@
-use geo_weights::Weights;
+use geo_weights::{TieBreak, TransformType, Weights};
 use std::collections::HashSet;
 
 #[test]
@@ -44,3 +44,62 @@ fn we_should_be_able_to_save_and_load_list_reps() {
 
     assert_eq!(weights.weights(), weights2.weights());
 }
+
+fn chain_weights() -> Weights {
+    // 0 - 1 - 2 - 3, plus 4 with no neighbors
+    let origins: Vec<usize> = vec![0, 1, 2];
+    let dests: Vec<usize> = vec![1, 2, 3];
+    let weights: Vec<f64> = vec![1.0, 1.0, 1.0];
+    Weights::from_list_rep(&origins, &dests, &weights, 5)
+}
+
+#[test]
+fn lag_should_compute_the_spatial_lag_with_each_transform() {
+    let weights = chain_weights();
+    let values = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+
+    let lag = weights.lag(&values, Some(TransformType::Row)).unwrap();
+    assert_eq!(lag, vec![2.0, 2.0, 3.0, 3.0, 0.0]);
+
+    let lag = weights.lag(&values, None).unwrap();
+    assert_eq!(lag, vec![2.0, 4.0, 6.0, 3.0, 0.0]);
+
+    let lag = weights
+        .lag(&values, Some(TransformType::DoublyStandardized))
+        .unwrap();
+    assert_eq!(lag, vec![2.0 / 6.0, 4.0 / 6.0, 1.0, 0.5, 0.0]);
+
+    assert!(weights.lag(&values[..2], None).is_err());
+}
+
+#[test]
+fn lag_columns_should_lag_each_column() {
+    let weights = chain_weights();
+    let columns = vec![vec![1.0, 2.0, 3.0, 4.0, 5.0], vec![1.0, 1.0, 1.0, 1.0, 1.0]];
+
+    let lags = weights
+        .lag_columns(&columns, Some(TransformType::Row))
+        .unwrap();
+    assert_eq!(lags[0], vec![2.0, 2.0, 3.0, 3.0, 0.0]);
+    assert_eq!(lags[1], vec![1.0, 1.0, 1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn lag_categorical_should_pick_the_most_common_neighboring_label() {
+    let weights = chain_weights();
+    let labels = vec!["b", "a", "b", "a", "c"];
+
+    let lag = weights.lag_categorical(&labels, TieBreak::Lowest).unwrap();
+    assert_eq!(lag, vec![Some("a"), Some("b"), Some("a"), Some("b"), None]);
+
+    let labels = vec!["a", "b", "c", "d", "e"];
+    let lag = weights.lag_categorical(&labels, TieBreak::Lowest).unwrap();
+    assert_eq!(lag[1], Some("a"));
+    let lag = weights
+        .lag_categorical(&labels, TieBreak::PreferOwn)
+        .unwrap();
+    assert_eq!(lag[1], Some("a"));
+    let lag = weights.lag_categorical(&labels, TieBreak::NoLabel).unwrap();
+    assert_eq!(lag[1], None);
+    assert_eq!(lag[0], Some("b"));
+}
@@ -0,0 +1,119 @@
+use geo_types::{polygon, Geometry, Point};
+use geo_weights::{DistanceMetric, KNNWeights, WeightsSpec};
+use std::collections::HashSet;
+
+#[test]
+fn weights_spec_should_deserialize_and_build_each_builder_type() {
+    let squares: Vec<Geometry<f64>> = vec![
+        polygon![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0), (x: 1.0, y: 1.0), (x: 0.0, y: 1.0)].into(),
+        polygon![(x: 1.0, y: 0.0), (x: 2.0, y: 0.0), (x: 2.0, y: 1.0), (x: 1.0, y: 1.0)].into(),
+        polygon![(x: 2.0, y: 1.0), (x: 3.0, y: 1.0), (x: 3.0, y: 2.0), (x: 2.0, y: 2.0)].into(),
+    ];
+
+    let queen: WeightsSpec<f64> =
+        serde_json::from_str(r#"{"type": "queen", "tolerance": 10000.0}"#).unwrap();
+    let weights = queen.build(&squares).unwrap();
+    assert_eq!(weights.get_neighbor_ids(1), Some(HashSet::from([0, 2])));
+
+    let rook: WeightsSpec<f64> =
+        serde_json::from_str(r#"{"type": "rook", "tolerance": 10000.0}"#).unwrap();
+    let weights = rook.build(&squares).unwrap();
+    assert_eq!(weights.get_neighbor_ids(1), Some(HashSet::from([0])));
+
+    let distance: WeightsSpec<f64> = serde_json::from_str(
+        r#"{"type": "distance", "cutoff_dist": 1.5, "use_distance_as_weight": false}"#,
+    )
+    .unwrap();
+    let weights = distance.build(&squares).unwrap();
+    assert_eq!(weights.get_neighbor_ids(0), Some(HashSet::from([1])));
+
+    let knn: WeightsSpec<f64> =
+        serde_json::from_str(r#"{"type": "knn", "k": 1, "metric": "haversine"}"#).unwrap();
+    let weights = knn.build(&squares).unwrap();
+    assert_eq!(weights.get_neighbor_ids(2), Some(HashSet::from([1])));
+}
+
+#[test]
+fn weights_spec_should_round_trip_through_json() {
+    let spec: WeightsSpec<f64> = WeightsSpec::Knn(KNNWeights::new(6, DistanceMetric::Haversine));
+    let json = serde_json::to_string(&spec).unwrap();
+    assert_eq!(json, r#"{"type":"knn","k":6,"metric":"haversine"}"#);
+
+    let points: Vec<Geometry<f64>> = vec![Point::new(0.0, 0.0).into()];
+    let parsed: WeightsSpec<f64> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.build(&points).unwrap().no_elements(), 1);
+}
+
+#[test]
+fn weights_spec_should_build_network_and_raster_weights() {
+    let points: Vec<Geometry<f64>> = vec![
+        Point::new(0.0, 0.0).into(),
+        Point::new(5.0, 0.0).into(),
+        Point::new(10.0, 0.0).into(),
+    ];
+
+    let network: WeightsSpec<f64> = serde_json::from_str(
+        r#"{
+            "type": "network",
+            "network": [[{"x": 0.0, "y": 0.0}, {"x": 5.0, "y": 0.0}, {"x": 10.0, "y": 0.0}]],
+            "tolerance": 1000.0,
+            "cutoff_dist": 6.0,
+            "weight_type": "binary"
+        }"#,
+    )
+    .unwrap();
+    let weights = network.build(&points).unwrap();
+    assert_eq!(weights.get_neighbor_ids(1), Some(HashSet::from([0, 2])));
+
+    let raster: WeightsSpec<f64> = serde_json::from_str(
+        r#"{
+            "type": "raster",
+            "rows": 1,
+            "cols": 3,
+            "cell_size": [1.0, 1.0],
+            "nodata_mask": null,
+            "contiguity": "rook"
+        }"#,
+    )
+    .unwrap();
+    let weights = raster.build(&points).unwrap();
+    assert_eq!(weights.get_neighbor_ids(1), Some(HashSet::from([0, 2])));
+    assert!(raster.build(&points[..2].to_vec()).is_err());
+}
+
+#[test]
+fn weights_spec_should_return_an_error_for_specs_that_can_not_build() {
+    let points: Vec<Geometry<f64>> = vec![Point::new(0.0, 0.0).into(), Point::new(1.0, 0.0).into()];
+
+    let distance: WeightsSpec<f64> = serde_json::from_str(
+        r#"{"type": "distance", "cutoff_dist": null, "use_distance_as_weight": false}"#,
+    )
+    .unwrap();
+    assert!(distance.build(&points).is_err());
+
+    let queen: WeightsSpec<f64> =
+        serde_json::from_str(r#"{"type": "queen", "tolerance": -1.0}"#).unwrap();
+    assert!(queen.build(&points).is_err());
+}
+
+#[test]
+fn weights_spec_should_reject_a_distance_given_as_the_tolerance() {
+    let points: Vec<Geometry<f64>> = vec![Point::new(0.0, 0.0).into(), Point::new(1.0, 0.0).into()];
+
+    for spec in [
+        r#"{"type": "queen", "tolerance": 0.0001}"#,
+        r#"{"type": "rook", "tolerance": 0.0001}"#,
+        r#"{
+            "type": "network",
+            "network": [[{"x": 0.0, "y": 0.0}, {"x": 1.0, "y": 0.0}]],
+            "tolerance": 0.0001,
+            "cutoff_dist": null,
+            "weight_type": {"kernel": {"function": "gaussian", "bandwidth": 1.0}}
+        }"#,
+    ] {
+        let spec: WeightsSpec<f64> = serde_json::from_str(spec).unwrap();
+        let error = spec.build(&points).unwrap_err();
+        assert!(error.contains("at least 1"), "{}", error);
+        assert!(spec.build_compact(&points).is_err());
+    }
+}